@@ -0,0 +1,193 @@
+//! Evaluation of arithmetic expansions `$((...))`.
+//!
+//! Expressions use 64-bit signed integers with the operators `+ - * / %`, unary `+`
+//! and `-`, and parentheses. Like in bash, overflow wraps around and `/` truncates
+//! towards zero. A bare name stands for the value of that variable, 0 when it is
+//! unset or empty.
+
+/// Errors of [`evaluate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ArithError {
+    /// The right operand of `/` or `%` is 0.
+    DivisionByZero,
+    /// The expression ended where an operand or `)` was expected.
+    UnexpectedEnd,
+    /// A character that doesn't fit the expression at this point.
+    UnexpectedChar(char),
+    /// A number that doesn't fit in 64 bits, or a variable whose value isn't a number.
+    BadValue(String),
+}
+
+impl std::fmt::Display for ArithError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArithError::DivisionByZero => write!(f, "division by zero"),
+            ArithError::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            ArithError::UnexpectedChar(c) => write!(f, "unexpected `{}`", c),
+            ArithError::BadValue(value) => write!(f, "not a number: {}", value),
+        }
+    }
+}
+
+/// Evaluate `expression`, looking up the values of variables with `lookup`.
+pub(crate) fn evaluate(
+    expression: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<i64, ArithError> {
+    let mut evaluator = Evaluator {
+        chars: expression.chars().collect(),
+        pos: 0,
+        lookup: &lookup,
+    };
+    let value = evaluator.sum()?;
+    match evaluator.peek() {
+        None => Ok(value),
+        Some(c) => Err(ArithError::UnexpectedChar(c)),
+    }
+}
+
+struct Evaluator<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    lookup: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl Evaluator<'_> {
+    /// The next character that isn't whitespace, without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    /// sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<i64, ArithError> {
+        let mut value = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.product()?;
+            value = match op {
+                '+' => value.wrapping_add(rhs),
+                _ => value.wrapping_sub(rhs),
+            };
+        }
+        Ok(value)
+    }
+
+    /// product := unary (('*' | '/' | '%') unary)*
+    fn product(&mut self) -> Result<i64, ArithError> {
+        let mut value = self.unary()?;
+        while let Some(op @ ('*' | '/' | '%')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err(ArithError::DivisionByZero),
+                '/' => value.wrapping_div(rhs),
+                _ => value.wrapping_rem(rhs),
+            };
+        }
+        Ok(value)
+    }
+
+    /// unary := ('+' | '-') unary | operand
+    fn unary(&mut self) -> Result<i64, ArithError> {
+        match self.peek() {
+            Some('+') => {
+                self.pos += 1;
+                self.unary()
+            }
+            Some('-') => {
+                self.pos += 1;
+                Ok(self.unary()?.wrapping_neg())
+            }
+            _ => self.operand(),
+        }
+    }
+
+    /// operand := number | name | '(' sum ')'
+    fn operand(&mut self) -> Result<i64, ArithError> {
+        match self.peek() {
+            None => Err(ArithError::UnexpectedEnd),
+            Some('(') => {
+                self.pos += 1;
+                let value = self.sum()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.pos += 1;
+                        Ok(value)
+                    }
+                    Some(c) => Err(ArithError::UnexpectedChar(c)),
+                    None => Err(ArithError::UnexpectedEnd),
+                }
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let digits = self.take_while(|c| c.is_ascii_alphanumeric());
+                digits.parse().map_err(|_| ArithError::BadValue(digits))
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let value = (self.lookup)(&name).unwrap_or_default();
+                match value.trim() {
+                    "" => Ok(0),
+                    number => number
+                        .parse()
+                        .map_err(|_| ArithError::BadValue(format!("{}={}", name, value))),
+                }
+            }
+            Some(c) => Err(ArithError::UnexpectedChar(c)),
+        }
+    }
+
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> String {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|&c| accept(c)) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> Result<i64, ArithError> {
+        evaluate(expression, |name| match name {
+            "X" => Some("4".to_string()),
+            "EMPTY" => Some(String::new()),
+            "WORD" => Some("abc".to_string()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_precedence_and_parentheses() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("10 - 4 - 3"), Ok(3));
+        assert_eq!(eval("7 / 2 + 7 % 2"), Ok(4));
+        assert_eq!(eval("-7 / 2"), Ok(-3));
+        assert_eq!(eval("- -(2)"), Ok(2));
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(eval("X + 1"), Ok(5));
+        assert_eq!(eval("X*X"), Ok(16));
+        assert_eq!(eval("UNSET + EMPTY"), Ok(0));
+        assert_eq!(eval("WORD"), Err(ArithError::BadValue("WORD=abc".to_string())));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(eval("1 / 0"), Err(ArithError::DivisionByZero));
+        assert_eq!(eval("1 % (X - 4)"), Err(ArithError::DivisionByZero));
+        assert_eq!(eval("1 +"), Err(ArithError::UnexpectedEnd));
+        assert_eq!(eval("(1"), Err(ArithError::UnexpectedEnd));
+        assert_eq!(eval("1 2"), Err(ArithError::UnexpectedChar('2')));
+        assert_eq!(eval("2 ** 3"), Err(ArithError::UnexpectedChar('*')));
+        assert_eq!(eval("12abc"), Err(ArithError::BadValue("12abc".to_string())));
+    }
+}
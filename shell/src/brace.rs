@@ -0,0 +1,234 @@
+//! Brace expansion: `{a,b,c}` alternatives and `{1..3}` or `{a..e}` ranges.
+//!
+//! It runs before any other expansion and only sees unquoted text, which the lexer
+//! keeps in [`WordPart::Glob`] parts, so quoted or escaped braces stay literal.
+
+use crate::lexer::WordPart;
+
+/// A piece of a word: an unquoted character, which may be a brace or comma taking part
+/// in an expansion, or any other part that is copied as is.
+#[derive(Debug, Clone)]
+enum Piece {
+    Char(char),
+    Part(WordPart),
+}
+
+/// Whether brace expansion may change the word made of `parts`.
+pub(crate) fn has_braces(parts: &[WordPart]) -> bool {
+    parts
+        .iter()
+        .any(|part| matches!(part, WordPart::Glob(text) if text.contains('{')))
+}
+
+/// Expand the braces of a word into the parts of the resulting words, left to right
+/// like bash: `{a,b}{1,2}` gives `a1 a2 b1 b2`. Words that end up empty are dropped.
+pub(crate) fn expand(parts: &[WordPart]) -> Vec<Vec<WordPart>> {
+    let mut pieces = Vec::new();
+    for part in parts {
+        match part {
+            WordPart::Glob(text) => pieces.extend(text.chars().map(Piece::Char)),
+            part => pieces.push(Piece::Part(part.clone())),
+        }
+    }
+    expand_pieces(pieces)
+        .into_iter()
+        .map(into_parts)
+        .filter(|parts| !parts.is_empty())
+        .collect()
+}
+
+fn expand_pieces(pieces: Vec<Piece>) -> Vec<Vec<Piece>> {
+    let mut search_from = 0;
+    while let Some(open) = find_char(&pieces, search_from, '{') {
+        search_from = open + 1;
+        let Some((close, commas)) = find_close(&pieces, open) else {
+            continue;
+        };
+        let inner = &pieces[open + 1..close];
+        let alternatives: Vec<Vec<Piece>> = if commas.is_empty() {
+            match range(inner) {
+                Some(values) => values
+                    .into_iter()
+                    .map(|value| value.chars().map(Piece::Char).collect())
+                    .collect(),
+                // Like `{a}` or `{}`, nothing to expand here
+                None => continue,
+            }
+        } else {
+            let mut bounds = vec![open];
+            bounds.extend(&commas);
+            bounds.push(close);
+            bounds
+                .windows(2)
+                .map(|bound| pieces[bound[0] + 1..bound[1]].to_vec())
+                .collect()
+        };
+
+        let prefix = &pieces[..open];
+        let suffix = &pieces[close + 1..];
+        return alternatives
+            .into_iter()
+            .flat_map(|alternative| {
+                let word = [prefix, &alternative, suffix].concat();
+                expand_pieces(word)
+            })
+            .collect();
+    }
+    vec![pieces]
+}
+
+fn find_char(pieces: &[Piece], from: usize, wanted: char) -> Option<usize> {
+    (from..pieces.len()).find(|&i| matches!(pieces[i], Piece::Char(c) if c == wanted))
+}
+
+/// Find the `}` matching the `{` at `open`, and the commas between them that aren't
+/// nested in other braces.
+fn find_close(pieces: &[Piece], open: usize) -> Option<(usize, Vec<usize>)> {
+    let mut depth = 0;
+    let mut commas = Vec::new();
+    for (i, piece) in pieces.iter().enumerate().skip(open + 1) {
+        match piece {
+            Piece::Char('{') => depth += 1,
+            Piece::Char('}') if depth == 0 => return Some((i, commas)),
+            Piece::Char('}') => depth -= 1,
+            Piece::Char(',') if depth == 0 => commas.push(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The values of a range like `1..5`, `5..1`, `0..10..2` or `a..e`, the text between
+/// the braces.
+fn range(inner: &[Piece]) -> Option<Vec<String>> {
+    let mut text = String::new();
+    for piece in inner {
+        match piece {
+            Piece::Char(c) => text.push(*c),
+            Piece::Part(_) => return None,
+        }
+    }
+    let bounds: Vec<&str> = text.split("..").collect();
+    let (start, end, step) = match bounds.as_slice() {
+        [start, end] => (*start, *end, 1),
+        [start, end, step] => (*start, *end, step.parse::<i64>().ok()?.unsigned_abs().max(1)),
+        _ => return None,
+    };
+
+    if let (Ok(start), Ok(end)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        return Some(stepped(start, end, step)?.map(|n| n.to_string()).collect());
+    }
+    let letter = |bound: &str| match bound.chars().collect::<Vec<_>>()[..] {
+        [c] if c.is_ascii_alphabetic() => Some(c as i64),
+        _ => None,
+    };
+    let (start, end) = (letter(start)?, letter(end)?);
+    Some(stepped(start, end, step)?.map(|c| (c as u8 as char).to_string()).collect())
+}
+
+/// The numbers from `start` to `end`, both included, counting up or down by `step`.
+/// `None` if there are too many of them to count.
+fn stepped(start: i64, end: i64, step: u64) -> Option<impl Iterator<Item = i64>> {
+    let count = usize::try_from(start.abs_diff(end) / step).ok()?.checked_add(1)?;
+    let next = move |n: &i64| {
+        if start <= end {
+            n.checked_add_unsigned(step)
+        } else {
+            n.checked_sub_unsigned(step)
+        }
+    };
+    Some(std::iter::successors(Some(start), next).take(count))
+}
+
+/// Turn expanded pieces back into word parts. Unquoted text stays a glob pattern only
+/// if it still contains glob metacharacters.
+fn into_parts(pieces: Vec<Piece>) -> Vec<WordPart> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let flush = |text: &mut String, parts: &mut Vec<WordPart>| {
+        if text.is_empty() {
+            return;
+        }
+        let text = std::mem::take(text);
+        if text.contains(['*', '?', '[']) {
+            parts.push(WordPart::Glob(text));
+        } else {
+            parts.push(WordPart::Literal(text));
+        }
+    };
+    for piece in pieces {
+        match piece {
+            Piece::Char(c) => text.push(c),
+            Piece::Part(part) => {
+                flush(&mut text, &mut parts);
+                parts.push(part);
+            }
+        }
+    }
+    flush(&mut text, &mut parts);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Expand the unquoted text `word` and render the resulting words.
+    fn expand_str(word: &str) -> Vec<String> {
+        expand(&[WordPart::Glob(word.to_string())])
+            .into_iter()
+            .map(|parts| parts.iter().map(WordPart::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_comma_lists() {
+        assert_eq!(expand_str("{a,b,c}"), ["a", "b", "c"]);
+        assert_eq!(expand_str("file.{txt,md}"), ["file.txt", "file.md"]);
+        assert_eq!(expand_str("x{a,}"), ["xa", "x"]);
+        assert_eq!(expand_str("{a,{b,c}d}"), ["a", "bd", "cd"]);
+        assert_eq!(expand_str("{a,b}*"), ["a*", "b*"]);
+    }
+
+    #[test]
+    fn test_cross_product() {
+        assert_eq!(expand_str("{a,b}{1,2}"), ["a1", "a2", "b1", "b2"]);
+        assert_eq!(expand_str("-{x,y}-{1..2}"), ["-x-1", "-x-2", "-y-1", "-y-2"]);
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert_eq!(expand_str("{1..3}"), ["1", "2", "3"]);
+        assert_eq!(expand_str("{3..1}"), ["3", "2", "1"]);
+        assert_eq!(expand_str("{-1..1}"), ["-1", "0", "1"]);
+        assert_eq!(expand_str("{0..10..4}"), ["0", "4", "8"]);
+        assert_eq!(expand_str("{a..c}"), ["a", "b", "c"]);
+        assert_eq!(expand_str("{1..c}"), ["{1..c}"]);
+
+        // Ranges with more numbers than can be counted stay as they are
+        let huge = "{-9223372036854775808..9223372036854775807}";
+        assert_eq!(expand_str(huge), [huge]);
+        let ends = "{-9223372036854775808..9223372036854775807..9223372036854775807}";
+        assert_eq!(expand_str(ends), ["-9223372036854775808", "-1", "9223372036854775806"]);
+        let down = expand_str("{1..-9223372036854775808..-9223372036854775808}");
+        assert_eq!(down, ["1", "-9223372036854775807"]);
+    }
+
+    #[test]
+    fn test_braces_without_expansion_stay_literal() {
+        assert_eq!(expand_str("{}"), ["{}"]);
+        assert_eq!(expand_str("{a}"), ["{a}"]);
+        assert_eq!(expand_str("{a,b"), ["{a,b"]);
+        assert_eq!(expand_str("{a}{b,c}"), ["{a}b", "{a}c"]);
+
+        // A quoted comma doesn't separate alternatives
+        let parts = [
+            WordPart::Glob("{a".to_string()),
+            WordPart::Literal(",".to_string()),
+            WordPart::Glob("b}".to_string()),
+        ];
+        let words = expand(&parts);
+        assert_eq!(words.len(), 1);
+        assert_eq!(words[0].iter().map(WordPart::to_string).collect::<String>(), "{a,b}");
+    }
+}
@@ -1,15 +1,20 @@
-use crate::command::{CommandFactory, ExecutableCommand, ExitCode, Stdin, Stdout};
+use crate::command::{CommandFactory, CommandKind, ExecutableCommand, ExitCode, Stdin, Stdout};
 use crate::env::Environment;
+use crate::external::{ExternalCommand, find_command_path};
 use crate::interpreter::Factory;
 use anyhow::{Context, Result};
 use argh::{EarlyExit, FromArgs};
 use regex::RegexBuilder;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
 use std::env;
-use std::fs;
-use std::io::BufRead;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufRead};
 use std::io::BufReader;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
 
 /// Built-in commands known to the shell at compile time.
 ///
@@ -21,11 +26,14 @@ pub(crate) trait BuiltinCommand: Sized + FromArgs {
 
     /// Executes the command using provided IO streams and environment.
     ///
-    /// Return value should follow shell conventions: 0 for success, non-zero for error.
+    /// Data goes to `stdout`, diagnostics to `stderr`. An error returned from here is
+    /// reported on `stderr` too. Return value should follow shell conventions:
+    /// 0 for success, non-zero for error.
     fn execute(
         self,
         stdin: &mut dyn Read,
         stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
         env: &mut Environment,
     ) -> Result<ExitCode>;
 }
@@ -35,12 +43,17 @@ impl<T: BuiltinCommand> ExecutableCommand for T {
         self: Box<Self>,
         mut stdin: Box<dyn Stdin>,
         mut stdout: Box<dyn Stdout>,
+        mut stderr: Box<dyn Stdout>,
         env: &mut Environment,
     ) -> Result<ExitCode> {
-        match T::execute(*self, &mut stdin, &mut stdout, env) {
+        let result = T::execute(*self, &mut stdin, &mut stdout, &mut stderr, env);
+        // Output without a trailing newline would otherwise wait in the line buffer of the
+        // process stdout, and show up after the output of programs started later
+        stdout.flush()?;
+        match result {
             Ok(x) => Ok(x),
             Err(e) => {
-                stdout.write_all(e.to_string().as_bytes())?;
+                writeln!(stderr, "{}", e)?;
                 Ok(1)
             }
         }
@@ -57,10 +70,17 @@ impl ExecutableCommand for InvalidArgs {
         self: Box<Self>,
         _stdin: Box<dyn Stdin>,
         mut stdout: Box<dyn Stdout>,
+        mut stderr: Box<dyn Stdout>,
         _env: &mut Environment,
     ) -> anyhow::Result<i32> {
-        stdout.write_all(self.output.as_bytes())?;
-        Ok(if self.is_error { 1 } else { 0 })
+        // `--help` output is regular output, a usage error is a diagnostic
+        if self.is_error {
+            stderr.write_all(self.output.as_bytes())?;
+            Ok(1)
+        } else {
+            stdout.write_all(self.output.as_bytes())?;
+            Ok(0)
+        }
     }
 }
 
@@ -72,7 +92,15 @@ impl<T: BuiltinCommand + 'static> CommandFactory for Factory<T> {
         args: &[&str],
     ) -> Option<Box<dyn ExecutableCommand>> {
         if name == T::name() {
-            Some(match T::from_args(&[name], args) {
+            // argh rejects a lone `-` as an unknown flag, although it is a conventional
+            // operand (e.g. `cd -`). Operands come after options, so pass it after `--`.
+            let mut args = args.to_vec();
+            if let Some(pos) = args.iter().position(|x| *x == "-" || *x == "--")
+                && args[pos] == "-"
+            {
+                args.insert(pos, "--");
+            }
+            Some(match T::from_args(&[name], &args) {
                 Ok(cmd) => Box::new(cmd),
                 Err(EarlyExit { output, status }) => Box::new(InvalidArgs {
                     output,
@@ -83,6 +111,18 @@ impl<T: BuiltinCommand + 'static> CommandFactory for Factory<T> {
             None
         }
     }
+
+    fn builtin_name(&self) -> Option<&'static str> {
+        Some(T::name())
+    }
+
+    /// The first line of the command's doc comment, taken from its `--help` output.
+    fn description(&self) -> Option<String> {
+        let help = T::from_args(&[T::name()], &["--help"]).err()?.output;
+        // The output starts with a usage line followed by a blank line
+        let description = help.lines().skip(1).find(|line| !line.trim().is_empty())?;
+        Some(description.trim().to_string())
+    }
 }
 
 #[derive(FromArgs)]
@@ -98,6 +138,7 @@ impl BuiltinCommand for Pwd {
         self,
         _stdin: &mut dyn Read,
         stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
         env: &mut Environment,
     ) -> Result<ExitCode> {
         writeln!(stdout, "{}", env.current_dir.to_string_lossy())?;
@@ -110,7 +151,8 @@ impl BuiltinCommand for Pwd {
 /// If no target is provided, changes to the directory specified by the HOME environment variable.
 pub struct Cd {
     #[argh(positional)]
-    /// directory to switch to; absolute or relative to the current directory. Defaults to $HOME when omitted.
+    /// directory to switch to; absolute, relative to a directory listed in $CDPATH or to the
+    /// current directory. Defaults to $HOME when omitted.
     pub target: Option<String>,
 }
 
@@ -122,11 +164,26 @@ impl BuiltinCommand for Cd {
     fn execute(
         self,
         _stdin: &mut dyn Read,
-        _stdout: &mut dyn Write,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
         env: &mut Environment,
     ) -> Result<ExitCode> {
+        let mut print_dir = self.target.as_deref() == Some("-");
         let target = match &self.target {
-            Some(t) if !t.is_empty() => PathBuf::from(t),
+            Some(t) if t == "-" => match env.get_var("OLDPWD") {
+                Some(old) => PathBuf::from(old),
+                None => {
+                    writeln!(stderr, "cd: OLDPWD not set")?;
+                    return Ok(1);
+                }
+            },
+            Some(t) if !t.is_empty() => match search_cdpath(env, t) {
+                Some((dir, from_cdpath)) => {
+                    print_dir = from_cdpath;
+                    dir
+                }
+                None => PathBuf::from(t),
+            },
             _ => {
                 if let Some(home) = env.get_var("HOME") {
                     PathBuf::from(home)
@@ -136,18 +193,198 @@ impl BuiltinCommand for Cd {
             }
         };
 
-        let new_dir = if target.is_absolute() {
-            target
-        } else {
-            env.current_dir.join(target)
+        change_dir(env, "cd", target)?;
+        if print_dir {
+            writeln!(stdout, "{}", env.current_dir.display())?;
+        }
+        Ok(0)
+    }
+}
+
+/// Look a relative `target` that doesn't start with `.` or `..` up in the directories
+/// listed in `$CDPATH`, like bash. An empty entry stands for the current directory.
+///
+/// Returns the directory found and whether it came from a non-empty entry, in which case
+/// `cd` prints where it went.
+fn search_cdpath(env: &Environment, target: &str) -> Option<(PathBuf, bool)> {
+    if !matches!(Path::new(target).components().next(), Some(Component::Normal(_))) {
+        return None;
+    }
+    let cdpath = env.get_var("CDPATH")?;
+    cdpath.split(':').find_map(|dir| {
+        let candidate = env.current_dir.join(dir).join(target);
+        candidate.is_dir().then_some((candidate, !dir.is_empty()))
+    })
+}
+
+/// Make `target` (absolute or relative to the current directory) the working directory
+/// and update `OLDPWD` and `PWD`. Returns the previous working directory.
+///
+/// `command` is used as the prefix of error messages.
+fn change_dir(env: &mut Environment, command: &str, target: PathBuf) -> Result<PathBuf> {
+    let new_dir = if target.is_absolute() {
+        target
+    } else {
+        env.current_dir.join(target)
+    };
+
+    let canonical = fs::canonicalize(&new_dir)
+        .with_context(|| format!("{}: can't canonicalize {}", command, new_dir.display()))?;
+
+    env::set_current_dir(&canonical)
+        .with_context(|| format!("{}: can't chdir to {}", command, canonical.display()))?;
+    let previous = std::mem::replace(&mut env.current_dir, canonical);
+    env.set_var("OLDPWD", previous.to_string_lossy());
+    env.set_var("PWD", env.current_dir.to_string_lossy().into_owned());
+    Ok(previous)
+}
+
+/// Print the current directory followed by the directory stack, most recent first.
+fn write_dir_stack(stdout: &mut dyn Write, env: &Environment) -> Result<()> {
+    let dirs: Vec<String> = std::iter::once(&env.current_dir)
+        .chain(env.dir_stack.iter().rev())
+        .map(|dir| dir.display().to_string())
+        .collect();
+    writeln!(stdout, "{}", dirs.join(" "))?;
+    Ok(())
+}
+
+#[derive(FromArgs)]
+/// Save the current directory on the directory stack and change to another one.
+pub struct Pushd {
+    #[argh(positional)]
+    /// directory to switch to; absolute or relative to the current directory.
+    pub dir: String,
+}
+
+impl BuiltinCommand for Pushd {
+    fn name() -> &'static str {
+        "pushd"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let previous = change_dir(env, "pushd", PathBuf::from(self.dir))?;
+        env.dir_stack.push(previous);
+        write_dir_stack(stdout, env)?;
+        Ok(0)
+    }
+}
+
+#[derive(FromArgs)]
+/// Remove the top directory from the directory stack and change to it.
+pub struct Popd {}
+
+impl BuiltinCommand for Popd {
+    fn name() -> &'static str {
+        "popd"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let Some(dir) = env.dir_stack.last().cloned() else {
+            writeln!(stderr, "popd: directory stack empty")?;
+            return Ok(1);
         };
+        change_dir(env, "popd", dir)?;
+        env.dir_stack.pop();
+        write_dir_stack(stdout, env)?;
+        Ok(0)
+    }
+}
+
+#[derive(FromArgs)]
+/// Print the current directory followed by the directory stack.
+pub struct Dirs {}
+
+impl BuiltinCommand for Dirs {
+    fn name() -> &'static str {
+        "dirs"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        write_dir_stack(stdout, env)?;
+        Ok(0)
+    }
+}
+
+#[derive(FromArgs)]
+/// List the background jobs.
+pub struct Jobs {}
+
+impl BuiltinCommand for Jobs {
+    fn name() -> &'static str {
+        "jobs"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let finished = env.jobs.reap();
+        let running = env.jobs.list();
+        let mut lines: Vec<(usize, String)> = finished
+            .into_iter()
+            .map(|(id, command, code)| (id, job_status_line(id, &command, Some(code))))
+            .chain(
+                running
+                    .into_iter()
+                    .map(|(id, command)| (id, job_status_line(id, &command, None))),
+            )
+            .collect();
+        lines.sort();
+        for (_, line) in lines {
+            writeln!(stdout, "{}", line)?;
+        }
+        Ok(0)
+    }
+}
 
-        let canonical = fs::canonicalize(&new_dir)
-            .with_context(|| format!("cd: can't canonicalize {}", new_dir.display()))?;
+/// Describe a background job like `[1] Running sleep 5`; `code` is `None` while it runs.
+pub(crate) fn job_status_line(id: usize, command: &str, code: Option<ExitCode>) -> String {
+    match code {
+        None => format!("[{}] Running {}", id, command),
+        Some(0) => format!("[{}] Done {}", id, command),
+        Some(code) => format!("[{}] Exit {} {}", id, code, command),
+    }
+}
+
+#[derive(FromArgs)]
+/// Wait for all background jobs to finish.
+pub struct Wait {}
 
-        env::set_current_dir(&canonical)
-            .with_context(|| format!("cd: can't chdir to {}", canonical.display()))?;
-        env.current_dir = canonical;
+impl BuiltinCommand for Wait {
+    fn name() -> &'static str {
+        "wait"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        env.jobs.wait_all();
         Ok(0)
     }
 }
@@ -156,10 +393,12 @@ impl BuiltinCommand for Cd {
 /// Exit shell process
 pub struct Exit {
     #[argh(positional, greedy)]
-    /// hack to ignore arguments, but actually should accept 0-255 value
-    pub _args: Vec<String>,
+    /// exit status in range 0-255; defaults to 0 when omitted.
+    pub args: Vec<String>,
 }
 
+/// Only asks the shell to stop via `env.should_exit`; the status is returned as the
+/// command's exit code, so the caller decides whether to terminate the process.
 impl BuiltinCommand for Exit {
     fn name() -> &'static str {
         "exit"
@@ -169,13 +408,34 @@ impl BuiltinCommand for Exit {
         self,
         _stdin: &mut dyn Read,
         _stdout: &mut dyn Write,
-        _env: &mut Environment,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
     ) -> Result<ExitCode> {
-        std::process::exit(0)
+        env.should_exit = true;
+        match parse_exit_code(&self.args) {
+            Ok(code) => Ok(code),
+            Err(msg) => {
+                writeln!(stderr, "{}", msg)?;
+                Ok(2)
+            }
+        }
     }
 }
 
-#[derive(FromArgs)]
+/// Parse the status code passed to `exit`.
+///
+/// No arguments means 0. Otherwise the first argument must fit into `u8`, like in bash.
+pub(crate) fn parse_exit_code(args: &[String]) -> std::result::Result<i32, String> {
+    match args.first() {
+        None => Ok(0),
+        Some(arg) => arg
+            .parse::<u8>()
+            .map(i32::from)
+            .map_err(|_| "exit: numeric argument required".to_string()),
+    }
+}
+
+#[derive(FromArgs, Default)]
 /// write the arguments to standard output, separated by spaces.
 /// by default, a trailing newline is printed.
 pub struct Echo {
@@ -183,6 +443,14 @@ pub struct Echo {
     /// do not output the trailing newline.
     pub no_newline: bool,
 
+    #[argh(switch, short = 'e')]
+    /// enable interpretation of backslash escapes.
+    pub escapes: bool,
+
+    #[argh(switch, short = 'E')]
+    /// disable interpretation of backslash escapes (default), overrides -e.
+    pub no_escapes: bool,
+
     #[argh(positional, greedy)]
     /// values to print as-is, separated by spaces.
     pub args: Vec<String>,
@@ -197,9 +465,13 @@ impl BuiltinCommand for Echo {
         self,
         _stdin: &mut dyn Read,
         stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
         _env: &mut Environment,
     ) -> Result<ExitCode> {
-        let s = self.args.join(" ");
+        let mut s = self.args.join(" ");
+        if self.escapes && !self.no_escapes {
+            s = interpret_escapes(&s);
+        }
         if self.no_newline {
             write!(stdout, "{}", s)?;
         } else {
@@ -209,13 +481,153 @@ impl BuiltinCommand for Echo {
     }
 }
 
-#[derive(argh::FromArgs)]
+/// Translates the backslash escapes supported by `echo -e` into control characters.
+/// Unknown escapes are printed as is, backslash included.
+fn interpret_escapes(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('0') => result.push('\0'),
+            Some('a') => result.push('\x07'),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[derive(argh::FromArgs, Default)]
 /// count lines, words and bytes
 pub struct WC {
+    #[argh(switch, short = 'l')]
+    /// print the newline counts
+    pub lines: bool,
+
+    #[argh(switch, short = 'w')]
+    /// print the word counts
+    pub words: bool,
+
+    #[argh(switch, short = 'm')]
+    /// print the character counts
+    pub chars: bool,
+
+    #[argh(switch, short = 'c')]
+    /// print the byte counts
+    pub bytes: bool,
+
+    #[argh(switch, short = 'L')]
+    /// print the length of the longest line, in display columns
+    pub max_line_length: bool,
+
     #[argh(positional, greedy)]
     pub files: Vec<String>,
 }
 
+/// Counts collected by `wc` for a single input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct WcCounts {
+    lines: usize,
+    words: usize,
+    chars: usize,
+    bytes: usize,
+    max_line_length: usize,
+}
+
+/// Width of `line` in display columns. A tab advances to the next multiple of 8,
+/// every other character takes one column.
+fn line_width(line: &str) -> usize {
+    line.chars().fold(0, |width, c| match c {
+        '\t' => (width / 8 + 1) * 8,
+        _ => width + 1,
+    })
+}
+
+impl WcCounts {
+    /// Counts raw bytes, so input that isn't valid UTF-8 is fine too. Invalid sequences
+    /// count as one character each, like the replacement characters they decode to.
+    fn of(data: &[u8]) -> Self {
+        let newlines = data.iter().filter(|&&b| b == b'\n').count();
+        // A last line without a trailing newline still counts as a line
+        let unterminated = !data.is_empty() && !data.ends_with(b"\n");
+        let text = String::from_utf8_lossy(data);
+        Self {
+            lines: newlines + usize::from(unterminated),
+            words: data
+                .split(|b| b.is_ascii_whitespace())
+                .filter(|word| !word.is_empty())
+                .count(),
+            chars: text.chars().count(),
+            bytes: data.len(),
+            max_line_length: text.lines().map(line_width).max().unwrap_or(0),
+        }
+    }
+
+    fn add(&mut self, other: &WcCounts) {
+        self.lines += other.lines;
+        self.words += other.words;
+        self.chars += other.chars;
+        self.bytes += other.bytes;
+        self.max_line_length = self.max_line_length.max(other.max_line_length);
+    }
+}
+
+impl WC {
+    /// Selects the requested counts in the conventional order: lines, words, characters,
+    /// bytes, longest line. Without any flag selects lines, words and bytes.
+    fn columns(&self, counts: &WcCounts) -> Vec<usize> {
+        let show_all =
+            !(self.lines || self.words || self.chars || self.bytes || self.max_line_length);
+        let mut columns = Vec::new();
+        if show_all || self.lines {
+            columns.push(counts.lines);
+        }
+        if show_all || self.words {
+            columns.push(counts.words);
+        }
+        if self.chars {
+            columns.push(counts.chars);
+        }
+        if show_all || self.bytes {
+            columns.push(counts.bytes);
+        }
+        if self.max_line_length {
+            columns.push(counts.max_line_length);
+        }
+        columns
+    }
+
+    /// Formats the requested counts right-aligned to `width`, like GNU wc does
+    /// so that the columns of several lines match up.
+    fn format_counts(&self, counts: &WcCounts, width: usize) -> String {
+        self.columns(counts)
+            .iter()
+            .map(|x| format!("{:>width$}", x, width = width))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The width of the widest count printed for any of `rows`.
+    fn column_width<'a>(&self, rows: impl IntoIterator<Item = &'a WcCounts>) -> usize {
+        rows.into_iter()
+            .flat_map(|counts| self.columns(counts))
+            .map(|x| x.to_string().len())
+            .max()
+            .unwrap_or(1)
+    }
+}
+
 impl BuiltinCommand for WC {
     fn name() -> &'static str {
         "wc"
@@ -225,39 +637,130 @@ impl BuiltinCommand for WC {
         self,
         stdin: &mut dyn Read,
         stdout: &mut dyn Write,
-        _env: &mut Environment,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
     ) -> Result<ExitCode> {
         use std::io::Read;
         if self.files.is_empty() {
-            let mut buf = String::new();
-            stdin.read_to_string(&mut buf)?;
-            let lines = buf.lines().count();
-            let words = buf.split_whitespace().count();
-            let bytes = buf.as_bytes().len();
-            writeln!(stdout, "{} {} {}", lines, words, bytes)?;
+            let mut buf = Vec::new();
+            stdin.read_to_end(&mut buf)?;
+            let counts = WcCounts::of(&buf);
+            let width = self.column_width([&counts]);
+            writeln!(stdout, "{}", self.format_counts(&counts, width))?;
             return Ok(0);
         }
-        for fname in self.files {
-            let mut f =
-                std::fs::File::open(&fname).map_err(|e| anyhow::anyhow!("wc: {}: {}", fname, e))?;
-            let mut s = String::new();
-            f.read_to_string(&mut s)?;
-            let lines = s.lines().count();
-            let words = s.split_whitespace().count();
-            let bytes = s.as_bytes().len();
-            writeln!(stdout, "{} {} {} {}", lines, words, bytes, fname)?;
+        // All files are counted before printing anything, so the widths are known
+        let mut rows = Vec::new();
+        let mut total = WcCounts::default();
+        let mut final_exit_code = 0;
+        for fname in &self.files {
+            let mut data = Vec::new();
+            let read = std::fs::File::open(env.current_dir.join(fname))
+                .and_then(|mut f| f.read_to_end(&mut data));
+            if let Err(e) = read {
+                writeln!(stderr, "wc: {}: {}", fname, e)?;
+                final_exit_code = 1;
+                continue;
+            }
+            let counts = WcCounts::of(&data);
+            total.add(&counts);
+            rows.push((counts, fname.as_str()));
+        }
+        // Like GNU wc, the total only covers the files that could be read
+        let show_total = self.files.len() > 1;
+        let width = self.column_width(
+            rows.iter()
+                .map(|(counts, _)| counts)
+                .chain(show_total.then_some(&total)),
+        );
+        for (counts, fname) in &rows {
+            writeln!(stdout, "{} {}", self.format_counts(counts, width), fname)?;
+        }
+        if show_total {
+            writeln!(stdout, "{} total", self.format_counts(&total, width))?;
         }
-        Ok(0)
+        Ok(final_exit_code)
     }
 }
 
-#[derive(argh::FromArgs)]
+#[derive(argh::FromArgs, Default)]
 /// print file(s) to stdout
 pub struct Cat {
+    #[argh(switch, short = 'n')]
+    /// number all output lines
+    pub number: bool,
+
+    #[argh(switch, short = 'b')]
+    /// number non-empty output lines, overrides -n
+    pub number_nonblank: bool,
+
+    #[argh(switch, short = 's')]
+    /// suppress repeated empty output lines
+    pub squeeze_blank: bool,
+
+    #[argh(switch, short = 'E')]
+    /// display $ at end of each line
+    pub show_ends: bool,
+
     #[argh(positional, greedy)]
     pub files: Vec<String>,
 }
 
+/// Line processing state of `cat` that carries over from one input to the next.
+#[derive(Default)]
+struct CatState {
+    line_num: usize,
+    prev_blank: bool,
+}
+
+impl Cat {
+    /// Whether the output has to be produced line by line instead of copied as is.
+    fn needs_line_processing(&self) -> bool {
+        self.number || self.number_nonblank || self.squeeze_blank || self.show_ends
+    }
+
+    /// Copies `reader` to `stdout` line by line, applying the requested formatting.
+    /// `state` is shared between inputs so numbering and squeezing continue across files.
+    fn copy_lines(
+        &self,
+        reader: &mut dyn Read,
+        stdout: &mut dyn Write,
+        state: &mut CatState,
+    ) -> Result<()> {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let is_blank = line == b"\n";
+            if self.squeeze_blank && is_blank && state.prev_blank {
+                continue;
+            }
+            state.prev_blank = is_blank;
+
+            let numbered = if self.number_nonblank {
+                !is_blank
+            } else {
+                self.number
+            };
+            if numbered {
+                state.line_num += 1;
+                write!(stdout, "{:>6}\t", state.line_num)?;
+            }
+            match line.strip_suffix(b"\n") {
+                Some(content) if self.show_ends => {
+                    stdout.write_all(content)?;
+                    stdout.write_all(b"$\n")?;
+                }
+                _ => stdout.write_all(&line)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 impl BuiltinCommand for Cat {
     fn name() -> &'static str {
         "cat"
@@ -265,35 +768,146 @@ impl BuiltinCommand for Cat {
 
     fn execute(
         self,
-        _stdin: &mut dyn Read,
+        stdin: &mut dyn Read,
         stdout: &mut dyn Write,
-        _env: &mut Environment,
+        _stderr: &mut dyn Write,
+        env: &mut Environment,
     ) -> Result<ExitCode> {
+        let mut state = CatState::default();
         if self.files.is_empty() {
-            // read stdin to stdout
-            let mut buf = String::new();
-            _stdin.read_to_string(&mut buf)?;
-            write!(stdout, "{}", buf)?;
+            if self.needs_line_processing() {
+                self.copy_lines(stdin, stdout, &mut state)?;
+                return Ok(0);
+            }
+            // Stream stdin to stdout as raw bytes
+            std::io::copy(stdin, stdout)?;
             return Ok(0);
         }
-        for fname in self.files {
-            let mut f = std::fs::File::open(&fname)
+        for fname in &self.files {
+            let mut f = std::fs::File::open(env.current_dir.join(fname))
                 .map_err(|e| anyhow::anyhow!("cat: {}: {}", fname, e))?;
-            std::io::copy(&mut f, stdout)?;
+            if self.needs_line_processing() {
+                self.copy_lines(&mut f, stdout, &mut state)?;
+            } else {
+                std::io::copy(&mut f, stdout)?;
+            }
         }
         Ok(0)
     }
 }
 
+/// Print the environment or run a command in a modified copy of it, like POSIX `env`.
+/// `-u NAME` removes a variable before the assignments are applied.
+///
+/// Unlike other builtins, `env` has to hand its streams over to the program it runs,
+/// so it implements [`ExecutableCommand`] directly instead of [`BuiltinCommand`].
+pub struct Env {
+    unset: Vec<String>,
+    assignments: Vec<(String, String)>,
+    argv: Vec<String>,
+    /// `-u` was given without a variable name
+    missing_name: bool,
+}
+
+impl CommandFactory for Factory<Env> {
+    fn try_create(
+        &self,
+        _env: &Environment,
+        name: &str,
+        args: &[&str],
+    ) -> Option<Box<dyn ExecutableCommand>> {
+        if name != "env" {
+            return None;
+        }
+        let mut env = Env {
+            unset: Vec::new(),
+            assignments: Vec::new(),
+            argv: Vec::new(),
+            missing_name: false,
+        };
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            if *arg == "-u" {
+                match rest.next() {
+                    Some(name) => env.unset.push(name.to_string()),
+                    None => env.missing_name = true,
+                }
+                continue;
+            }
+            match arg.split_once('=') {
+                Some((key, value)) if !key.is_empty() => {
+                    env.assignments.push((key.to_string(), value.to_string()))
+                }
+                _ => {
+                    env.argv = std::iter::once(arg).chain(rest).map(|x| x.to_string()).collect();
+                    break;
+                }
+            }
+        }
+        Some(Box::new(env))
+    }
+
+    fn builtin_name(&self) -> Option<&'static str> {
+        Some("env")
+    }
+
+    fn description(&self) -> Option<String> {
+        Some("print the environment or run a command in a modified one".to_string())
+    }
+}
+
+impl ExecutableCommand for Env {
+    fn execute(
+        self: Box<Self>,
+        stdin: Box<dyn Stdin>,
+        mut stdout: Box<dyn Stdout>,
+        mut stderr: Box<dyn Stdout>,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        if self.missing_name {
+            writeln!(stderr, "env: option requires an argument -- 'u'")?;
+            return Ok(125);
+        }
+        let mut local_env = env.clone();
+        for name in &self.unset {
+            local_env.remove_var(name);
+        }
+        for (key, value) in self.assignments {
+            local_env.set_var(key, value);
+        }
+
+        let Some((name, args)) = self.argv.split_first() else {
+            let mut vars: Vec<_> = local_env.vars.iter().collect();
+            vars.sort();
+            for (key, value) in vars {
+                writeln!(stdout, "{}={}", key, value)?;
+            }
+            return Ok(0);
+        };
+
+        let search_paths = local_env.get_var("PATH").unwrap_or_default();
+        match find_command_path(OsStr::new(&search_paths), Path::new(name)) {
+            Some(executable) => {
+                let cmd = ExternalCommand::new(
+                    executable.as_os_str().to_owned(),
+                    args.iter().map(|x| x.into()).collect(),
+                );
+                Box::new(cmd).execute(stdin, stdout, stderr, &mut local_env)
+            }
+            None => {
+                writeln!(stderr, "env: {}: No such file or directory", name)?;
+                Ok(127)
+            }
+        }
+    }
+}
+
 #[derive(argh::FromArgs)]
 /// print lines matching a pattern
 pub struct Grep {
-    #[argh(positional)]
-    /// the pattern to search for (a regular expression)
-    pub pattern: String,
-
     #[argh(positional, greedy)]
-    /// files to search. If none provided, reads from stdin.
+    /// the pattern to search for (a regular expression) unless given with -e, followed by
+    /// the files to search. If no files are provided, reads from stdin.
     pub files: Vec<String>,
 
     #[argh(switch, short = 'w')]
@@ -307,79 +921,249 @@ pub struct Grep {
     #[argh(option, short = 'A', default = "0")]
     /// print NUM lines of trailing context after matching lines
     pub after_context: usize,
-}
 
-impl Grep {
-    fn process_source(
-        &self,
-        reader: &mut dyn Read,
-        stdout: &mut dyn Write,
-        file_name: Option<&str>,
-        re: &regex::Regex,
-    ) -> Result<ExitCode> {
-        let mut reader = BufReader::new(reader);
+    #[argh(option, short = 'B', default = "0")]
+    /// print NUM lines of leading context before matching lines
+    pub before_context: usize,
 
-        let mut lines = Vec::new();
-        let mut match_indices = Vec::new();
+    #[argh(option, short = 'C', default = "0")]
+    /// print NUM lines of context both before and after matching lines
+    pub context: usize,
 
-        let mut line_num = 0;
-        loop {
-            let mut line = String::new();
+    #[argh(switch, short = 'v')]
+    /// select non-matching lines
+    pub invert_match: bool,
 
-            let bytes_read = match reader.read_line(&mut line) {
-                Ok(n) => n,
-                Err(e) => return Err(anyhow::anyhow!("read error: {}", e)),
-            };
+    #[argh(switch, short = 'n')]
+    /// prefix each output line with its 1-based line number
+    pub line_number: bool,
 
-            if bytes_read == 0 {
-                break;
-            }
+    #[argh(switch, short = 'c')]
+    /// print only a count of selected lines per file
+    pub count: bool,
 
-            if re.is_match(&line) {
-                match_indices.push(line_num);
-            }
+    #[argh(switch, short = 'r')]
+    /// search directories recursively, without following symbolic links
+    pub recursive: bool,
 
-            lines.push(line);
-            line_num += 1;
-        }
+    #[argh(switch, short = 'l')]
+    /// print only the names of files containing a selected line
+    pub files_with_matches: bool,
 
-        if lines.is_empty() {
-            return Ok(0);
-        }
+    #[argh(switch, short = 'F')]
+    /// interpret the pattern as a fixed string, not a regular expression
+    pub fixed_string: bool,
+
+    #[argh(switch, short = 'o')]
+    /// print only the matched parts of lines, one per line; context options are ignored
+    pub only_matching: bool,
+
+    #[argh(option, short = 'e')]
+    /// a pattern to search for; can be repeated to select lines matching any of them
+    pub patterns: Vec<String>,
+
+    #[argh(option, short = 'm')]
+    /// stop reading a file after NUM selected lines, printing only the trailing context
+    /// of the last one
+    pub max_count: Option<usize>,
+
+    #[argh(option, default = "GrepColor::Never", from_str_fn(parse_grep_color))]
+    /// highlight matches: never (the default), always, or auto to do it only on a terminal
+    pub color: GrepColor,
+}
 
-        let total_lines = lines.len();
-        let mut to_print = vec![false; total_lines];
-        let context_end = self.after_context;
+/// When `grep` highlights the matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GrepColor {
+    Never,
+    Always,
+    /// Only when stdout is a terminal.
+    Auto,
+}
+
+fn parse_grep_color(value: &str) -> std::result::Result<GrepColor, String> {
+    match value {
+        "never" => Ok(GrepColor::Never),
+        "always" => Ok(GrepColor::Always),
+        "auto" => Ok(GrepColor::Auto),
+        _ => Err(format!("invalid color mode: '{}'", value)),
+    }
+}
 
-        for &match_line in &match_indices {
-            let start = match_line;
-            let end_print = (match_line + context_end + 1).min(total_lines);
+/// Escape sequences that make a match bold red, and that reset the style after it.
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const RESET_COLOR: &str = "\x1b[0m";
+
+/// Wrap the non-empty matches of `re` in `line` in [`MATCH_COLOR`] and [`RESET_COLOR`].
+fn highlight_matches(line: &str, re: &regex::Regex) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut last_end = 0;
+    for found in re.find_iter(line).filter(|found| !found.is_empty()) {
+        result.push_str(&line[last_end..found.start()]);
+        result.push_str(MATCH_COLOR);
+        result.push_str(found.as_str());
+        result.push_str(RESET_COLOR);
+        last_end = found.end();
+    }
+    result.push_str(&line[last_end..]);
+    result
+}
 
-            for i in start..end_print {
-                to_print[i] = true;
+impl Grep {
+    /// Recursively collects regular files under `dir`, sorted by name for stable output.
+    /// Symbolic links are skipped so that link cycles can't cause endless recursion.
+    ///
+    /// A relative `dir` is looked up in `base`, but the files are named as `dir/...`.
+    fn collect_files(base: &Path, dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        let mut entries = fs::read_dir(base.join(dir))?.collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let file_type = entry.file_type()?;
+            let path = dir.join(entry.file_name());
+            if file_type.is_dir() {
+                Self::collect_files(base, &path, out)?;
+            } else if file_type.is_file() {
+                out.push(path);
             }
         }
+        Ok(())
+    }
+
+    /// Search `reader` line by line, printing the selected lines as soon as they are read.
+    /// Only up to `-B` lines of leading context are kept in memory.
+    fn process_source(
+        &self,
+        reader: &mut dyn Read,
+        stdout: &mut dyn Write,
+        file_name: Option<&str>,
+        re: &regex::Regex,
+    ) -> Result<ExitCode> {
+        let mut reader = BufReader::new(reader);
 
         let prefix = file_name
             .map(|name| format!("{}:", name))
             .unwrap_or_default();
-        let mut last_printed_index: Option<usize> = None;
-        let separator = if self.after_context > 0 { "--\n" } else { "" };
-
-        for (i, line) in lines.iter().enumerate() {
-            if to_print[i] {
-                if self.after_context > 0
-                    && last_printed_index.is_some()
-                    && i > last_printed_index.unwrap() + 1
-                {
-                    stdout.write_all(separator.as_bytes())?;
+        let colored = match self.color {
+            GrepColor::Never => false,
+            GrepColor::Always => true,
+            GrepColor::Auto => std::io::stdout().is_terminal(),
+        };
+        // Context is neither counted nor printed with -c and ignored with -o
+        let with_context = !self.count && !self.only_matching;
+        let context_before = if with_context { self.before_context.max(self.context) } else { 0 };
+        let context_after = if with_context { self.after_context.max(self.context) } else { 0 };
+        let has_context = context_before > 0 || context_after > 0;
+
+        let max_count = self.max_count.unwrap_or(usize::MAX);
+        // Lines left to read after the last selected line allowed by -m
+        let mut lines_left = (max_count == 0).then_some(0);
+
+        // The last lines that weren't printed, with their indices, as leading context
+        let mut before: VecDeque<(usize, String)> = VecDeque::with_capacity(context_before);
+        // How many of the next lines are trailing context
+        let mut after_left = 0;
+        let mut last_printed: Option<usize> = None;
+        let mut selected = 0;
+
+        let mut print_line = |stdout: &mut dyn Write, i: usize, line: &str| -> Result<()> {
+            if has_context && last_printed.is_some_and(|last| i > last + 1) {
+                stdout.write_all(b"--\n")?;
+            }
+            if self.line_number {
+                write!(stdout, "{}{}:{}", prefix, i + 1, line)?;
+            } else {
+                write!(stdout, "{}{}", prefix, line)?;
+            }
+            last_printed = Some(i);
+            Ok(())
+        };
+
+        let mut line_num: usize = 0;
+        loop {
+            if lines_left == Some(0) {
+                break;
+            }
+            let mut raw_line = Vec::new();
+
+            let bytes_read = match reader.read_until(b'\n', &mut raw_line) {
+                Ok(n) => n,
+                Err(e) => return Err(anyhow::anyhow!("read error: {}", e)),
+            };
+
+            if bytes_read == 0 {
+                break;
+            }
+            // Binary input, like the output of another program, isn't always valid UTF-8
+            let line = String::from_utf8_lossy(&raw_line).into_owned();
+
+            // Lines after the last one allowed by -m can only be trailing context
+            let is_selected = lines_left.is_none() && re.is_match(&line) != self.invert_match;
+            if let Some(left) = &mut lines_left {
+                *left -= 1;
+            }
+
+            if is_selected {
+                if self.files_with_matches {
+                    // One match is enough, there is no need to read the rest of the source
+                    writeln!(stdout, "{}", file_name.unwrap_or("(standard input)"))?;
+                    return Ok(0);
+                }
+                selected += 1;
+                if selected == max_count {
+                    lines_left = Some(context_after);
                 }
 
-                write!(stdout, "{}{}", prefix, line)?;
-                last_printed_index = Some(i);
+                if self.count {
+                    // Printed once the whole source is read
+                } else if self.only_matching {
+                    // Like GNU grep, non-matching lines selected with -v have nothing to print
+                    if !self.invert_match {
+                        let line = line.trim_end_matches('\n');
+                        for found in re.find_iter(line).filter(|found| !found.is_empty()) {
+                            let text = match colored {
+                                true => {
+                                    format!("{}{}{}", MATCH_COLOR, found.as_str(), RESET_COLOR)
+                                }
+                                false => found.as_str().to_string(),
+                            };
+                            if self.line_number {
+                                writeln!(stdout, "{}{}:{}", prefix, line_num + 1, text)?;
+                            } else {
+                                writeln!(stdout, "{}{}", prefix, text)?;
+                            }
+                        }
+                    }
+                } else {
+                    for (i, context_line) in before.drain(..) {
+                        print_line(stdout, i, &context_line)?;
+                    }
+                    // Selected lines with -v have no matches to highlight
+                    if colored && !self.invert_match {
+                        print_line(stdout, line_num, &highlight_matches(&line, re))?;
+                    } else {
+                        print_line(stdout, line_num, &line)?;
+                    }
+                    after_left = context_after;
+                }
+            } else if after_left > 0 {
+                print_line(stdout, line_num, &line)?;
+                after_left -= 1;
+            } else if context_before > 0 {
+                if before.len() == context_before {
+                    before.pop_front();
+                }
+                before.push_back((line_num, line));
             }
+            line_num += 1;
         }
 
+        if self.count {
+            match file_name {
+                Some(name) => writeln!(stdout, "{}:{}", name, selected)?,
+                None => writeln!(stdout, "{}", selected)?,
+            }
+        }
         Ok(0)
     }
 }
@@ -393,9 +1177,27 @@ impl BuiltinCommand for Grep {
         self,
         stdin: &mut dyn Read,
         stdout: &mut dyn Write,
-        _env: &mut Environment,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
     ) -> Result<ExitCode> {
-        let user_pattern = self.pattern.clone();
+        // Without -e, the first positional argument is the pattern
+        let (patterns, files) = match self.files.split_first() {
+            _ if !self.patterns.is_empty() => (self.patterns.clone(), self.files.clone()),
+            Some((pattern, files)) => (vec![pattern.clone()], files.to_vec()),
+            None => {
+                writeln!(stderr, "grep: no pattern given")?;
+                return Ok(2);
+            }
+        };
+
+        let user_pattern = patterns
+            .iter()
+            .map(|pattern| match self.fixed_string {
+                true => format!("(?:{})", regex::escape(pattern)),
+                false => format!("(?:{})", pattern),
+            })
+            .collect::<Vec<_>>()
+            .join("|");
 
         let pattern = if self.word_regexp {
             format!(r"\b({})\b", user_pattern)
@@ -408,22 +1210,40 @@ impl BuiltinCommand for Grep {
             .build()
             .with_context(|| format!("Invalid regex pattern: {}", pattern))?;
 
-        if self.files.is_empty() {
+        if files.is_empty() {
             // Чтение из stdin
             self.process_source(stdin, stdout, None, &re)
         } else {
             // Чтение из файлов
             let mut final_exit_code = 0;
-            for file_name in &self.files {
-                match fs::File::open(file_name) {
+            let mut targets = Vec::new();
+            for file_name in &files {
+                let path = Path::new(file_name);
+                if self.recursive && env.current_dir.join(path).is_dir() {
+                    if let Err(e) = Self::collect_files(&env.current_dir, path, &mut targets) {
+                        writeln!(stderr, "grep: {}: {}", file_name, e)?;
+                        final_exit_code = 1;
+                    }
+                } else {
+                    targets.push(path.to_path_buf());
+                }
+            }
+
+            // Like in GNU grep, lines and counts from a lone file come without its name.
+            // With -l the names are all there is to print.
+            let with_name = self.files_with_matches || targets.len() > 1 || self.recursive;
+            for target in &targets {
+                let file_name = target.to_string_lossy();
+                match fs::File::open(env.current_dir.join(target)) {
                     Ok(mut f) => {
-                        if let Err(e) = self.process_source(&mut f, stdout, Some(file_name), &re) {
-                            writeln!(stdout, "grep: {}: {}", file_name, e)?;
+                        let label = if with_name { Some(file_name.as_ref()) } else { None };
+                        if let Err(e) = self.process_source(&mut f, stdout, label, &re) {
+                            writeln!(stderr, "grep: {}: {}", file_name, e)?;
                             final_exit_code = 1;
                         }
                     }
                     Err(e) => {
-                        writeln!(stdout, "grep: {}: {}", file_name, e)?;
+                        writeln!(stderr, "grep: {}: {}", file_name, e)?;
                         final_exit_code = 1;
                     }
                 }
@@ -433,479 +1253,4298 @@ impl BuiltinCommand for Grep {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::collections::HashMap;
-    use std::env as stdenv;
-    use std::io;
-    use std::io::Cursor;
-    use std::sync::{Mutex, MutexGuard, OnceLock};
-    use std::time::{SystemTime, UNIX_EPOCH};
+#[derive(argh::FromArgs)]
+/// print the first lines of files
+pub struct Head {
+    #[argh(option, short = 'n', default = "10")]
+    /// print the first NUM lines instead of the first 10
+    pub lines: usize,
 
-    fn lock_current_dir() -> MutexGuard<'static, ()> {
-        static MUTEX: OnceLock<Mutex<()>> = OnceLock::new();
-        MUTEX.get_or_init(|| Mutex::new(())).lock().unwrap()
-    }
+    #[argh(option, short = 'c')]
+    /// print the first NUM bytes instead of lines
+    pub bytes: Option<usize>,
 
-    #[test]
-    fn test_pwd_prints_current_dir() {
-        let _lock = lock_current_dir();
-        let cur = stdenv::current_dir().unwrap();
+    #[argh(positional, greedy)]
+    /// files to read. If none provided, reads from stdin.
+    pub files: Vec<String>,
+}
 
-        let mut env = Environment {
-            vars: HashMap::new(),
-            current_dir: cur.clone(),
-            should_exit: false,
-        };
+impl Head {
+    /// Copies the first bytes or lines of `reader`, depending on the options.
+    fn copy(&self, reader: &mut dyn Read, stdout: &mut dyn Write) -> Result<()> {
+        match self.bytes {
+            Some(count) => {
+                std::io::copy(&mut reader.take(count as u64), stdout)?;
+                Ok(())
+            }
+            None => self.copy_lines(reader, stdout),
+        }
+    }
 
-        let mut out = Vec::new();
-        let cmd = Pwd {};
-        let res = cmd.execute(&mut Cursor::new(Vec::new()), &mut out, &mut env);
+    /// Copies at most `self.lines` lines from `reader`, without reading any further.
+    fn copy_lines(&self, reader: &mut dyn Read, stdout: &mut dyn Write) -> Result<()> {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        for _ in 0..self.lines {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            stdout.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
 
-        assert!(res.is_ok());
+impl BuiltinCommand for Head {
+    fn name() -> &'static str {
+        "head"
+    }
 
-        let s = String::from_utf8(out).unwrap();
-        let expected = format!("{}\n", cur.to_string_lossy());
+    fn execute(
+        self,
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        if self.files.is_empty() {
+            self.copy(stdin, stdout)?;
+            return Ok(0);
+        }
 
-        assert_eq!(s, expected);
+        let mut final_exit_code = 0;
+        let with_headers = self.files.len() > 1;
+        for (i, file_name) in self.files.iter().enumerate() {
+            match fs::File::open(env.current_dir.join(file_name)) {
+                Ok(mut f) => {
+                    if with_headers {
+                        if i > 0 {
+                            writeln!(stdout)?;
+                        }
+                        writeln!(stdout, "==> {} <==", file_name)?;
+                    }
+                    self.copy(&mut f, stdout)?;
+                }
+                Err(e) => {
+                    writeln!(stderr, "head: {}: {}", file_name, e)?;
+                    final_exit_code = 1;
+                }
+            }
+        }
+        Ok(final_exit_code)
     }
+}
 
-    #[test]
-    fn test_echo_with_and_without_newline() {
-        let mut env = Environment {
-            vars: HashMap::new(),
-            current_dir: stdenv::current_dir().unwrap(),
-            should_exit: false,
-        };
+/// Which lines `tail` should print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TailLines {
+    /// The last N lines (`-n N`).
+    Last(usize),
+    /// Everything starting at the 1-based line K (`-n +K`).
+    From(usize),
+}
 
-        // With newline
-        let mut out1 = Vec::new();
-        let args = vec!["hello".to_string(), "world".to_string()];
-        let echo1 = Echo {
-            no_newline: false,
-            args,
-        };
-        let res1 = echo1.execute(&mut Cursor::new(Vec::new()), &mut out1, &mut env);
+fn parse_tail_lines(value: &str) -> std::result::Result<TailLines, String> {
+    let (from_start, number) = match value.strip_prefix('+') {
+        Some(number) => (true, number),
+        None => (false, value),
+    };
+    let n = number
+        .parse::<usize>()
+        .map_err(|_| format!("invalid number of lines: '{}'", value))?;
+    Ok(if from_start {
+        TailLines::From(n)
+    } else {
+        TailLines::Last(n)
+    })
+}
 
-        assert!(res1.is_ok());
-        assert_eq!(String::from_utf8(out1).unwrap(), "hello world\n");
+#[derive(argh::FromArgs)]
+/// print the last lines of files
+pub struct Tail {
+    #[argh(
+        option,
+        short = 'n',
+        default = "TailLines::Last(10)",
+        from_str_fn(parse_tail_lines)
+    )]
+    /// print the last NUM lines instead of the last 10; use +NUM to start at line NUM
+    pub lines: TailLines,
+
+    #[argh(option, short = 'c')]
+    /// print the last NUM bytes instead of lines
+    pub bytes: Option<usize>,
 
-        // Without newline
-        let mut out2 = Vec::new();
-        let args = vec!["foo".to_string(), "bar".to_string()];
-        let echo2 = Echo {
-            no_newline: true,
-            args,
-        };
-        let res2 = echo2.execute(&mut Cursor::new(Vec::new()), &mut out2, &mut env);
+    #[argh(positional, greedy)]
+    /// files to read. If none provided, reads from stdin.
+    pub files: Vec<String>,
+}
 
-        assert!(res2.is_ok());
-        assert_eq!(String::from_utf8(out2).unwrap(), "foo bar");
+impl Tail {
+    /// Copies the last bytes or lines of `reader`, depending on the options.
+    fn copy(&self, reader: &mut dyn Read, stdout: &mut dyn Write) -> Result<()> {
+        match self.bytes {
+            Some(count) => {
+                let mut data = Vec::new();
+                reader.read_to_end(&mut data)?;
+                stdout.write_all(&data[data.len().saturating_sub(count)..])?;
+                Ok(())
+            }
+            None => self.copy_lines(reader, stdout),
+        }
     }
 
-    fn make_unique_temp_dir() -> io::Result<PathBuf> {
-        let mut p = stdenv::temp_dir();
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-        p.push(format!("task1_test_cd_{}_{}", std::process::id(), nanos));
-        fs::create_dir_all(&p)?;
-        Ok(p)
+    fn copy_lines(&self, reader: &mut dyn Read, stdout: &mut dyn Write) -> Result<()> {
+        let mut reader = BufReader::new(reader);
+        let mut kept: VecDeque<Vec<u8>> = VecDeque::new();
+        let mut line_num: usize = 0;
+        loop {
+            let mut line = Vec::new();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            line_num += 1;
+            match self.lines {
+                TailLines::From(start) => {
+                    if line_num >= start {
+                        stdout.write_all(&line)?;
+                    }
+                }
+                TailLines::Last(count) => {
+                    // Keep a ring buffer of the last `count` lines only
+                    if count == 0 {
+                        continue;
+                    }
+                    if kept.len() == count {
+                        kept.pop_front();
+                    }
+                    kept.push_back(line);
+                }
+            }
+        }
+        for line in kept {
+            stdout.write_all(&line)?;
+        }
+        Ok(())
     }
+}
 
-    #[test]
-    fn test_cd_to_absolute_path() {
-        let _lock = lock_current_dir();
-        let temp = make_unique_temp_dir().expect("failed to create temp dir");
-        let canonical_temp = fs::canonicalize(&temp).expect("canonicalize failed");
+impl BuiltinCommand for Tail {
+    fn name() -> &'static str {
+        "tail"
+    }
 
-        // save original cwd to restore later
-        let orig = stdenv::current_dir().unwrap();
+    fn execute(
+        self,
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        if self.files.is_empty() {
+            self.copy(stdin, stdout)?;
+            return Ok(0);
+        }
 
-        let mut env = Environment {
-            vars: HashMap::new(),
-            current_dir: orig.clone(),
-            should_exit: false,
-        };
+        let mut final_exit_code = 0;
+        let with_headers = self.files.len() > 1;
+        for (i, file_name) in self.files.iter().enumerate() {
+            match fs::File::open(env.current_dir.join(file_name)) {
+                Ok(mut f) => {
+                    if with_headers {
+                        if i > 0 {
+                            writeln!(stdout)?;
+                        }
+                        writeln!(stdout, "==> {} <==", file_name)?;
+                    }
+                    self.copy(&mut f, stdout)?;
+                }
+                Err(e) => {
+                    writeln!(stderr, "tail: {}: {}", file_name, e)?;
+                    final_exit_code = 1;
+                }
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
 
-        let target = Some(canonical_temp.to_string_lossy().to_string());
-        let cmd = Cd { target };
-        let res = cmd.execute(&mut Cursor::new(Vec::new()), &mut Vec::new(), &mut env);
+#[derive(argh::FromArgs, Default)]
+/// list directory contents
+pub struct Ls {
+    #[argh(switch, short = 'a')]
+    /// do not ignore entries starting with .
+    pub all: bool,
 
-        assert!(res.is_ok());
+    #[argh(switch, short = 'l')]
+    /// use a long listing format showing file type and size
+    pub long: bool,
 
-        let new_cwd = stdenv::current_dir().unwrap();
-        let new_canonical = fs::canonicalize(&new_cwd).unwrap();
+    #[argh(positional, greedy)]
+    /// directories or files to list. Defaults to the current directory.
+    pub paths: Vec<String>,
+}
 
-        assert_eq!(new_canonical, canonical_temp);
-        assert_eq!(env.current_dir, canonical_temp);
+impl Ls {
+    fn write_entry(&self, stdout: &mut dyn Write, name: &str, meta: &fs::Metadata) -> Result<()> {
+        if self.long {
+            let file_type = meta.file_type();
+            let kind = if file_type.is_dir() {
+                'd'
+            } else if file_type.is_symlink() {
+                'l'
+            } else {
+                '-'
+            };
+            writeln!(stdout, "{} {:>10} {}", kind, meta.len(), name)?;
+        } else {
+            writeln!(stdout, "{}", name)?;
+        }
+        Ok(())
+    }
 
-        stdenv::set_current_dir(orig).expect("failed to restore cwd");
+    fn list_dir(&self, stdout: &mut dyn Write, dir: &Path) -> Result<()> {
+        let mut entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
 
-        let _ = fs::remove_dir_all(&temp);
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !self.all && name.starts_with('.') {
+                continue;
+            }
+            // `DirEntry::metadata` does not follow symlinks, so they are reported as links
+            self.write_entry(stdout, &name, &entry.metadata()?)?;
+        }
+        Ok(())
     }
+}
 
-    #[test]
-    fn test_cd_to_home_when_none() {
-        let _lock = lock_current_dir();
-        let temp = make_unique_temp_dir().expect("failed to create temp dir");
-        let canonical_temp = fs::canonicalize(&temp).expect("canonicalize failed");
-
-        let orig = stdenv::current_dir().unwrap();
+impl BuiltinCommand for Ls {
+    fn name() -> &'static str {
+        "ls"
+    }
 
-        let mut env = Environment {
-            vars: HashMap::new(),
-            current_dir: orig.clone(),
-            should_exit: false,
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let paths = if self.paths.is_empty() {
+            vec![".".to_string()]
+        } else {
+            self.paths.clone()
         };
 
-        env.set_var("HOME", canonical_temp.to_string_lossy().to_string());
+        let mut final_exit_code = 0;
+        let with_headers = paths.len() > 1;
+        for (i, path) in paths.iter().enumerate() {
+            let full_path = env.current_dir.join(path);
+            let result = match fs::symlink_metadata(&full_path) {
+                Ok(_) if full_path.is_dir() => {
+                    if with_headers {
+                        if i > 0 {
+                            writeln!(stdout)?;
+                        }
+                        writeln!(stdout, "{}:", path)?;
+                    }
+                    self.list_dir(stdout, &full_path)
+                }
+                Ok(meta) => self.write_entry(stdout, path, &meta),
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                writeln!(stderr, "ls: {}: {}", path, e)?;
+                final_exit_code = 1;
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// copy files and directories
+pub struct Cp {
+    #[argh(switch, short = 'r')]
+    /// copy directories recursively
+    pub recursive: bool,
+
+    #[argh(positional, greedy)]
+    /// files to copy, followed by the destination file or directory
+    pub paths: Vec<String>,
+}
+
+/// Split the operands of `cp` or `mv` into the sources, paired with where each one goes,
+/// resolving them against the current directory. Several sources need a directory as
+/// the destination.
+fn copy_targets(paths: &[String], env: &Environment) -> Result<Vec<(String, PathBuf, PathBuf)>> {
+    let Some((dest, sources)) = paths.split_last().filter(|(_, sources)| !sources.is_empty())
+    else {
+        anyhow::bail!("missing destination file operand");
+    };
+    let dest_path = env.current_dir.join(dest);
+    let into_dir = dest_path.is_dir();
+    if sources.len() > 1 && !into_dir {
+        anyhow::bail!("target '{}' is not a directory", dest);
+    }
+    Ok(sources
+        .iter()
+        .map(|source| {
+            let source_path = env.current_dir.join(source);
+            let target = match source_path.file_name() {
+                Some(name) if into_dir => dest_path.join(name),
+                _ => dest_path.clone(),
+            };
+            (source.clone(), source_path, target)
+        })
+        .collect())
+}
+
+/// Whether `a` and `b` are the same existing file, like a path and a hard link to it.
+/// Copying a file onto itself would truncate it.
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &Path, b: &Path) -> bool {
+    matches!((fs::canonicalize(a), fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+fn same_file_error(source: &Path, target: &Path) -> std::io::Error {
+    std::io::Error::other(format!(
+        "'{}' and '{}' are the same file",
+        source.display(),
+        target.display()
+    ))
+}
+
+/// Copy the directory `source` with everything in it to `target`, creating `target`.
+fn copy_dir_all(source: &Path, target: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let to = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &to)?;
+        } else if same_file(&entry.path(), &to) {
+            return Err(same_file_error(&entry.path(), &to));
+        } else {
+            fs::copy(entry.path(), to)?;
+        }
+    }
+    Ok(())
+}
+
+impl BuiltinCommand for Cp {
+    fn name() -> &'static str {
+        "cp"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let targets = match copy_targets(&self.paths, env) {
+            Ok(targets) => targets,
+            Err(e) => {
+                writeln!(stderr, "cp: {}", e)?;
+                return Ok(1);
+            }
+        };
+
+        let mut final_exit_code = 0;
+        for (name, source, target) in targets {
+            if same_file(&source, &target) {
+                let shown = target.strip_prefix(&env.current_dir).unwrap_or(&target);
+                writeln!(stderr, "cp: '{}' and '{}' are the same file", name, shown.display())?;
+                final_exit_code = 1;
+                continue;
+            }
+            let result = if !source.is_dir() {
+                fs::copy(&source, &target).map(|_| ()).map_err(Into::into)
+            } else if !self.recursive {
+                Err(anyhow::anyhow!("-r not specified; omitting directory"))
+            } else if target.starts_with(&source) {
+                Err(anyhow::anyhow!("cannot copy a directory into itself"))
+            } else {
+                copy_dir_all(&source, &target).map_err(Into::into)
+            };
+            if let Err(e) = result {
+                writeln!(stderr, "cp: {}: {}", name, e)?;
+                final_exit_code = 1;
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// move or rename files and directories
+pub struct Mv {
+    #[argh(positional, greedy)]
+    /// files to move, followed by the new name or the destination directory
+    pub paths: Vec<String>,
+}
+
+/// Move `source` to `target`. Renaming doesn't work across filesystems, so then the
+/// source is copied and removed instead.
+fn move_path(source: &Path, target: &Path) -> std::io::Result<()> {
+    match fs::rename(source, target) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            if source.is_dir() {
+                copy_dir_all(source, target)?;
+                fs::remove_dir_all(source)
+            } else {
+                fs::copy(source, target)?;
+                fs::remove_file(source)
+            }
+        }
+        result => result,
+    }
+}
+
+impl BuiltinCommand for Mv {
+    fn name() -> &'static str {
+        "mv"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let targets = match copy_targets(&self.paths, env) {
+            Ok(targets) => targets,
+            Err(e) => {
+                writeln!(stderr, "mv: {}", e)?;
+                return Ok(1);
+            }
+        };
+
+        let mut final_exit_code = 0;
+        for (name, source, target) in targets {
+            if let Err(e) = move_path(&source, &target) {
+                writeln!(stderr, "mv: {}: {}", name, e)?;
+                final_exit_code = 1;
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// remove files or directories
+pub struct Rm {
+    #[argh(switch, short = 'r')]
+    /// remove directories and their contents recursively
+    pub recursive: bool,
+
+    #[argh(switch, short = 'f')]
+    /// ignore files that don't exist
+    pub force: bool,
+
+    #[argh(positional, greedy)]
+    /// files to remove
+    pub paths: Vec<String>,
+}
+
+impl BuiltinCommand for Rm {
+    fn name() -> &'static str {
+        "rm"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        if self.paths.is_empty() && !self.force {
+            writeln!(stderr, "rm: missing operand")?;
+            return Ok(1);
+        }
+
+        let mut final_exit_code = 0;
+        for name in &self.paths {
+            let path = env.current_dir.join(name);
+            // Don't follow a symlink to a directory, remove the link itself
+            let result = match fs::symlink_metadata(&path) {
+                Err(e) if self.force && e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => Err(e.into()),
+                Ok(meta) if !meta.is_dir() => fs::remove_file(&path).map_err(Into::into),
+                Ok(_) if self.recursive => fs::remove_dir_all(&path).map_err(Into::into),
+                Ok(_) => Err(anyhow::anyhow!("is a directory")),
+            };
+            if let Err(e) = result {
+                writeln!(stderr, "rm: {}: {}", name, e)?;
+                final_exit_code = 1;
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// create directories
+pub struct Mkdir {
+    #[argh(switch, short = 'p')]
+    /// create missing parent directories too, and don't fail if a directory already exists
+    pub parents: bool,
+
+    #[argh(positional, greedy)]
+    /// directories to create
+    pub paths: Vec<String>,
+}
+
+impl BuiltinCommand for Mkdir {
+    fn name() -> &'static str {
+        "mkdir"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        if self.paths.is_empty() {
+            writeln!(stderr, "mkdir: missing operand")?;
+            return Ok(1);
+        }
+
+        let mut final_exit_code = 0;
+        for name in &self.paths {
+            let path = env.current_dir.join(name);
+            let result = if self.parents {
+                fs::create_dir_all(&path)
+            } else {
+                fs::create_dir(&path)
+            };
+            if let Err(e) = result {
+                writeln!(stderr, "mkdir: {}: {}", name, e)?;
+                final_exit_code = 1;
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// create empty files or update their modification time
+pub struct Touch {
+    #[argh(switch, short = 'c')]
+    /// don't create missing files
+    pub no_create: bool,
+
+    #[argh(positional, greedy)]
+    /// files to touch
+    pub paths: Vec<String>,
+}
+
+impl Touch {
+    fn touch(&self, path: &Path) -> io::Result<()> {
+        if path.exists() {
+            File::open(path)?.set_modified(SystemTime::now())
+        } else if self.no_create {
+            Ok(())
+        } else {
+            File::create(path).map(|_| ())
+        }
+    }
+}
+
+impl BuiltinCommand for Touch {
+    fn name() -> &'static str {
+        "touch"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        _stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        if self.paths.is_empty() {
+            writeln!(stderr, "touch: missing file operand")?;
+            return Ok(1);
+        }
+
+        let mut final_exit_code = 0;
+        for name in &self.paths {
+            if let Err(e) = self.touch(&env.current_dir.join(name)) {
+                writeln!(stderr, "touch: {}: {}", name, e)?;
+                final_exit_code = 1;
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// translate, squeeze or delete characters from standard input
+pub struct Tr {
+    #[argh(switch, short = 'd')]
+    /// delete characters in SET1 instead of translating them
+    pub delete: bool,
+
+    #[argh(switch, short = 's')]
+    /// replace each run of a repeated character from the last set with a single one
+    pub squeeze: bool,
+
+    #[argh(positional, greedy)]
+    /// SET1 and, when translating, SET2
+    pub sets: Vec<String>,
+}
+
+/// Expand a `tr` set: ranges like `a-z` and the escapes `\\`, `\n` and `\t`.
+fn expand_tr_set(set: &str) -> Vec<char> {
+    let mut chars = Vec::new();
+    let mut iter = set.chars();
+    while let Some(c) = iter.next() {
+        chars.push(match c {
+            '\\' => match iter.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(other) => other,
+                None => '\\',
+            },
+            c => c,
+        });
+    }
+
+    let mut expanded = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if i + 2 < chars.len() && chars[i + 1] == '-' && chars[i] <= chars[i + 2] {
+            expanded.extend(chars[i]..=chars[i + 2]);
+            i += 3;
+        } else {
+            expanded.push(chars[i]);
+            i += 1;
+        }
+    }
+    expanded
+}
+
+impl BuiltinCommand for Tr {
+    fn name() -> &'static str {
+        "tr"
+    }
+
+    fn execute(
+        self,
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        _env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let (min_sets, max_sets) = match (self.delete, self.squeeze) {
+            (false, false) | (true, true) => (2, 2),
+            (false, true) => (1, 2),
+            (true, false) => (1, 1),
+        };
+        if self.sets.len() < min_sets {
+            writeln!(stderr, "tr: missing operand")?;
+            return Ok(1);
+        }
+        if let Some(extra) = self.sets.get(max_sets) {
+            writeln!(stderr, "tr: extra operand: {}", extra)?;
+            return Ok(1);
+        }
+
+        let set1 = expand_tr_set(&self.sets[0]);
+        let set2 = self.sets.get(1).map(|set| expand_tr_set(set));
+        if !self.delete && set2.as_ref().is_some_and(|set| set.is_empty()) {
+            writeln!(stderr, "tr: SET2 must not be empty")?;
+            return Ok(1);
+        }
+
+        // Squeezing applies to the last set given
+        let squeeze_set: &[char] = match &set2 {
+            _ if !self.squeeze => &[],
+            Some(set) => set,
+            None => &set1,
+        };
+        let map = |c: char| -> Option<char> {
+            let Some(i) = set1.iter().position(|&member| member == c) else {
+                return Some(c);
+            };
+            match &set2 {
+                _ if self.delete => None,
+                // A short SET2 is padded with its last character
+                Some(set2) => Some(set2.get(i).copied().unwrap_or(set2[set2.len() - 1])),
+                None => Some(c),
+            }
+        };
+
+        let mut reader = BufReader::new(stdin);
+        let mut line = Vec::new();
+        let mut last: Option<char> = None;
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let mut output = String::with_capacity(line.len());
+            for c in String::from_utf8_lossy(&line).chars().filter_map(map) {
+                if last == Some(c) && squeeze_set.contains(&c) {
+                    continue;
+                }
+                output.push(c);
+                last = Some(c);
+            }
+            stdout.write_all(output.as_bytes())?;
+        }
+        Ok(0)
+    }
+}
+
+/// The 1-based positions selected by a `cut` list like `1,3` or `2-4`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CutList(Vec<(usize, usize)>);
+
+impl CutList {
+    fn contains(&self, position: usize) -> bool {
+        self.0.iter().any(|&(start, end)| (start..=end).contains(&position))
+    }
+}
+
+fn parse_cut_list(value: &str) -> std::result::Result<CutList, String> {
+    let position = |number: &str| match number.parse::<usize>() {
+        Ok(0) => Err("positions are numbered from 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(_) => Err(format!("invalid list: '{}'", value)),
+    };
+    let mut ranges = Vec::new();
+    for item in value.split(',') {
+        let range = match item.split_once('-') {
+            // `N-` runs to the end of the line and `-M` starts from its beginning
+            Some(("", "")) => return Err(format!("invalid range: '{}'", item)),
+            Some((start, "")) => (position(start)?, usize::MAX),
+            Some(("", end)) => (1, position(end)?),
+            Some((start, end)) => (position(start)?, position(end)?),
+            None => (position(item)?, position(item)?),
+        };
+        if range.0 > range.1 {
+            return Err(format!("invalid decreasing range: '{}'", item));
+        }
+        ranges.push(range);
+    }
+    Ok(CutList(ranges))
+}
+
+#[derive(argh::FromArgs)]
+/// print selected parts of each line
+pub struct Cut {
+    #[argh(option, short = 'f', from_str_fn(parse_cut_list))]
+    /// select only these fields, e.g. `1,3` or `2-4`
+    pub fields: Option<CutList>,
+
+    #[argh(option, short = 'd', default = "'\\t'")]
+    /// use this character instead of TAB as the field delimiter
+    pub delimiter: char,
+
+    #[argh(option, short = 'c', from_str_fn(parse_cut_list))]
+    /// select only the characters at these positions
+    pub characters: Option<CutList>,
+
+    #[argh(positional, greedy)]
+    /// files to read. If none provided, reads from stdin.
+    pub files: Vec<String>,
+}
+
+impl Cut {
+    fn cut_line(&self, line: &str) -> String {
+        match (&self.fields, &self.characters) {
+            // Lines without the delimiter are printed as they are
+            (Some(fields), _) if line.contains(self.delimiter) => line
+                .split(self.delimiter)
+                .enumerate()
+                .filter(|(i, _)| fields.contains(i + 1))
+                .map(|(_, field)| field)
+                .collect::<Vec<_>>()
+                .join(&self.delimiter.to_string()),
+            (_, Some(characters)) => line
+                .chars()
+                .enumerate()
+                .filter(|(i, _)| characters.contains(i + 1))
+                .map(|(_, c)| c)
+                .collect(),
+            _ => line.to_string(),
+        }
+    }
+
+    fn cut(&self, reader: &mut dyn Read, stdout: &mut dyn Write) -> Result<()> {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(());
+            }
+            let text = String::from_utf8_lossy(&line);
+            writeln!(stdout, "{}", self.cut_line(text.strip_suffix('\n').unwrap_or(&text)))?;
+        }
+    }
+}
+
+impl BuiltinCommand for Cut {
+    fn name() -> &'static str {
+        "cut"
+    }
+
+    fn execute(
+        self,
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        if self.fields.is_some() == self.characters.is_some() {
+            writeln!(stderr, "cut: specify exactly one of -f and -c")?;
+            return Ok(1);
+        }
+
+        if self.files.is_empty() {
+            self.cut(stdin, stdout)?;
+            return Ok(0);
+        }
+
+        let mut final_exit_code = 0;
+        for file_name in &self.files {
+            match fs::File::open(env.current_dir.join(file_name)) {
+                Ok(mut f) => self.cut(&mut f, stdout)?,
+                Err(e) => {
+                    writeln!(stderr, "cut: {}: {}", file_name, e)?;
+                    final_exit_code = 1;
+                }
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// sort lines of text
+pub struct Sort {
+    #[argh(switch, short = 'n')]
+    /// compare by the number at the start of each line
+    pub numeric: bool,
+
+    #[argh(switch, short = 'r')]
+    /// reverse the result of comparisons
+    pub reverse: bool,
+
+    #[argh(switch, short = 'u')]
+    /// output only the first of the lines that compare equal
+    pub unique: bool,
+
+    #[argh(positional, greedy)]
+    /// files to read. If none provided, reads from stdin.
+    pub files: Vec<String>,
+}
+
+/// The number `sort -n` compares by: the leading, optionally signed decimal number of
+/// `line`, or 0 when there is none.
+fn numeric_sort_key(line: &str) -> f64 {
+    let line = line.trim_start();
+    let digits = line.strip_prefix('-').unwrap_or(line);
+    let mut end = line.len() - digits.len();
+    let mut seen_point = false;
+    for c in digits.chars() {
+        match c {
+            '0'..='9' => {}
+            '.' if !seen_point => seen_point = true,
+            _ => break,
+        }
+        end += 1;
+    }
+    line[..end].parse().unwrap_or(0.0)
+}
+
+impl Sort {
+    /// Compares two lines by the sort key alone, ignoring `-r`.
+    fn compare_keys(&self, a: &str, b: &str) -> Ordering {
+        if self.numeric {
+            numeric_sort_key(a).total_cmp(&numeric_sort_key(b))
+        } else {
+            a.cmp(b)
+        }
+    }
+
+    fn read_lines(reader: &mut dyn Read, lines: &mut Vec<String>) -> io::Result<()> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        let text = String::from_utf8_lossy(&data);
+        lines.extend(text.lines().map(str::to_string));
+        Ok(())
+    }
+}
+
+impl BuiltinCommand for Sort {
+    fn name() -> &'static str {
+        "sort"
+    }
+
+    fn execute(
+        self,
+        stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let mut lines = Vec::new();
+        let mut final_exit_code = 0;
+        if self.files.is_empty() {
+            Self::read_lines(stdin, &mut lines)?;
+        }
+        for file_name in &self.files {
+            let read = fs::File::open(env.current_dir.join(file_name))
+                .and_then(|mut f| Self::read_lines(&mut f, &mut lines));
+            if let Err(e) = read {
+                writeln!(stderr, "sort: {}: {}", file_name, e)?;
+                final_exit_code = 1;
+            }
+        }
+
+        lines.sort_by(|a, b| {
+            // Lines with equal keys fall back to comparing the whole line, unless -u
+            // has to keep the first of them
+            let ordering = match self.compare_keys(a, b) {
+                Ordering::Equal if !self.unique => a.cmp(b),
+                ordering => ordering,
+            };
+            if self.reverse { ordering.reverse() } else { ordering }
+        });
+        if self.unique {
+            lines.dedup_by(|a, b| self.compare_keys(a, b) == Ordering::Equal);
+        }
+
+        for line in lines {
+            writeln!(stdout, "{}", line)?;
+        }
+        Ok(final_exit_code)
+    }
+}
+
+#[derive(FromArgs)]
+/// Print the command history with line numbers.
+pub struct History {
+    #[argh(positional)]
+    /// how many of the last entries to print; all of them when omitted.
+    pub count: Option<usize>,
+}
+
+impl BuiltinCommand for History {
+    fn name() -> &'static str {
+        "history"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let skip = match self.count {
+            Some(count) => env.history.len().saturating_sub(count),
+            None => 0,
+        };
+        for (i, line) in env.history.iter().enumerate().skip(skip) {
+            writeln!(stdout, "{:5}  {}", i + 1, line)?;
+        }
+        Ok(0)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// locate a command
+pub struct Which {
+    #[argh(positional, greedy)]
+    /// command names to look up
+    pub names: Vec<String>,
+}
+
+impl BuiltinCommand for Which {
+    fn name() -> &'static str {
+        "which"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let mut final_exit_code = 0;
+        for name in &self.names {
+            match classify_command(env, name) {
+                CommandKind::Builtin => writeln!(stdout, "{}: shell builtin", name)?,
+                CommandKind::External(path) => writeln!(stdout, "{}", path.display())?,
+                CommandKind::NotFound => {
+                    writeln!(stderr, "{}: not found", name)?;
+                    final_exit_code = 1;
+                }
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
+
+/// Tell whether `name` is a builtin known to `env` or a program in its PATH.
+fn classify_command(env: &Environment, name: &str) -> CommandKind {
+    if env.builtins.contains_key(name) {
+        return CommandKind::Builtin;
+    }
+    let search_paths = env.get_var("PATH").unwrap_or_default();
+    match find_command_path(OsStr::new(&search_paths), Path::new(name)) {
+        Some(path) => CommandKind::External(path.into_owned()),
+        None => CommandKind::NotFound,
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// describe how each name would be interpreted as a command
+pub struct Type {
+    #[argh(positional, greedy)]
+    /// command names to classify
+    pub names: Vec<String>,
+}
+
+impl BuiltinCommand for Type {
+    fn name() -> &'static str {
+        "type"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let mut final_exit_code = 0;
+        for name in &self.names {
+            match classify_command(env, name) {
+                CommandKind::Builtin => writeln!(stdout, "{} is a shell builtin", name)?,
+                CommandKind::External(path) => writeln!(stdout, "{} is {}", name, path.display())?,
+                CommandKind::NotFound => {
+                    writeln!(stderr, "type: {}: not found", name)?;
+                    final_exit_code = 1;
+                }
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// list the builtin commands
+pub struct Help {}
+
+impl BuiltinCommand for Help {
+    fn name() -> &'static str {
+        "help"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let width = env.builtins.keys().map(|name| name.len()).max().unwrap_or(0);
+        for (name, description) in &env.builtins {
+            writeln!(stdout, "{:<width$}  {}", name, description, width = width)?;
+        }
+        Ok(0)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// define or display aliases
+pub struct Alias {
+    #[argh(positional, greedy)]
+    /// definitions like `name=value`, or names of aliases to print
+    pub definitions: Vec<String>,
+}
+
+impl BuiltinCommand for Alias {
+    fn name() -> &'static str {
+        "alias"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        stderr: &mut dyn Write,
+        env: &mut Environment,
+    ) -> Result<ExitCode> {
+        if self.definitions.is_empty() {
+            let mut aliases: Vec<_> = env.aliases.iter().collect();
+            aliases.sort();
+            for (name, value) in aliases {
+                writeln!(stdout, "alias {}='{}'", name, value)?;
+            }
+            return Ok(0);
+        }
+
+        let mut final_exit_code = 0;
+        for definition in &self.definitions {
+            match definition.split_once('=') {
+                Some((name, value)) if !name.is_empty() => {
+                    env.aliases.insert(name.to_string(), value.to_string());
+                }
+                _ => match env.aliases.get(definition) {
+                    Some(value) => writeln!(stdout, "alias {}='{}'", definition, value)?,
+                    None => {
+                        writeln!(stderr, "alias: {}: not found", definition)?;
+                        final_exit_code = 1;
+                    }
+                },
+            }
+        }
+        Ok(final_exit_code)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// repeatedly output a line with the given text, or "y"
+pub struct Yes {
+    #[argh(positional, greedy)]
+    /// text to repeat; words are joined with spaces
+    pub words: Vec<String>,
+}
+
+impl BuiltinCommand for Yes {
+    fn name() -> &'static str {
+        "yes"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+        _env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let mut line = if self.words.is_empty() {
+            "y".to_string()
+        } else {
+            self.words.join(" ")
+        };
+        line.push('\n');
+        // Write many lines at once instead of issuing a write per line
+        let chunk = line.repeat((8192 / line.len()).max(1));
+        loop {
+            match stdout.write_all(chunk.as_bytes()) {
+                Ok(()) => {}
+                // The reader went away (e.g. `yes | head`): that is how `yes` normally ends
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(0),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// strip directory and suffix from a file name
+pub struct Basename {
+    #[argh(positional)]
+    /// the path to take the last component of
+    pub path: String,
+
+    #[argh(positional)]
+    /// a suffix to remove from the result, unless it is the whole name
+    pub suffix: Option<String>,
+}
+
+impl BuiltinCommand for Basename {
+    fn name() -> &'static str {
+        "basename"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+        _env: &mut Environment,
+    ) -> Result<ExitCode> {
+        // `file_name` ignores trailing slashes and is `None` only for paths like `/` or `..`
+        let name = match Path::new(&self.path).file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None if self.path.starts_with('/') => "/".to_string(),
+            None => self.path.clone(),
+        };
+        let name = match &self.suffix {
+            Some(suffix) if name != *suffix => name.strip_suffix(suffix.as_str()).unwrap_or(&name),
+            _ => &name,
+        };
+        writeln!(stdout, "{}", name)?;
+        Ok(0)
+    }
+}
+
+#[derive(argh::FromArgs)]
+/// strip the last component from a file name
+pub struct Dirname {
+    #[argh(positional)]
+    /// the path to take the directory part of
+    pub path: String,
+}
+
+impl BuiltinCommand for Dirname {
+    fn name() -> &'static str {
+        "dirname"
+    }
+
+    fn execute(
+        self,
+        _stdin: &mut dyn Read,
+        stdout: &mut dyn Write,
+        _stderr: &mut dyn Write,
+        _env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let dir = match Path::new(&self.path).parent() {
+            // A single relative component lives in the current directory
+            Some(parent) if parent.as_os_str().is_empty() => Path::new("."),
+            Some(parent) => parent,
+            None if self.path.starts_with('/') => Path::new("/"),
+            None => Path::new("."),
+        };
+        writeln!(stdout, "{}", dir.display())?;
+        Ok(0)
+    }
+}
+
+/// Print a sequence of integers, one per line: `seq LAST`, `seq FIRST LAST` or
+/// `seq FIRST STEP LAST`.
+///
+/// A negative step is written like `-2`, which argh would reject as an unknown flag,
+/// so `seq` parses its arguments itself instead of being a [`BuiltinCommand`].
+pub struct Seq {
+    args: Vec<String>,
+}
+
+impl CommandFactory for Factory<Seq> {
+    fn try_create(
+        &self,
+        _env: &Environment,
+        name: &str,
+        args: &[&str],
+    ) -> Option<Box<dyn ExecutableCommand>> {
+        (name == "seq").then(|| {
+            Box::new(Seq {
+                args: args.iter().map(|x| x.to_string()).collect(),
+            }) as Box<dyn ExecutableCommand>
+        })
+    }
+
+    fn builtin_name(&self) -> Option<&'static str> {
+        Some("seq")
+    }
+
+    fn description(&self) -> Option<String> {
+        Some("print a sequence of integers".to_string())
+    }
+}
+
+impl Seq {
+    /// Parse the arguments into `(first, step, last)`.
+    fn bounds(&self) -> std::result::Result<(i64, i64, i64), String> {
+        let numbers = self
+            .args
+            .iter()
+            .map(|arg| {
+                arg.parse::<i64>()
+                    .map_err(|_| format!("seq: invalid argument: '{}'", arg))
+            })
+            .collect::<std::result::Result<Vec<i64>, String>>()?;
+        match numbers[..] {
+            [last] => Ok((1, 1, last)),
+            [first, last] => Ok((first, 1, last)),
+            [_, 0, _] => Err("seq: zero increment".to_string()),
+            [first, step, last] => Ok((first, step, last)),
+            [] => Err("seq: missing operand".to_string()),
+            _ => Err(format!("seq: extra operand '{}'", self.args[3])),
+        }
+    }
+}
+
+/// Print arguments according to a format: `printf FORMAT [ARGUMENT]...`.
+///
+/// Supports `%s`, `%d`, `%x` and `%%` along with the `echo -e` backslash escapes. The format
+/// is reused while arguments remain; missing ones are empty strings or zeros.
+/// Like `seq` it parses its own arguments, so negative numbers aren't taken for flags.
+pub struct Printf {
+    args: Vec<String>,
+}
+
+impl CommandFactory for Factory<Printf> {
+    fn try_create(
+        &self,
+        _env: &Environment,
+        name: &str,
+        args: &[&str],
+    ) -> Option<Box<dyn ExecutableCommand>> {
+        (name == "printf").then(|| {
+            Box::new(Printf {
+                args: args.iter().map(|x| x.to_string()).collect(),
+            }) as Box<dyn ExecutableCommand>
+        })
+    }
+
+    fn builtin_name(&self) -> Option<&'static str> {
+        Some("printf")
+    }
+
+    fn description(&self) -> Option<String> {
+        Some("format and print arguments".to_string())
+    }
+}
+
+impl Printf {
+    /// Expand `format` once, taking arguments from `args` as the specifiers need them.
+    /// Invalid numbers are reported to `stderr` and printed as 0, which sets `failed`.
+    fn format_once<'a>(
+        format: &str,
+        args: &mut impl Iterator<Item = &'a String>,
+        stderr: &mut dyn Write,
+        failed: &mut bool,
+    ) -> String {
+        let mut output = String::new();
+        let mut chars = format.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                output.push(ch);
+                continue;
+            }
+            match chars.next() {
+                Some('%') => output.push('%'),
+                Some('s') => output.push_str(args.next().map_or("", String::as_str)),
+                Some(spec @ ('d' | 'x')) => {
+                    let number = match args.next() {
+                        None => 0,
+                        Some(arg) => arg.trim().parse::<i64>().unwrap_or_else(|_| {
+                            *failed = true;
+                            let _ = writeln!(stderr, "printf: '{}': invalid number", arg);
+                            0
+                        }),
+                    };
+                    if spec == 'd' {
+                        output.push_str(&number.to_string());
+                    } else {
+                        output.push_str(&format!("{:x}", number));
+                    }
+                }
+                // Unsupported specifiers are printed as is
+                Some(other) => {
+                    output.push('%');
+                    output.push(other);
+                }
+                None => output.push('%'),
+            }
+        }
+        output
+    }
+}
+
+impl ExecutableCommand for Printf {
+    fn execute(
+        self: Box<Self>,
+        _stdin: Box<dyn Stdin>,
+        mut stdout: Box<dyn Stdout>,
+        mut stderr: Box<dyn Stdout>,
+        _env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let Some((format, args)) = self.args.split_first() else {
+            writeln!(stderr, "printf: missing operand")?;
+            return Ok(1);
+        };
+        let format = interpret_escapes(format);
+        let mut failed = false;
+        let mut remaining = args.iter().peekable();
+        loop {
+            let before = remaining.len();
+            let output = Self::format_once(&format, &mut remaining, &mut stderr, &mut failed);
+            stdout.write_all(output.as_bytes())?;
+            // Reuse the format only while it keeps consuming arguments
+            if remaining.peek().is_none() || remaining.len() == before {
+                break;
+            }
+        }
+        Ok(if failed { 1 } else { 0 })
+    }
+}
+
+impl ExecutableCommand for Seq {
+    fn execute(
+        self: Box<Self>,
+        _stdin: Box<dyn Stdin>,
+        mut stdout: Box<dyn Stdout>,
+        mut stderr: Box<dyn Stdout>,
+        _env: &mut Environment,
+    ) -> Result<ExitCode> {
+        let (first, step, last) = match self.bounds() {
+            Ok(bounds) => bounds,
+            Err(message) => {
+                writeln!(stderr, "{}", message)?;
+                return Ok(1);
+            }
+        };
+        let mut current = first;
+        while (step > 0 && current <= last) || (step < 0 && current >= last) {
+            writeln!(stdout, "{}", current)?;
+            match current.checked_add(step) {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::env as stdenv;
+    use std::io;
+    use std::io::Cursor;
+    use crate::test_util::lock_current_dir;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn test_pwd_prints_current_dir() {
+        let _lock = lock_current_dir();
+        let cur = stdenv::current_dir().unwrap();
+
+        let mut env = Environment {
+            vars: HashMap::new(),
+            current_dir: cur.clone(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        let mut out = Vec::new();
+        let cmd = Pwd {};
+        let res = cmd.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut env);
+
+        assert!(res.is_ok());
+
+        let s = String::from_utf8(out).unwrap();
+        let expected = format!("{}\n", cur.to_string_lossy());
+
+        assert_eq!(s, expected);
+    }
+
+    #[test]
+    fn test_echo_with_and_without_newline() {
+        let mut env = Environment {
+            vars: HashMap::new(),
+            current_dir: stdenv::current_dir().unwrap(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        // With newline
+        let mut out1 = Vec::new();
+        let args = vec!["hello".to_string(), "world".to_string()];
+        let echo1 = Echo {
+            no_newline: false,
+            args,
+            ..Default::default()
+        };
+        let res1 = echo1.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut out1,
+            &mut io::sink(),
+            &mut env,
+        );
+
+        assert!(res1.is_ok());
+        assert_eq!(String::from_utf8(out1).unwrap(), "hello world\n");
+
+        // Without newline
+        let mut out2 = Vec::new();
+        let args = vec!["foo".to_string(), "bar".to_string()];
+        let echo2 = Echo {
+            no_newline: true,
+            args,
+            ..Default::default()
+        };
+        let res2 = echo2.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut out2,
+            &mut io::sink(),
+            &mut env,
+        );
+
+        assert!(res2.is_ok());
+        assert_eq!(String::from_utf8(out2).unwrap(), "foo bar");
+    }
+
+    fn make_unique_temp_dir() -> io::Result<PathBuf> {
+        let mut p = stdenv::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        p.push(format!("task1_test_cd_{}_{}", std::process::id(), nanos));
+        fs::create_dir_all(&p)?;
+        Ok(p)
+    }
+
+    #[test]
+    fn test_cd_to_absolute_path() {
+        let _lock = lock_current_dir();
+        let temp = make_unique_temp_dir().expect("failed to create temp dir");
+        let canonical_temp = fs::canonicalize(&temp).expect("canonicalize failed");
+
+        // save original cwd to restore later
+        let orig = stdenv::current_dir().unwrap();
+
+        let mut env = Environment {
+            vars: HashMap::new(),
+            current_dir: orig.clone(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        let target = Some(canonical_temp.to_string_lossy().to_string());
+        let cmd = Cd { target };
+        let res = cmd.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut Vec::new(),
+            &mut io::sink(),
+            &mut env,
+        );
+
+        assert!(res.is_ok());
+
+        let new_cwd = stdenv::current_dir().unwrap();
+        let new_canonical = fs::canonicalize(&new_cwd).unwrap();
+
+        assert_eq!(new_canonical, canonical_temp);
+        assert_eq!(env.current_dir, canonical_temp);
+
+        stdenv::set_current_dir(orig).expect("failed to restore cwd");
+
+        let _ = fs::remove_dir_all(&temp);
+    }
+
+    #[test]
+    fn test_cd_updates_pwd() {
+        let _lock = lock_current_dir();
+        let temp = make_unique_temp_dir().expect("failed to create temp dir");
+        let canonical_temp = fs::canonicalize(&temp).expect("canonicalize failed");
+        fs::create_dir(canonical_temp.join("sub")).unwrap();
+        let orig = stdenv::current_dir().unwrap();
+
+        let mut env = Environment::new();
+        env.current_dir = canonical_temp.clone();
+        let cmd = Cd {
+            target: Some("sub/../sub".to_string()),
+        };
+        let res = cmd.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut Vec::new(),
+            &mut io::sink(),
+            &mut env,
+        );
+        stdenv::set_current_dir(orig).expect("failed to restore cwd");
+
+        assert!(res.is_ok());
+        assert_eq!(
+            env.get_var("PWD"),
+            Some(canonical_temp.join("sub").to_string_lossy().into_owned())
+        );
+
+        let _ = fs::remove_dir_all(&temp);
+    }
+
+    #[test]
+    fn test_cd_to_home_when_none() {
+        let _lock = lock_current_dir();
+        let temp = make_unique_temp_dir().expect("failed to create temp dir");
+        let canonical_temp = fs::canonicalize(&temp).expect("canonicalize failed");
+
+        let orig = stdenv::current_dir().unwrap();
+
+        let mut env = Environment {
+            vars: HashMap::new(),
+            current_dir: orig.clone(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        env.set_var("HOME", canonical_temp.to_string_lossy().to_string());
+
+        let cmd = Cd { target: None };
+        let res = cmd.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut Vec::new(),
+            &mut io::sink(),
+            &mut env,
+        );
+
+        assert!(res.is_ok());
+
+        let new_cwd = stdenv::current_dir().unwrap();
+        let new_canonical = fs::canonicalize(&new_cwd).unwrap();
+
+        assert_eq!(new_canonical, canonical_temp);
+        assert_eq!(env.current_dir, canonical_temp);
+
+        stdenv::set_current_dir(orig).expect("failed to restore cwd");
+
+        let _ = fs::remove_dir_all(&temp);
+    }
+
+    #[test]
+    fn test_cd_nonexistent_path_errors() {
+        let _lock = lock_current_dir();
+        let orig = stdenv::current_dir().unwrap();
+
+        let mut env = Environment {
+            vars: HashMap::new(),
+            current_dir: orig.clone(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        let name = format!("nonexistent_dir_for_task1_test_{}", std::process::id());
+        let target = Some(name);
+        let cmd = Cd { target };
+        let res = cmd.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut Vec::new(),
+            &mut io::sink(),
+            &mut env,
+        );
+
+        assert!(res.is_err());
+        assert_eq!(stdenv::current_dir().unwrap(), orig);
+    }
+
+    #[test]
+    fn test_cat_reads_file() {
+        let _lock = lock_current_dir();
+
+        // create temp file
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("cat_test_file_{}", std::process::id()));
+        let mut f = fs::File::create(&tmp).expect("create tmp file");
+        write!(f, "hello\nworld\n").expect("write");
+        drop(f);
+
+        let mut env = Environment {
+            vars: HashMap::new(),
+            current_dir: stdenv::current_dir().unwrap(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        // Run cat on file
+        let cat = Cat {
+            files: vec![tmp.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let res = cat.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut env);
+        assert!(res.is_ok());
+
+        let s = String::from_utf8(out).unwrap();
+        assert_eq!(s, "hello\nworld\n");
+
+        let _ = fs::remove_file(tmp);
+    }
+
+    #[test]
+    fn test_file_operands_are_relative_to_shell_dir() -> Result<(), anyhow::Error> {
+        let dir = make_unique_temp_dir()?;
+        fs::create_dir_all(dir.join("sub"))?;
+        fs::write(dir.join("sub/a.txt"), "hello\n")?;
+        let mut env = Environment::new();
+        env.current_dir = dir.clone();
+
+        let cat = Cat::from_args(&["cat"], &["sub/a.txt"]).unwrap();
+        let mut out = Vec::new();
+        cat.execute(&mut io::empty(), &mut out, &mut io::sink(), &mut env)?;
+        assert_eq!(String::from_utf8(out)?, "hello\n");
+
+        let grep = Grep::from_args(&["grep"], &["-r", "hello", "sub"]).unwrap();
+        let mut out = Vec::new();
+        grep.execute(&mut io::empty(), &mut out, &mut io::sink(), &mut env)?;
+        let name = Path::new("sub").join("a.txt");
+        assert_eq!(String::from_utf8(out)?, format!("{}:hello\n", name.display()));
+
+        let _ = fs::remove_dir_all(dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cat_reads_stdin_when_no_args() {
+        let _lock = lock_current_dir();
+
+        let mut env = Environment {
+            vars: HashMap::new(),
+            current_dir: stdenv::current_dir().unwrap(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        let cat = Cat::default();
+        let input = b"from stdin\nline2\n".to_vec();
+        let mut out = Vec::new();
+        let res = cat.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut env);
+        assert!(res.is_ok());
+
+        let s = String::from_utf8(out).unwrap();
+        assert_eq!(s, "from stdin\nline2\n");
+    }
+
+    #[test]
+    fn test_wc_counts_file() {
+        let _lock = lock_current_dir();
+
+        // create temp file
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("wc_test_file_{}", std::process::id()));
+        let mut f = fs::File::create(&tmp).expect("create tmp file");
+        // two lines, three words, bytes include newlines
+        write!(f, "one two\nthree\n").expect("write");
+        drop(f);
+
+        let mut env = Environment {
+            vars: HashMap::new(),
+            current_dir: stdenv::current_dir().unwrap(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        let wc = WC {
+            files: vec![tmp.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let res = wc.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut env);
+        assert!(res.is_ok());
+
+        let s = String::from_utf8(out).unwrap();
+        // Expect format: "<lines> <words> <bytes> <filename>\n", aligned to the widest count
+        // lines = 2, words = 3, bytes = len("one two\nthree\n") = 14
+        let expected_prefix = " 2  3 14 ";
+        assert!(s.starts_with(expected_prefix));
+        assert!(s.trim_end().ends_with(&tmp.to_string_lossy().to_string()));
+
+        let _ = fs::remove_file(tmp);
+    }
+
+    #[test]
+    fn test_wc_counts_stdin_when_no_args() {
+        let _lock = lock_current_dir();
+
+        let mut env = Environment {
+            vars: HashMap::new(),
+            current_dir: stdenv::current_dir().unwrap(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        let wc = WC::default();
+        let input = b"a b c\n".to_vec(); // 1 line, 3 words, bytes = 6 (including newline)
+        let mut out = Vec::new();
+        let res = wc.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut env);
+        assert!(res.is_ok());
+
+        let s = String::from_utf8(out).unwrap();
+        assert_eq!(s, "1 3 6\n");
+    }
+
+    #[test]
+    fn test_wc_multiple_files_output_contains_each_filename() {
+        let _lock = lock_current_dir();
+
+        // create two temp files
+        let mut tmp1 = stdenv::temp_dir();
+        tmp1.push(format!("wc_multi_{}_1", std::process::id()));
+        let mut f1 = fs::File::create(&tmp1).unwrap();
+        writeln!(f1, "a b").unwrap();
+        drop(f1);
+
+        let mut tmp2 = stdenv::temp_dir();
+        tmp2.push(format!("wc_multi_{}_2", std::process::id()));
+        let mut f2 = fs::File::create(&tmp2).unwrap();
+        writeln!(f2, "c").unwrap();
+        drop(f2);
+
+        let mut env = Environment {
+            vars: HashMap::new(),
+            current_dir: stdenv::current_dir().unwrap(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        let wc = WC {
+            files: vec![
+                tmp1.to_string_lossy().to_string(),
+                tmp2.to_string_lossy().to_string(),
+            ],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let res = wc.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut env);
+        assert!(res.is_ok());
+
+        let s = String::from_utf8(out).unwrap();
+        // Should contain two lines, each ending with filename
+        assert!(s.contains(&tmp1.to_string_lossy().to_string()));
+        assert!(s.contains(&tmp2.to_string_lossy().to_string()));
+
+        let _ = fs::remove_file(tmp1);
+        let _ = fs::remove_file(tmp2);
+    }
+
+    #[test]
+    fn test_wc_longest_line() {
+        let mut shell_env = Environment::new();
+        let mut wc_l = |args: &[&str], input: &str| {
+            let wc = WC::from_args(&["wc"], args).unwrap();
+            let mut out = Vec::new();
+            wc.execute(&mut input.as_bytes(), &mut out, &mut io::sink(), &mut shell_env)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(wc_l(&["-L"], "ab\nabcdef\nabc"), "6\n");
+        assert_eq!(wc_l(&["-L"], ""), "0\n");
+        assert_eq!(wc_l(&["-L"], "ab\tc\n"), "9\n", "The tab advances to column 8");
+        assert_eq!(wc_l(&["-L"], "\t\tx\nabcdefgh\n"), "17\n");
+        assert_eq!(wc_l(&["-L"], "абв\n"), "3\n");
+        assert_eq!(wc_l(&["-l", "-L"], "a\nbbbbbbbbbbbb\n"), " 2 12\n");
+    }
+
+    use std::io::Error;
+
+    struct TestEnvironment {
+        temp_dir: PathBuf,
+        file_path: PathBuf,
+    }
+
+    /// Создает изолированную временную директорию и файл внутри нее.
+    /// Возвращает абсолютный путь к файлу и временную директорию.
+    fn setup_test_environment(content: &str) -> Result<TestEnvironment, Error> {
+        let mut p = stdenv::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir_name = format!("grep_test_dir_{}_{}", std::process::id(), nanos);
+        p.push(dir_name);
+        fs::create_dir_all(&p)?;
+
+        let file_path = p.join("test_data.txt");
+        let mut f = fs::File::create(&file_path)?;
+        write!(f, "{}", content)?;
+        drop(f);
+
+        Ok(TestEnvironment {
+            temp_dir: p,
+            file_path,
+        })
+    }
+
+    #[test]
+    fn test_grep_ignore_case_i_isolated() -> Result<(), anyhow::Error> {
+        let env = setup_test_environment("Target 1\nTaRgEt 2\nNo match\n")
+            .context("Failed to set up environment")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let filename = env.file_path.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![filename.clone()],
+            word_regexp: false,
+            ignore_case: true, // <- -i
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["target".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        let expected = "Target 1\nTaRgEt 2\n";
+        assert_eq!(s, expected);
+
+        fs::remove_dir_all(env.temp_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_trailing_context_a_1_isolated() -> Result<(), anyhow::Error> {
+        let content = "Line 1\nMATCH 1\nLine 3\nLine 4\nMATCH 2\nLine 6\nLine 7\nLine 8\n";
+        let env = setup_test_environment(content).context("Failed to set up environment")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let filename = env.file_path.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![filename.clone()],
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 1, // <- -A 1
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        let expected = "MATCH 1\n\
+            Line 3\n\
+            --\n\
+            MATCH 2\n\
+            Line 6\n";
+
+        assert_eq!(s, expected);
+
+        fs::remove_dir_all(env.temp_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_context_overlap_a_2_isolated() -> Result<(), anyhow::Error> {
+        let content = "MATCH 1\nLine 2\nMATCH 2\nLine 4\nLine 5\nLine 6\n";
+        let env = setup_test_environment(content).context("Failed to set up environment")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let filename = env.file_path.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![filename.clone()],
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 2, // <- -A 2
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        let expected = "MATCH 1\n\
+            Line 2\n\
+            MATCH 2\n\
+            Line 4\n\
+            Line 5\n";
+
+        assert_eq!(s, expected);
+
+        fs::remove_dir_all(env.temp_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_stdin_pipe_isolated() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+
+        let grep = Grep {
+            files: Vec::new(), // <- stdin
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["pipe".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        let input = b"Line 1\nLine with pipe target\nLine 3\n".to_vec();
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        // При чтении из stdin префикс файла отсутствует
+        assert_eq!(s, "Line with pipe target\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_invert_match_v_stdin() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+
+        let grep = Grep {
+            files: Vec::new(),
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: true, // <- -v
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["an".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        let input = b"apple\nbanana\ncherry\n".to_vec();
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        assert_eq!(s, "apple\ncherry\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_invert_match_with_trailing_context() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+
+        let grep = Grep {
+            files: Vec::new(),
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 1,   // <- -A 1
+            before_context: 0,
+            context: 0,
+            invert_match: true, // <- -v
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["skip".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        let input = b"skip 1\nkeep 2\nskip 3\nskip 4\nskip 5\nkeep 6\n".to_vec();
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        assert_eq!(s, "keep 2\nskip 3\n--\nkeep 6\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_line_number_n_with_context() -> Result<(), anyhow::Error> {
+        let content = "Line 1\nMATCH 2\nLine 3\nLine 4\nMATCH 5\nLine 6\nLine 7\n";
+        let env = setup_test_environment(content).context("Failed to set up environment")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let filename = env.file_path.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![filename.clone()],
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 1, // <- -A 1
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: true, // <- -n
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        let expected = "2:MATCH 2\n\
+            3:Line 3\n\
+            --\n\
+            5:MATCH 5\n\
+            6:Line 6\n";
+        assert_eq!(s, expected);
+
+        fs::remove_dir_all(env.temp_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_line_number_n_stdin() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+
+        let grep = Grep {
+            files: Vec::new(),
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: true, // <- -n
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        let input = b"Line 1\nMATCH 2\nLine 3\nLine 4\nMATCH 5\n".to_vec();
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        assert_eq!(s, "2:MATCH 2\n5:MATCH 5\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_count_c_single_file() -> Result<(), anyhow::Error> {
+        let env = setup_test_environment("MATCH 1\nLine 2\nMATCH 3\n")
+            .context("Failed to set up environment")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let filename = env.file_path.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![filename],
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: true, // <- -c
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "2\n");
+
+        fs::remove_dir_all(env.temp_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_count_c_stdin_inverted() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+
+        let grep = Grep {
+            files: Vec::new(),
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: true, // <- -v
+            line_number: false,
+            count: true, // <- -c
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["an".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        let input = b"apple\nbanana\ncherry\n".to_vec();
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "2\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_count_c_multiple_files_prefixed() -> Result<(), anyhow::Error> {
+        let env1 = setup_test_environment("MATCH\nLine\n").context("Failed to set up environment")?;
+        let env2 = setup_test_environment("MATCH\nMATCH\nMATCH\n")
+            .context("Failed to set up environment")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let filename1 = env1.file_path.to_string_lossy().to_string();
+        let filename2 = env2.file_path.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![filename1.clone(), filename2.clone()],
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: true, // <- -c
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let expected = format!("{}:1\n{}:3\n", filename1, filename2);
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        fs::remove_dir_all(env1.temp_dir)?;
+        fs::remove_dir_all(env2.temp_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_leading_context_b_1_isolated() -> Result<(), anyhow::Error> {
+        let content = "Line 1\nMATCH 1\nLine 3\nLine 4\nMATCH 2\nLine 6\n";
+        let env = setup_test_environment(content).context("Failed to set up environment")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let filename = env.file_path.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![filename.clone()],
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 1, // <- -B 1
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        let expected = "Line 1\n\
+            MATCH 1\n\
+            --\n\
+            Line 4\n\
+            MATCH 2\n";
+
+        assert_eq!(s, expected);
+
+        fs::remove_dir_all(env.temp_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_combined_context_c_1_isolated() -> Result<(), anyhow::Error> {
+        let content = "Line 1\nMATCH 1\nLine 3\nLine 4\nLine 5\nMATCH 2\nLine 7\n";
+        let env = setup_test_environment(content).context("Failed to set up environment")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let filename = env.file_path.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![filename.clone()],
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 1, // <- -C 1
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        let expected = "Line 1\n\
+            MATCH 1\n\
+            Line 3\n\
+            --\n\
+            Line 5\n\
+            MATCH 2\n\
+            Line 7\n";
+
+        assert_eq!(s, expected);
+
+        fs::remove_dir_all(env.temp_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_recursive_r_walks_directory_tree() -> Result<(), anyhow::Error> {
+        let env = setup_test_environment("Line 1\nMATCH top\n")
+            .context("Failed to set up environment")?;
+        let nested_dir = env.temp_dir.join("nested");
+        fs::create_dir_all(&nested_dir)?;
+        fs::write(nested_dir.join("deep.txt"), "MATCH deep\nLine 2\n")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let dirname = env.temp_dir.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![dirname],
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: true, // <- -r
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+
+        let expected = format!(
+            "{}:MATCH deep\n{}:MATCH top\n",
+            nested_dir.join("deep.txt").to_string_lossy(),
+            env.file_path.to_string_lossy()
+        );
+        assert_eq!(s, expected);
+
+        fs::remove_dir_all(env.temp_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_names_files_only_when_there_are_several() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        fs::write(dir.join("f.txt"), "hi\n")?;
+        fs::write(dir.join("g.txt"), "hi there\n")?;
+
+        let mut grep = |args: &[&str]| -> Result<String, anyhow::Error> {
+            let grep = Grep::from_args(&["grep"], args).unwrap();
+            let mut out = Vec::new();
+            grep.execute(&mut io::empty(), &mut out, &mut io::sink(), shell_env)?;
+            Ok(String::from_utf8(out)?)
+        };
+        assert_eq!(grep(&["hi", "f.txt"])?, "hi\n");
+        assert_eq!(grep(&["-n", "hi", "f.txt"])?, "1:hi\n");
+        assert_eq!(grep(&["hi", "f.txt", "g.txt"])?, "f.txt:hi\ng.txt:hi there\n");
+        // -l prints the name even for a lone file
+        assert_eq!(grep(&["-l", "hi", "f.txt"])?, "f.txt\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_files_with_matches_l_lists_only_matching_files() -> Result<(), anyhow::Error> {
+        let env1 = setup_test_environment("Line 1\nMATCH\nMATCH again\n")
+            .context("Failed to set up environment")?;
+        let env2 = setup_test_environment("Line 1\nLine 2\n").context("Failed to set up environment")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let filename1 = env1.file_path.to_string_lossy().to_string();
+        let filename2 = env2.file_path.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![filename1.clone(), filename2],
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: true, // <- -l
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", filename1));
+
+        fs::remove_dir_all(env1.temp_dir)?;
+        fs::remove_dir_all(env2.temp_dir)?;
+        Ok(())
+    }
+
+    /// Yields a single chunk of data and fails on every subsequent read.
+    struct FailAfterFirstChunk(Option<Vec<u8>>);
+
+    impl Read for FailAfterFirstChunk {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.take() {
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                None => Err(io::Error::other("read past the first match")),
+            }
+        }
+    }
+
+    #[test]
+    fn test_grep_files_with_matches_l_stops_at_first_match() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+
+        let grep = Grep {
+            files: Vec::new(),
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: true, // <- -l
+            fixed_string: false,
+            only_matching: false,
+            patterns: vec!["MATCH".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        let mut input = FailAfterFirstChunk(Some(b"MATCH\n".to_vec()));
+
+        assert_eq!(grep.execute(&mut input, &mut out, &mut io::sink(), &mut shell_env)?, 0);
+        assert_eq!(String::from_utf8(out).unwrap(), "(standard input)\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_fixed_string_f_isolated() -> Result<(), anyhow::Error> {
+        let env = setup_test_environment("axb\na.b\n").context("Failed to set up environment")?;
+
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+        let filename = env.file_path.to_string_lossy().to_string();
+
+        let grep = Grep {
+            files: vec![filename.clone()],
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: true, // <- -F
+            only_matching: false,
+            patterns: vec!["a.b".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "a.b\n");
+
+        fs::remove_dir_all(env.temp_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_fixed_string_f_with_word_and_ignore_case() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
+
+        let grep = Grep {
+            files: Vec::new(),
+            word_regexp: true,  // <- -w
+            ignore_case: true,  // <- -i
+            after_context: 0,
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: true, // <- -F
+            only_matching: false,
+            patterns: vec!["A.B".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        let input = b"xa.b\nsay a.b now\naxb\n".to_vec();
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "say a.b now\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_exit_parse_code_defaults_to_zero() {
+        assert_eq!(parse_exit_code(&[]), Ok(0));
+    }
+
+    #[test]
+    fn test_exit_parse_code_accepts_u8_range() {
+        assert_eq!(parse_exit_code(&["0".to_string()]), Ok(0));
+        assert_eq!(parse_exit_code(&["42".to_string()]), Ok(42));
+        assert_eq!(parse_exit_code(&["255".to_string()]), Ok(255));
+    }
+
+    #[test]
+    fn test_exit_parse_code_rejects_invalid_arguments() {
+        let expected = Err("exit: numeric argument required".to_string());
+        assert_eq!(parse_exit_code(&["256".to_string()]), expected);
+        assert_eq!(parse_exit_code(&["-1".to_string()]), expected);
+        assert_eq!(parse_exit_code(&["abc".to_string()]), expected);
+    }
+
+    #[test]
+    fn test_env_prints_sorted_vars() {
+        let mut shell_env = Environment {
+            vars: HashMap::from([
+                ("B".to_string(), "2".to_string()),
+                ("A".to_string(), "1".to_string()),
+            ]),
+            current_dir: stdenv::current_dir().unwrap(),
+            should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
+        };
+
+        let cmd = Factory::<Env>::default()
+            .try_create(&shell_env, "env", &[])
+            .unwrap();
+        let (out, out_rc) = crate::MemWriter::with_handle();
+        let code = cmd.execute(
+            Box::new(crate::MemReader::new(Vec::new())),
+            Box::new(out),
+            Box::new(crate::MemWriter::new()),
+            &mut shell_env,
+        );
+
+        assert_eq!(code.unwrap(), 0);
+        assert_eq!(String::from_utf8(out_rc.borrow().clone()).unwrap(), "A=1\nB=2\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_env_unsets_var_for_command() {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("env_unset_out_{}", std::process::id()));
+        let out = fs::File::create(&tmp).expect("create tmp file");
+
+        let mut shell_env = Environment::new();
+        shell_env.set_var("GREETING", "hello");
+        let cmd = Factory::<Env>::default()
+            .try_create(
+                &shell_env,
+                "env",
+                &["-u", "GREETING", "-u", "HOME", "sh", "-c", "echo \"[$GREETING$HOME]\""],
+            )
+            .unwrap();
+        let code = cmd.execute(
+            Box::new(crate::MemReader::new(Vec::new())),
+            Box::new(out),
+            Box::new(crate::MemWriter::new()),
+            &mut shell_env,
+        );
+
+        assert_eq!(code.unwrap(), 0);
+        assert_eq!(fs::read_to_string(&tmp).unwrap(), "[]\n");
+        // Only the command's environment is affected
+        assert_eq!(shell_env.get_var("GREETING"), Some("hello".to_string()));
+
+        let (code, _, err) = run_with_factory(&Factory::<Env>::default(), "env", &["-u"]);
+        assert_eq!(code, 125);
+        assert!(err.contains("requires an argument"));
+
+        let _ = fs::remove_file(tmp);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_env_runs_command_with_extra_vars() {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("env_cmd_out_{}", std::process::id()));
+        let out = fs::File::create(&tmp).expect("create tmp file");
+
+        let mut shell_env = Environment::new();
+        let cmd = Factory::<Env>::default()
+            .try_create(
+                &shell_env,
+                "env",
+                &["GREETING=hello", "sh", "-c", "echo \"$GREETING\""],
+            )
+            .unwrap();
+        let code = cmd.execute(
+            Box::new(crate::MemReader::new(Vec::new())),
+            Box::new(out),
+            Box::new(crate::MemWriter::new()),
+            &mut shell_env,
+        );
+
+        assert_eq!(code.unwrap(), 0);
+        assert_eq!(fs::read_to_string(&tmp).unwrap(), "hello\n");
+        // The assignment must not leak into the shell's own environment
+        assert!(!shell_env.vars.contains_key("GREETING"));
+
+        let _ = fs::remove_file(tmp);
+    }
+
+    #[test]
+    fn test_head_stdin_default_and_n() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let input: String = (1..=12).map(|i| format!("{}\n", i)).collect();
+
+        let head = Head {
+            lines: 10,
+            bytes: None,
+            files: vec![],
+        };
+        let mut out = Vec::new();
+        assert_eq!(
+            head.execute(
+                &mut Cursor::new(input.clone()),
+                &mut out,
+                &mut io::sink(),
+                &mut shell_env,
+            )?,
+            0
+        );
+        let expected: String = (1..=10).map(|i| format!("{}\n", i)).collect();
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        let head = Head {
+            lines: 2,
+            bytes: None,
+            files: vec![],
+        };
+        let mut out = Vec::new();
+        head.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(String::from_utf8(out).unwrap(), "1\n2\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_head_single_file_has_no_header() -> Result<(), anyhow::Error> {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("head_single_{}", std::process::id()));
+        fs::write(&tmp, "a\nb\nc\n")?;
+
+        let mut shell_env = Environment::new();
+        let head = Head {
+            lines: 2,
+            bytes: None,
+            files: vec![tmp.to_string_lossy().to_string()],
+        };
+        let mut out = Vec::new();
+        assert_eq!(
+            head.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "a\nb\n");
+
+        let _ = fs::remove_file(tmp);
+        Ok(())
+    }
+
+    #[test]
+    fn test_head_multiple_files_print_headers() -> Result<(), anyhow::Error> {
+        let dir = stdenv::temp_dir();
+        let first = dir.join(format!("head_multi_{}_1", std::process::id()));
+        let second = dir.join(format!("head_multi_{}_2", std::process::id()));
+        fs::write(&first, "1\n2\n3\n")?;
+        fs::write(&second, "x\n")?;
+
+        let mut shell_env = Environment::new();
+        let first_name = first.to_string_lossy().to_string();
+        let second_name = second.to_string_lossy().to_string();
+        let head = Head {
+            lines: 2,
+            bytes: None,
+            files: vec![first_name.clone(), second_name.clone()],
+        };
+        let mut out = Vec::new();
+        assert_eq!(
+            head.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let expected = format!(
+            "==> {} <==\n1\n2\n\n==> {} <==\nx\n",
+            first_name, second_name
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        let _ = fs::remove_file(first);
+        let _ = fs::remove_file(second);
+        Ok(())
+    }
+
+    #[test]
+    fn test_head_and_tail_count_bytes() -> Result<(), anyhow::Error> {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("head_bytes_{}", std::process::id()));
+        fs::write(&tmp, b"\x89PNG\r\n\x1a\nrest")?;
+        let file_name = tmp.to_string_lossy().to_string();
+
+        let mut shell_env = Environment::new();
+        let head = Head::from_args(&["head"], &["-c", "4", &file_name]).unwrap();
+        let mut out = Vec::new();
+        head.execute(&mut io::empty(), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(out, b"\x89PNG");
+
+        let tail = Tail::from_args(&["tail"], &["-c", "4"]).unwrap();
+        let mut out = Vec::new();
+        tail.execute(&mut fs::File::open(&tmp)?, &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(out, b"rest");
+
+        let _ = fs::remove_file(tmp);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tail_last_n_lines_of_file() -> Result<(), anyhow::Error> {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("tail_last_{}", std::process::id()));
+        let contents: String = (1..=12).map(|i| format!("{}\n", i)).collect();
+        fs::write(&tmp, contents)?;
+
+        let mut shell_env = Environment::new();
+        let tail = Tail::from_args(&["tail"], &[tmp.to_str().unwrap()]).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(
+            tail.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let expected: String = (3..=12).map(|i| format!("{}\n", i)).collect();
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        let tail = Tail::from_args(&["tail"], &["-n", "2", tmp.to_str().unwrap()]).unwrap();
+        let mut out = Vec::new();
+        tail.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(String::from_utf8(out).unwrap(), "11\n12\n");
+
+        let _ = fs::remove_file(tmp);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tail_plus_k_starts_at_line() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let tail = Tail::from_args(&["tail"], &["-n", "+3"]).unwrap();
+        assert_eq!(tail.lines, TailLines::From(3));
+
+        let mut out = Vec::new();
+        tail.execute(
+            &mut Cursor::new(b"1\n2\n3\n4\n5\n".to_vec()),
+            &mut out,
+            &mut io::sink(),
+            &mut shell_env,
+        )?;
+        assert_eq!(String::from_utf8(out).unwrap(), "3\n4\n5\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_tail_stdin_and_multiple_files() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let tail = Tail {
+            lines: TailLines::Last(1),
+            bytes: None,
+            files: vec![],
+        };
+        let mut out = Vec::new();
+        tail.execute(
+            &mut Cursor::new(b"a\nb\n".to_vec()),
+            &mut out,
+            &mut io::sink(),
+            &mut shell_env,
+        )?;
+        assert_eq!(String::from_utf8(out).unwrap(), "b\n");
+
+        let dir = stdenv::temp_dir();
+        let first = dir.join(format!("tail_multi_{}_1", std::process::id()));
+        let second = dir.join(format!("tail_multi_{}_2", std::process::id()));
+        fs::write(&first, "1\n2\n")?;
+        fs::write(&second, "x\ny\n")?;
+        let first_name = first.to_string_lossy().to_string();
+        let second_name = second.to_string_lossy().to_string();
+
+        let tail = Tail {
+            lines: TailLines::Last(1),
+            bytes: None,
+            files: vec![first_name.clone(), second_name.clone()],
+        };
+        let mut out = Vec::new();
+        tail.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?;
+        let expected = format!(
+            "==> {} <==\n2\n\n==> {} <==\ny\n",
+            first_name, second_name
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        let _ = fs::remove_file(first);
+        let _ = fs::remove_file(second);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wc_selection_flags() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let input = b"a b c\nd\n".to_vec();
+
+        let wc = WC::from_args(&["wc"], &["-l"]).unwrap();
+        let mut out = Vec::new();
+        wc.execute(&mut Cursor::new(input.clone()), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(String::from_utf8(out).unwrap(), "2\n");
+
+        // Order is always lines, words, chars, bytes regardless of flag order
+        let wc = WC::from_args(&["wc"], &["-c", "-w"]).unwrap();
+        let mut out = Vec::new();
+        wc.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(String::from_utf8(out).unwrap(), "4 8\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_wc_chars_differ_from_bytes_for_utf8() -> Result<(), anyhow::Error> {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("wc_utf8_{}", std::process::id()));
+        // 6 Cyrillic characters plus a newline, each letter takes two bytes
+        fs::write(&tmp, "привет\n")?;
+        let name = tmp.to_string_lossy().to_string();
+
+        let mut shell_env = Environment::new();
+        let wc = WC::from_args(&["wc"], &["-m", &name]).unwrap();
+        let mut out = Vec::new();
+        wc.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(String::from_utf8(out).unwrap(), format!("7 {}\n", name));
+
+        let wc = WC::from_args(&["wc"], &["-c", &name]).unwrap();
+        let mut out = Vec::new();
+        wc.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(String::from_utf8(out).unwrap(), format!("13 {}\n", name));
+
+        let _ = fs::remove_file(tmp);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wc_aligns_columns() -> Result<(), anyhow::Error> {
+        let dir = stdenv::temp_dir();
+        let small = dir.join(format!("wc_align_{}_small", std::process::id()));
+        let large = dir.join(format!("wc_align_{}_large", std::process::id()));
+        fs::write(&small, "a\n")?; // 1 1 2
+        fs::write(&large, "word\n".repeat(2000))?; // 2000 2000 10000
+        let small_name = small.to_string_lossy().to_string();
+        let large_name = large.to_string_lossy().to_string();
+
+        let mut shell_env = Environment::new();
+        let wc = WC {
+            files: vec![small_name.clone(), large_name.clone()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        wc.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?;
+        let expected = format!(
+            "    1     1     2 {}\n 2000  2000 10000 {}\n 2001  2001 10002 total\n",
+            small_name, large_name
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        let _ = fs::remove_file(small);
+        let _ = fs::remove_file(large);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cat_passes_binary_stdin_through() {
+        let input = vec![0x00, 0xff, b'a', b'\n', 0xc3, 0x28, 0x00];
+        let cmd = Factory::<Cat>::default()
+            .try_create(&Environment::new(), "cat", &[])
+            .unwrap();
+        let (out, out_rc) = crate::MemWriter::with_handle();
+        let code = cmd.execute(
+            Box::new(crate::MemReader::new(input.clone())),
+            Box::new(out),
+            Box::new(crate::MemWriter::new()),
+            &mut Environment::new(),
+        );
+
+        assert_eq!(code.unwrap(), 0);
+        assert_eq!(*out_rc.borrow(), input);
+    }
+
+    #[test]
+    fn test_wc_counts_invalid_utf8_bytes() -> Result<(), anyhow::Error> {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("wc_latin1_{}", std::process::id()));
+        // "café" in Latin-1 followed by a word: 0xE9 is not valid UTF-8 on its own
+        fs::write(&tmp, b"caf\xe9 ok\n")?;
+        let name = tmp.to_string_lossy().to_string();
+
+        let mut shell_env = Environment::new();
+        let wc = WC::from_args(&["wc"], &[&name]).unwrap();
+        let mut out = Vec::new();
+        let code = wc.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut out,
+            &mut io::sink(),
+            &mut shell_env,
+        )?;
+        assert_eq!(code, 0);
+        assert_eq!(String::from_utf8(out).unwrap(), format!("1 2 8 {}\n", name));
+
+        let _ = fs::remove_file(tmp);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wc_multiple_files_prints_total() -> Result<(), anyhow::Error> {
+        let dir = stdenv::temp_dir();
+        let first = dir.join(format!("wc_total_{}_1", std::process::id()));
+        let second = dir.join(format!("wc_total_{}_2", std::process::id()));
+        fs::write(&first, "one two\nthree\n")?; // 2 3 14
+        fs::write(&second, "four\n")?; // 1 1 5
+        let first_name = first.to_string_lossy().to_string();
+        let second_name = second.to_string_lossy().to_string();
+
+        let mut shell_env = Environment::new();
+        let wc = WC {
+            files: vec![first_name.clone(), second_name.clone()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        assert_eq!(
+            wc.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let expected = format!(
+            " 2  3 14 {}\n 1  1  5 {}\n 3  4 19 total\n",
+            first_name, second_name
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        let _ = fs::remove_file(first);
+        let _ = fs::remove_file(second);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wc_continues_after_unreadable_file() -> Result<(), anyhow::Error> {
+        let dir = make_unique_temp_dir()?;
+        fs::write(dir.join("a.txt"), "one two\n")?;
+        fs::write(dir.join("b.txt"), "three\n")?;
+        let [a, missing, b] = ["a.txt", "missing.txt", "b.txt"].map(|name| {
+            dir.join(name).to_string_lossy().into_owned()
+        });
+
+        let mut shell_env = Environment::new();
+        let wc = WC {
+            files: vec![a.clone(), missing.clone(), b.clone()],
+            ..Default::default()
+        };
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        assert_eq!(wc.execute(&mut io::empty(), &mut out, &mut err, &mut shell_env)?, 1);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(" 1  2  8 {}\n 1  1  6 {}\n 2  3 14 total\n", a, b)
+        );
+        let err = String::from_utf8(err).unwrap();
+        assert!(err.starts_with(&format!("wc: {}: ", missing)), "{}", err);
+
+        let _ = fs::remove_dir_all(dir);
+        Ok(())
+    }
 
-        let cmd = Cd { target: None };
-        let res = cmd.execute(&mut Cursor::new(Vec::new()), &mut Vec::new(), &mut env);
+    #[test]
+    fn test_cat_number_lines_n() -> Result<(), anyhow::Error> {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("cat_number_{}", std::process::id()));
+        fs::write(&tmp, "first\nsecond\n")?;
 
-        assert!(res.is_ok());
+        let mut shell_env = Environment::new();
+        let cat = Cat {
+            number: true,
+            files: vec![tmp.to_string_lossy().to_string()],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        assert_eq!(
+            cat.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "     1\tfirst\n     2\tsecond\n"
+        );
 
-        let new_cwd = stdenv::current_dir().unwrap();
-        let new_canonical = fs::canonicalize(&new_cwd).unwrap();
+        let _ = fs::remove_file(tmp);
+        Ok(())
+    }
 
-        assert_eq!(new_canonical, canonical_temp);
-        assert_eq!(env.current_dir, canonical_temp);
+    #[test]
+    fn test_cat_number_nonblank_b_skips_blank_lines() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let cat = Cat::from_args(&["cat"], &["-b"]).unwrap();
+        let mut out = Vec::new();
+        cat.execute(
+            &mut Cursor::new(b"a\n\nb\n".to_vec()),
+            &mut out,
+            &mut io::sink(),
+            &mut shell_env,
+        )?;
+        assert_eq!(String::from_utf8(out).unwrap(), "     1\ta\n\n     2\tb\n");
+        Ok(())
+    }
 
-        stdenv::set_current_dir(orig).expect("failed to restore cwd");
+    #[test]
+    fn test_cat_squeeze_blank_s() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let cat = Cat::from_args(&["cat"], &["-s"]).unwrap();
+        let mut out = Vec::new();
+        cat.execute(
+            &mut Cursor::new(b"a\n\n\n\nb\n".to_vec()),
+            &mut out,
+            &mut io::sink(),
+            &mut shell_env,
+        )?;
+        assert_eq!(String::from_utf8(out).unwrap(), "a\n\nb\n");
+        Ok(())
+    }
 
-        let _ = fs::remove_dir_all(&temp);
+    #[test]
+    fn test_cat_show_ends_e() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let cat = Cat::from_args(&["cat"], &["-E"]).unwrap();
+        let mut out = Vec::new();
+        cat.execute(
+            &mut Cursor::new(b"trailing \n\nlast".to_vec()),
+            &mut out,
+            &mut io::sink(),
+            &mut shell_env,
+        )?;
+        assert_eq!(String::from_utf8(out).unwrap(), "trailing $\n$\nlast");
+        Ok(())
+    }
+
+    fn make_ls_fixture() -> Result<PathBuf, anyhow::Error> {
+        let dir = make_unique_temp_dir()?;
+        fs::write(dir.join("b.txt"), "hello")?;
+        fs::write(dir.join("a.txt"), "")?;
+        fs::write(dir.join(".hidden"), "")?;
+        fs::create_dir(dir.join("sub"))?;
+        Ok(dir)
     }
 
     #[test]
-    fn test_cd_nonexistent_path_errors() {
+    fn test_ls_lists_sorted_and_hides_dotfiles() -> Result<(), anyhow::Error> {
+        let dir = make_ls_fixture()?;
+        let mut shell_env = Environment::new();
+        shell_env.current_dir = dir.clone();
+
+        let mut out = Vec::new();
+        let ls = Ls::default();
+        assert_eq!(
+            ls.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "a.txt\nb.txt\nsub\n");
+
+        let mut out = Vec::new();
+        let ls = Ls::from_args(&["ls"], &["-a"]).unwrap();
+        ls.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ".hidden\na.txt\nb.txt\nsub\n"
+        );
+
+        let _ = fs::remove_dir_all(dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ls_long_format_resolves_relative_to_env_dir() -> Result<(), anyhow::Error> {
+        let dir = make_ls_fixture()?;
+        let mut shell_env = Environment::new();
+        shell_env.current_dir = dir.parent().unwrap().to_path_buf();
+        let relative = dir.file_name().unwrap().to_string_lossy().to_string();
+
+        let mut out = Vec::new();
+        let ls = Ls::from_args(&["ls"], &["-l", &relative]).unwrap();
+        assert_eq!(
+            ls.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        let s = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = s.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].split_whitespace().collect::<Vec<_>>(), ["-", "0", "a.txt"]);
+        assert_eq!(lines[1].split_whitespace().collect::<Vec<_>>(), ["-", "5", "b.txt"]);
+        assert!(lines[2].starts_with('d') && lines[2].ends_with(" sub"));
+
+        let _ = fs::remove_dir_all(dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cd_searches_cdpath() {
         let _lock = lock_current_dir();
+        let temp = make_unique_temp_dir().expect("failed to create temp dir");
+        let canonical_temp = fs::canonicalize(&temp).expect("canonicalize failed");
+        fs::create_dir_all(canonical_temp.join("projects/app")).unwrap();
+        fs::create_dir_all(canonical_temp.join("elsewhere/local")).unwrap();
         let orig = stdenv::current_dir().unwrap();
 
-        let mut env = Environment {
-            vars: HashMap::new(),
-            current_dir: orig.clone(),
-            should_exit: false,
+        let mut env = Environment::new();
+        env.current_dir = canonical_temp.join("elsewhere");
+        let projects = canonical_temp.join("projects");
+        env.set_var("CDPATH", format!("/nonexistent:{}", projects.display()));
+        let cd = |target: &str, env: &mut Environment| {
+            let cmd = Cd {
+                target: Some(target.to_string()),
+            };
+            let mut out = Vec::new();
+            let res = cmd.execute(&mut io::empty(), &mut out, &mut io::sink(), env);
+            (res.is_ok(), String::from_utf8(out).unwrap())
         };
 
-        let name = format!("nonexistent_dir_for_task1_test_{}", std::process::id());
-        let target = Some(name);
-        let cmd = Cd { target };
-        let res = cmd.execute(&mut Cursor::new(Vec::new()), &mut Vec::new(), &mut env);
+        let app = canonical_temp.join("projects/app");
+        let (ok, out) = cd("app", &mut env);
+        assert!(ok);
+        assert_eq!(env.current_dir, app);
+        assert_eq!(out, format!("{}\n", app.display()), "A directory found in CDPATH is printed");
 
-        assert!(res.is_err());
-        assert_eq!(stdenv::current_dir().unwrap(), orig);
+        // Paths starting with `.` or `..` skip CDPATH
+        env.current_dir = canonical_temp.join("elsewhere");
+        assert!(!cd("./app", &mut env).0);
+
+        // The current directory is the fallback, and isn't printed
+        let (ok, out) = cd("local", &mut env);
+        stdenv::set_current_dir(orig).expect("failed to restore cwd");
+        assert!(ok);
+        assert_eq!(env.current_dir, canonical_temp.join("elsewhere/local"));
+        assert_eq!(out, "");
+
+        let _ = fs::remove_dir_all(&temp);
     }
 
     #[test]
-    fn test_cat_reads_file() {
+    fn test_cd_dash_toggles_to_previous_dir() {
         let _lock = lock_current_dir();
-
-        // create temp file
-        let mut tmp = stdenv::temp_dir();
-        tmp.push(format!("cat_test_file_{}", std::process::id()));
-        let mut f = fs::File::create(&tmp).expect("create tmp file");
-        write!(f, "hello\nworld\n").expect("write");
-        drop(f);
+        let temp = make_unique_temp_dir().expect("failed to create temp dir");
+        let canonical_temp = fs::canonicalize(&temp).expect("canonicalize failed");
+        let orig = stdenv::current_dir().unwrap();
+        let canonical_orig = fs::canonicalize(&orig).unwrap();
 
         let mut env = Environment {
             vars: HashMap::new(),
-            current_dir: stdenv::current_dir().unwrap(),
+            current_dir: canonical_orig.clone(),
             should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
         };
 
-        // Run cat on file
-        let cat = Cat {
-            files: vec![tmp.to_string_lossy().to_string()],
+        let cmd = Cd {
+            target: Some(canonical_temp.to_string_lossy().to_string()),
         };
-        let mut out = Vec::new();
-        let res = cat.execute(&mut Cursor::new(Vec::new()), &mut out, &mut env);
+        let res = cmd.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut Vec::new(),
+            &mut io::sink(),
+            &mut env,
+        );
         assert!(res.is_ok());
+        assert_eq!(env.current_dir, canonical_temp);
+        assert_eq!(
+            env.get_var("OLDPWD"),
+            Some(canonical_orig.to_string_lossy().to_string())
+        );
+        assert_eq!(
+            env.get_var("PWD"),
+            Some(canonical_temp.to_string_lossy().to_string())
+        );
 
-        let s = String::from_utf8(out).unwrap();
-        assert_eq!(s, "hello\nworld\n");
+        let cmd = Factory::<Cd>::default()
+            .try_create(&env, "cd", &["-"])
+            .unwrap();
+        let (out, out_rc) = crate::MemWriter::with_handle();
+        let res = cmd.execute(
+            Box::new(crate::MemReader::new(Vec::new())),
+            Box::new(out),
+            Box::new(crate::MemWriter::new()),
+            &mut env,
+        );
+        assert_eq!(res.unwrap(), 0);
+        assert_eq!(env.current_dir, canonical_orig);
+        assert_eq!(
+            String::from_utf8(out_rc.borrow().clone()).unwrap(),
+            format!("{}\n", canonical_orig.display())
+        );
 
-        let _ = fs::remove_file(tmp);
+        // And back again
+        let cmd = Factory::<Cd>::default()
+            .try_create(&env, "cd", &["-"])
+            .unwrap();
+        let res = cmd.execute(
+            Box::new(crate::MemReader::new(Vec::new())),
+            Box::new(crate::MemWriter::new()),
+            Box::new(crate::MemWriter::new()),
+            &mut env,
+        );
+        assert_eq!(res.unwrap(), 0);
+        assert_eq!(env.current_dir, canonical_temp);
+
+        stdenv::set_current_dir(orig).expect("failed to restore cwd");
+        let _ = fs::remove_dir_all(&temp);
     }
 
     #[test]
-    fn test_cat_reads_stdin_when_no_args() {
+    fn test_echo_e_interprets_escapes() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let echo = Echo::from_args(&["echo"], &["-e", "a\\tb", "c\\\\d\\x"]).unwrap();
+        let mut out = Vec::new();
+        echo.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(String::from_utf8(out).unwrap(), "a\tb c\\d\\x\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_echo_keeps_backslashes_without_e() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        for args in [&["a\\tb"][..], &["-e", "-E", "a\\tb"][..]] {
+            let echo = Echo::from_args(&["echo"], args).unwrap();
+            let mut out = Vec::new();
+            echo.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?;
+            assert_eq!(String::from_utf8(out).unwrap(), "a\\tb\n");
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_which_finds_program_in_path() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let which = Which::from_args(&["which"], &["sh"]).unwrap();
+        let mut out = Vec::new();
+        let code = which.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut out,
+            &mut io::sink(),
+            &mut shell_env,
+        )?;
+
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(code, 0);
+        assert!(Path::new(out.trim_end()).is_absolute(), "got {:?}", out);
+        assert!(out.ends_with("/sh\n"), "got {:?}", out);
+        Ok(())
+    }
+
+    #[test]
+    fn test_which_reports_builtins_and_missing_commands() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        shell_env.builtins = BTreeMap::from([("echo".to_string(), String::new())]);
+        let which =
+            Which::from_args(&["which"], &["echo", "definitely_missing_cmd_12345"]).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = which.execute(&mut Cursor::new(Vec::new()), &mut out, &mut err, &mut shell_env)?;
+
+        assert_eq!(code, 1);
+        assert_eq!(String::from_utf8(out).unwrap(), "echo: shell builtin\n");
+        assert_eq!(
+            String::from_utf8(err).unwrap(),
+            "definitely_missing_cmd_12345: not found\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_alias_defines_and_lists() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let alias = Alias::from_args(&["alias"], &["ll=ls -l", "la=ls -a"]).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(alias.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut out,
+            &mut io::sink(),
+            &mut shell_env)?, 0,
+        );
+        assert!(out.is_empty());
+
+        let alias = Alias::from_args(&["alias"], &[]).unwrap();
+        let mut out = Vec::new();
+        alias.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "alias la='ls -a'\nalias ll='ls -l'\n"
+        );
+
+        let alias = Alias::from_args(&["alias"], &["ll", "nope"]).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = alias.execute(&mut Cursor::new(Vec::new()), &mut out, &mut err, &mut shell_env)?;
+        assert_eq!(code, 1);
+        assert_eq!(String::from_utf8(out).unwrap(), "alias ll='ls -l'\n");
+        assert_eq!(String::from_utf8(err).unwrap(), "alias: nope: not found\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_pushd_popd_round_trip() -> Result<(), anyhow::Error> {
         let _lock = lock_current_dir();
+        let temp = make_unique_temp_dir().expect("failed to create temp dir");
+        let canonical_temp = fs::canonicalize(&temp).expect("canonicalize failed");
+        let orig = stdenv::current_dir().unwrap();
+        let canonical_orig = fs::canonicalize(&orig).unwrap();
 
-        let mut env = Environment {
-            vars: HashMap::new(),
-            current_dir: stdenv::current_dir().unwrap(),
-            should_exit: false,
+        let mut env = Environment::new();
+        env.current_dir = canonical_orig.clone();
+
+        let pushd = Pushd::from_args(&["pushd"], &[&canonical_temp.to_string_lossy()]).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(pushd.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut out,
+            &mut io::sink(),
+            &mut env)?, 0,
+        );
+        assert_eq!(env.current_dir, canonical_temp);
+        assert_eq!(env.dir_stack, vec![canonical_orig.clone()]);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{} {}\n", canonical_temp.display(), canonical_orig.display())
+        );
+
+        let mut out = Vec::new();
+        Dirs {}.execute(&mut Cursor::new(Vec::new()), &mut out, &mut io::sink(), &mut env)?;
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{} {}\n", canonical_temp.display(), canonical_orig.display())
+        );
+
+        let mut out = Vec::new();
+        assert_eq!(Popd {}.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut out,
+            &mut io::sink(),
+            &mut env)?, 0,
+        );
+        assert_eq!(env.current_dir, canonical_orig);
+        assert!(env.dir_stack.is_empty());
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", canonical_orig.display()));
+
+        stdenv::set_current_dir(orig).expect("failed to restore cwd");
+        let _ = fs::remove_dir_all(&temp);
+        Ok(())
+    }
+
+    #[test]
+    fn test_popd_on_empty_stack() -> Result<(), anyhow::Error> {
+        let mut env = Environment::new();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = Popd {}.execute(&mut Cursor::new(Vec::new()), &mut out, &mut err, &mut env)?;
+        assert_eq!(code, 1);
+        assert!(out.is_empty());
+        assert_eq!(String::from_utf8(err).unwrap(), "popd: directory stack empty\n");
+        Ok(())
+    }
+
+    /// Run a command created by `factory`, returning its exit code, stdout and stderr.
+    fn run_with_factory(
+        factory: &dyn CommandFactory,
+        name: &str,
+        args: &[&str],
+    ) -> (ExitCode, String, String) {
+        run_with_input(factory, name, args, "")
+    }
+
+    /// Like [`run_with_factory`], with `input` on stdin.
+    fn run_with_input(
+        factory: &dyn CommandFactory,
+        name: &str,
+        args: &[&str],
+        input: &str,
+    ) -> (ExitCode, String, String) {
+        let mut shell_env = Environment::new();
+        let cmd = factory.try_create(&shell_env, name, args).unwrap();
+        let (out, out_rc) = crate::MemWriter::with_handle();
+        let (err, err_rc) = crate::MemWriter::with_handle();
+        let code = cmd
+            .execute(
+                Box::new(crate::MemReader::new(input.as_bytes().to_vec())),
+                Box::new(out),
+                Box::new(err),
+                &mut shell_env,
+            )
+            .unwrap();
+        let out = String::from_utf8(out_rc.borrow().clone()).unwrap();
+        let err = String::from_utf8(err_rc.borrow().clone()).unwrap();
+        (code, out, err)
+    }
+
+    fn run_seq(args: &[&str]) -> (ExitCode, String, String) {
+        run_with_factory(&Factory::<Seq>::default(), "seq", args)
+    }
+
+    #[test]
+    fn test_seq_argument_forms() {
+        assert_eq!(run_seq(&["3"]).1, "1\n2\n3\n");
+        assert_eq!(run_seq(&["2", "4"]).1, "2\n3\n4\n");
+        assert_eq!(run_seq(&["1", "2", "9"]).1, "1\n3\n5\n7\n9\n");
+        assert_eq!(run_seq(&["5", "-2", "0"]).1, "5\n3\n1\n");
+        assert_eq!(run_seq(&["3", "1"]), (0, String::new(), String::new()));
+    }
+
+    #[test]
+    fn test_seq_rejects_bad_arguments() {
+        let bad = |message: &str| (1, String::new(), message.to_string());
+        assert_eq!(run_seq(&["1", "0", "5"]), bad("seq: zero increment\n"));
+        assert_eq!(run_seq(&["x"]), bad("seq: invalid argument: 'x'\n"));
+        assert_eq!(run_seq(&[]), bad("seq: missing operand\n"));
+    }
+
+    fn run_printf(args: &[&str]) -> (ExitCode, String, String) {
+        run_with_factory(&Factory::<Printf>::default(), "printf", args)
+    }
+
+    #[test]
+    fn test_printf_substitutes_strings() {
+        assert_eq!(run_printf(&["%s, %s!\\n", "hello", "world"]).1, "hello, world!\n");
+        assert_eq!(run_printf(&["[%s]\\t100%%"]).1, "[]\t100%");
+    }
+
+    #[test]
+    fn test_printf_formats_numbers() {
+        assert_eq!(run_printf(&["%d %x %d\n", "42", "255", "-7"]).1, "42 ff -7\n");
+        assert_eq!(run_printf(&["%d|%x"]).1, "0|0");
+
+        let (code, out, err) = run_printf(&["%d\n", "abc"]);
+        assert_eq!((code, out.as_str()), (1, "0\n"));
+        assert_eq!(err, "printf: 'abc': invalid number\n");
+    }
+
+    #[test]
+    fn test_printf_reuses_format_for_extra_args() {
+        assert_eq!(run_printf(&["%s=%d\n", "a", "1", "b", "2", "c"]).1, "a=1\nb=2\nc=0\n");
+        assert_eq!(run_printf(&["no specifiers\n", "ignored"]).1, "no specifiers\n");
+        assert_eq!(run_printf(&[]), (1, String::new(), "printf: missing operand\n".to_string()));
+    }
+
+    #[test]
+    fn test_builtin_errors_go_to_stderr() {
+        // Reported by the builtin itself
+        let (code, out, err) =
+            run_with_factory(&Factory::<Head>::default(), "head", &["/definitely/missing"]);
+        assert_eq!(code, 1);
+        assert_eq!(out, "");
+        assert!(err.starts_with("head: /definitely/missing: "), "got {:?}", err);
+
+        // Returned as an error and reported by the blanket `ExecutableCommand` impl
+        let (code, out, err) =
+            run_with_factory(&Factory::<Cd>::default(), "cd", &["/definitely/missing"]);
+        assert_eq!(code, 1);
+        assert_eq!(out, "");
+        assert!(err.starts_with("cd: can't canonicalize"), "got {:?}", err);
+
+        // Usage errors from argument parsing
+        let (code, out, err) = run_with_factory(&Factory::<Cd>::default(), "cd", &["--bogus"]);
+        assert_eq!(code, 1);
+        assert_eq!(out, "");
+        assert!(!err.is_empty());
+    }
+
+    /// Accepts up to `capacity` bytes, then fails like a pipe whose reader exited.
+    struct CappedWriter {
+        data: Vec<u8>,
+        capacity: usize,
+    }
+
+    impl Write for CappedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let room = self.capacity - self.data.len();
+            if room == 0 {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+            }
+            let n = buf.len().min(room);
+            self.data.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_yes_stops_cleanly_on_broken_pipe() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        let mut out = CappedWriter {
+            data: Vec::new(),
+            capacity: 100_000,
         };
+        let yes = Yes::from_args(&["yes"], &["hello", "world"]).unwrap();
+        let code = yes.execute(
+            &mut Cursor::new(Vec::new()),
+            &mut out,
+            &mut io::sink(),
+            &mut shell_env,
+        )?;
+
+        assert_eq!(code, 0);
+        assert_eq!(out.data.len(), 100_000);
+        let text = String::from_utf8(out.data).unwrap();
+        assert!(text.lines().all(|line| "hello world".starts_with(line)));
+        assert!(text.starts_with("hello world\nhello world\n"));
+        Ok(())
+    }
 
-        let cat = Cat { files: Vec::new() };
-        let input = b"from stdin\nline2\n".to_vec();
+    #[test]
+    fn test_basename_strips_directory_and_suffix() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        for (args, expected) in [
+            (&["/a/b/c.txt", ".txt"][..], "c\n"),
+            (&["/a/b/"][..], "b\n"),
+            (&[".txt", ".txt"][..], ".txt\n"),
+            (&["/"][..], "/\n"),
+        ] {
+            let basename = Basename::from_args(&["basename"], args).unwrap();
+            let mut out = Vec::new();
+            basename.execute(
+                &mut Cursor::new(Vec::new()),
+                &mut out,
+                &mut io::sink(),
+                &mut shell_env,
+            )?;
+            assert_eq!(String::from_utf8(out).unwrap(), expected, "args: {:?}", args);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dirname_strips_last_component() -> Result<(), anyhow::Error> {
+        let mut shell_env = Environment::new();
+        for (path, expected) in [
+            ("/a/b/c", "/a/b\n"),
+            ("/a/b/", "/a\n"),
+            ("file", ".\n"),
+            ("/", "/\n"),
+        ] {
+            let dirname = Dirname::from_args(&["dirname"], &[path]).unwrap();
+            let mut out = Vec::new();
+            dirname.execute(
+                &mut Cursor::new(Vec::new()),
+                &mut out,
+                &mut io::sink(),
+                &mut shell_env,
+            )?;
+            assert_eq!(String::from_utf8(out).unwrap(), expected, "path: {}", path);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_only_matching_prints_each_match() -> Result<()> {
+        let mut shell_env = Environment::new();
         let mut out = Vec::new();
-        let res = cat.execute(&mut Cursor::new(input), &mut out, &mut env);
-        assert!(res.is_ok());
 
-        let s = String::from_utf8(out).unwrap();
-        assert_eq!(s, "from stdin\nline2\n");
+        let grep = Grep {
+            files: Vec::new(),
+            word_regexp: false,
+            ignore_case: false,
+            after_context: 1, // ignored with -o
+            before_context: 0,
+            context: 0,
+            invert_match: false,
+            line_number: false,
+            count: false,
+            recursive: false,
+            files_with_matches: false,
+            fixed_string: false,
+            only_matching: true, // <- -o
+            patterns: vec![r"\d+".to_string()],
+            max_count: None,
+            color: GrepColor::Never,
+        };
+
+        let input = b"order 12 of 345 items, 6 left\nno numbers\n7\n".to_vec();
+
+        assert_eq!(
+            grep.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?,
+            0
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "12\n345\n6\n7\n");
+        Ok(())
     }
 
     #[test]
-    fn test_wc_counts_file() {
-        let _lock = lock_current_dir();
-
-        // create temp file
-        let mut tmp = stdenv::temp_dir();
-        tmp.push(format!("wc_test_file_{}", std::process::id()));
-        let mut f = fs::File::create(&tmp).expect("create tmp file");
-        // two lines, three words, bytes include newlines
-        write!(f, "one two\nthree\n").expect("write");
-        drop(f);
-
-        let mut env = Environment {
-            vars: HashMap::new(),
-            current_dir: stdenv::current_dir().unwrap(),
-            should_exit: false,
-        };
+    fn test_grep_repeated_e_matches_any_pattern() -> Result<()> {
+        let mut shell_env = Environment::new();
+        let input = b"a cat\na bird\na dog\n".to_vec();
 
-        let wc = WC {
-            files: vec![tmp.to_string_lossy().to_string()],
-        };
         let mut out = Vec::new();
-        let res = wc.execute(&mut Cursor::new(Vec::new()), &mut out, &mut env);
-        assert!(res.is_ok());
-
-        let s = String::from_utf8(out).unwrap();
-        // Expect format: "<lines> <words> <bytes> <filename>\n"
-        // lines = 2, words = 3, bytes = len("one two\nthree\n") = 14
-        let expected_prefix = "2 3 ";
-        assert!(s.starts_with(expected_prefix));
-        assert!(s.trim_end().ends_with(&tmp.to_string_lossy().to_string()));
+        let grep = Grep::from_args(&["grep"], &["-e", "cat", "-e", "dog"]).unwrap();
+        let mut stdin = Cursor::new(input.clone());
+        assert_eq!(grep.execute(&mut stdin, &mut out, &mut io::sink(), &mut shell_env)?, 0);
+        assert_eq!(String::from_utf8(out).unwrap(), "a cat\na dog\n");
 
-        let _ = fs::remove_file(tmp);
+        // Without -e, the first positional argument is the pattern
+        let mut out = Vec::new();
+        let grep = Grep::from_args(&["grep"], &["bird"]).unwrap();
+        grep.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(String::from_utf8(out).unwrap(), "a bird\n");
+
+        let grep = Grep::from_args(&["grep"], &[]).unwrap();
+        let mut stdin = io::empty();
+        let code = grep.execute(&mut stdin, &mut io::sink(), &mut io::sink(), &mut shell_env)?;
+        assert_eq!(code, 2, "There is no pattern to search for");
+        Ok(())
     }
 
     #[test]
-    fn test_wc_counts_stdin_when_no_args() {
-        let _lock = lock_current_dir();
+    fn test_grep_color_always_highlights_matches() -> Result<()> {
+        let mut shell_env = Environment::new();
+        let input = b"one cat, two cats\nno match\n".to_vec();
 
-        let mut env = Environment {
-            vars: HashMap::new(),
-            current_dir: stdenv::current_dir().unwrap(),
-            should_exit: false,
-        };
+        let mut out = Vec::new();
+        let grep = Grep::from_args(&["grep"], &["--color", "always", "cat"]).unwrap();
+        grep.execute(&mut Cursor::new(input.clone()), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "one \x1b[1;31mcat\x1b[0m, two \x1b[1;31mcat\x1b[0ms\n"
+        );
 
-        let wc = WC { files: Vec::new() };
-        let input = b"a b c\n".to_vec(); // 1 line, 3 words, bytes = 6 (including newline)
         let mut out = Vec::new();
-        let res = wc.execute(&mut Cursor::new(input), &mut out, &mut env);
-        assert!(res.is_ok());
+        let grep = Grep::from_args(&["grep"], &["cat"]).unwrap();
+        grep.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?;
+        assert_eq!(String::from_utf8(out).unwrap(), "one cat, two cats\n");
 
-        let s = String::from_utf8(out).unwrap();
-        assert_eq!(s, "1 3 6\n");
+        assert!(Grep::from_args(&["grep"], &["--color", "rainbow", "cat"]).is_err());
+        Ok(())
     }
 
     #[test]
-    fn test_wc_multiple_files_output_contains_each_filename() {
-        let _lock = lock_current_dir();
+    fn test_grep_prints_matches_before_end_of_input() -> Result<()> {
+        let mut shell_env = Environment::new();
+        let mut out = Vec::new();
 
-        // create two temp files
-        let mut tmp1 = stdenv::temp_dir();
-        tmp1.push(format!("wc_multi_{}_1", std::process::id()));
-        let mut f1 = fs::File::create(&tmp1).unwrap();
-        write!(f1, "a b\n").unwrap();
-        drop(f1);
+        // The input never ends: reading past the first chunk fails
+        let grep = Grep::from_args(&["grep"], &["-n", "MATCH"]).unwrap();
+        let mut input = FailAfterFirstChunk(Some(b"skip\nMATCH 1\nskip\nMATCH 2\n".to_vec()));
+        assert!(grep.execute(&mut input, &mut out, &mut io::sink(), &mut shell_env).is_err());
+        assert_eq!(String::from_utf8(out).unwrap(), "2:MATCH 1\n4:MATCH 2\n");
 
-        let mut tmp2 = stdenv::temp_dir();
-        tmp2.push(format!("wc_multi_{}_2", std::process::id()));
-        let mut f2 = fs::File::create(&tmp2).unwrap();
-        write!(f2, "c\n").unwrap();
-        drop(f2);
+        // Leading context comes from a buffer of the last -B lines only
+        let mut out = Vec::new();
+        let grep = Grep::from_args(&["grep"], &["-B", "1", "-n", "MATCH"]).unwrap();
+        let mut input = FailAfterFirstChunk(Some(b"a\nb\nc\nMATCH 1\nd\ne\nMATCH 2\n".to_vec()));
+        assert!(grep.execute(&mut input, &mut out, &mut io::sink(), &mut shell_env).is_err());
+        assert_eq!(String::from_utf8(out).unwrap(), "3:c\n4:MATCH 1\n--\n6:e\n7:MATCH 2\n");
+        Ok(())
+    }
 
-        let mut env = Environment {
-            vars: HashMap::new(),
-            current_dir: stdenv::current_dir().unwrap(),
-            should_exit: false,
+    #[test]
+    fn test_grep_max_count_stops_after_n_matches() -> Result<()> {
+        let mut shell_env = Environment::new();
+        let mut grep_m = |args: &[&str], input: &[u8]| -> Result<String> {
+            let grep = Grep::from_args(&["grep"], args).unwrap();
+            let mut out = Vec::new();
+            grep.execute(&mut Cursor::new(input), &mut out, &mut io::sink(), &mut shell_env)?;
+            Ok(String::from_utf8(out).unwrap())
         };
+        let input = b"match 1\nmatch 2\nskip\nmatch 3\nmatch 4\nmatch 5\n";
 
-        let wc = WC {
-            files: vec![
-                tmp1.to_string_lossy().to_string(),
-                tmp2.to_string_lossy().to_string(),
-            ],
-        };
+        assert_eq!(grep_m(&["-m", "2", "match"], input)?, "match 1\nmatch 2\n");
+        assert_eq!(grep_m(&["-m", "2", "-c", "match"], input)?, "2\n");
+        assert_eq!(grep_m(&["-m", "9", "-c", "match"], input)?, "5\n");
+        assert_eq!(grep_m(&["-m", "0", "match"], input)?, "");
+        assert_eq!(
+            grep_m(&["-m", "2", "-A", "2", "-n", "match"], input)?,
+            "1:match 1\n2:match 2\n3:skip\n4:match 3\n",
+            "Only the trailing context of the last match is printed"
+        );
+
+        // Nothing after the last allowed match is read
+        let grep = Grep::from_args(&["grep"], &["-m", "1", "MATCH"]).unwrap();
+        let mut input = FailAfterFirstChunk(Some(b"MATCH\n".to_vec()));
         let mut out = Vec::new();
-        let res = wc.execute(&mut Cursor::new(Vec::new()), &mut out, &mut env);
-        assert!(res.is_ok());
+        assert_eq!(grep.execute(&mut input, &mut out, &mut io::sink(), &mut shell_env)?, 0);
+        assert_eq!(String::from_utf8(out).unwrap(), "MATCH\n");
+        Ok(())
+    }
 
-        let s = String::from_utf8(out).unwrap();
-        // Should contain two lines, each ending with filename
-        assert!(s.contains(&tmp1.to_string_lossy().to_string()));
-        assert!(s.contains(&tmp2.to_string_lossy().to_string()));
+    /// Parse `args` for the builtin `T` and run it with empty stdin, returning the exit code
+    /// and what it wrote to stderr.
+    fn run_builtin<T: BuiltinCommand>(args: &[&str], env: &mut Environment) -> (ExitCode, String) {
+        let cmd = T::from_args(&[T::name()], args).unwrap();
+        let mut err = Vec::new();
+        let code = cmd.execute(&mut io::empty(), &mut io::sink(), &mut err, env).unwrap();
+        (code, String::from_utf8(err).unwrap())
+    }
 
-        let _ = fs::remove_file(tmp1);
-        let _ = fs::remove_file(tmp2);
+    /// A fresh temporary directory and a shell environment working in it. The directory is
+    /// removed when the fixture is dropped, even if the test fails.
+    struct TempDirEnv {
+        dir: PathBuf,
+        env: Environment,
     }
 
-    use std::io::Error;
+    impl TempDirEnv {
+        fn new() -> io::Result<Self> {
+            let dir = make_unique_temp_dir()?;
+            let mut env = Environment::new();
+            env.current_dir = dir.clone();
+            Ok(TempDirEnv { dir, env })
+        }
+    }
 
-    struct TestEnvironment {
-        temp_dir: PathBuf,
-        file_path: PathBuf,
+    impl Drop for TempDirEnv {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
     }
 
-    /// Создает изолированную временную директорию и файл внутри нее.
-    /// Возвращает абсолютный путь к файлу и временную директорию.
-    fn setup_test_environment(content: &str) -> Result<TestEnvironment, Error> {
-        let mut p = stdenv::temp_dir();
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-        let dir_name = format!("grep_test_dir_{}_{}", std::process::id(), nanos);
-        p.push(dir_name);
-        fs::create_dir_all(&p)?;
+    #[test]
+    fn test_cp_copies_a_file() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        fs::write(dir.join("a.txt"), "hello")?;
+
+        assert_eq!(run_builtin::<Cp>(&["a.txt", "b.txt"], shell_env), (0, String::new()));
+        assert_eq!(fs::read_to_string(dir.join("b.txt"))?, "hello");
+        assert_eq!(fs::read_to_string(dir.join("a.txt"))?, "hello");
+
+        let (code, err) = run_builtin::<Cp>(&["missing.txt", "c.txt"], shell_env);
+        assert_eq!(code, 1);
+        assert!(err.starts_with("cp: missing.txt: "));
+        Ok(())
+    }
 
-        let file_path = p.join("test_data.txt");
-        let mut f = fs::File::create(&file_path)?;
-        write!(f, "{}", content)?;
-        drop(f);
+    #[test]
+    fn test_cp_copies_files_into_a_directory() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        fs::write(dir.join("a.txt"), "a")?;
+        fs::write(dir.join("b.txt"), "b")?;
+        fs::create_dir(dir.join("dest"))?;
+
+        let (code, _) = run_builtin::<Cp>(&["a.txt", "b.txt", "dest"], shell_env);
+        assert_eq!(code, 0);
+        assert_eq!(fs::read_to_string(dir.join("dest").join("a.txt"))?, "a");
+        assert_eq!(fs::read_to_string(dir.join("dest").join("b.txt"))?, "b");
 
-        Ok(TestEnvironment {
-            temp_dir: p,
-            file_path,
-        })
+        assert_eq!(
+            run_builtin::<Cp>(&["a.txt", "b.txt", "c.txt"], shell_env),
+            (1, "cp: target 'c.txt' is not a directory\n".to_string())
+        );
+        Ok(())
     }
 
     #[test]
-    fn test_grep_ignore_case_i_isolated() -> Result<(), anyhow::Error> {
-        let env = setup_test_environment("Target 1\nTaRgEt 2\nNo match\n")
-            .context("Failed to set up environment")?;
+    fn test_cp_recursive_copies_a_directory_tree() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        fs::create_dir_all(dir.join("src").join("nested"))?;
+        fs::write(dir.join("src").join("top.txt"), "top")?;
+        fs::write(dir.join("src").join("nested").join("deep.txt"), "deep")?;
 
-        let mut shell_env = Environment::new();
-        let mut out = Vec::new();
-        let filename = env.file_path.to_string_lossy().to_string();
+        assert_eq!(
+            run_builtin::<Cp>(&["src", "copy"], shell_env),
+            (1, "cp: src: -r not specified; omitting directory\n".to_string())
+        );
 
-        let grep = Grep {
-            pattern: "target".to_string(),
-            files: vec![filename.clone()],
-            word_regexp: false,
-            ignore_case: true, // <- -i
-            after_context: 0,
-        };
+        let (code, _) = run_builtin::<Cp>(&["-r", "src", "copy"], shell_env);
+        assert_eq!(code, 0);
+        assert_eq!(fs::read_to_string(dir.join("copy").join("top.txt"))?, "top");
+        let deep = dir.join("copy").join("nested").join("deep.txt");
+        assert_eq!(fs::read_to_string(deep)?, "deep");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cp_refuses_to_copy_a_file_onto_itself() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        fs::write(dir.join("f.txt"), "keep me")?;
+        fs::create_dir(dir.join("dest"))?;
 
         assert_eq!(
-            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut shell_env)?,
-            0
+            run_builtin::<Cp>(&["f.txt", "f.txt"], shell_env),
+            (1, "cp: 'f.txt' and 'f.txt' are the same file\n".to_string())
         );
-        let s = String::from_utf8(out).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("f.txt"))?, "keep me");
+
+        // A hard link is the same file under another name
+        #[cfg(unix)]
+        {
+            fs::hard_link(dir.join("f.txt"), dir.join("dest").join("f.txt"))?;
+            let (code, _) = run_builtin::<Cp>(&["f.txt", "dest"], shell_env);
+            assert_eq!(code, 1);
+            fs::create_dir(dir.join("src"))?;
+            fs::hard_link(dir.join("f.txt"), dir.join("src").join("f.txt"))?;
+            assert!(copy_dir_all(&dir.join("src"), &dir.join("dest")).is_err());
+            assert_eq!(fs::read_to_string(dir.join("f.txt"))?, "keep me");
+        }
+        Ok(())
+    }
 
-        let expected = format!("{}:Target 1\n{}:TaRgEt 2\n", filename, filename);
-        assert_eq!(s, expected);
+    #[test]
+    fn test_mv_renames_in_the_same_directory() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        fs::write(dir.join("old.txt"), "data")?;
+
+        assert_eq!(run_builtin::<Mv>(&["old.txt", "new.txt"], shell_env), (0, String::new()));
+        assert!(!dir.join("old.txt").exists());
+        assert_eq!(fs::read_to_string(dir.join("new.txt"))?, "data");
+
+        let (code, err) = run_builtin::<Mv>(&["old.txt", "again.txt"], shell_env);
+        assert_eq!(code, 1);
+        assert!(err.starts_with("mv: old.txt: "));
+        Ok(())
+    }
 
-        fs::remove_dir_all(env.temp_dir)?;
+    #[test]
+    fn test_mv_moves_a_file_into_a_subdirectory() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        fs::write(dir.join("a.txt"), "a")?;
+        fs::create_dir(dir.join("sub"))?;
+
+        assert_eq!(run_builtin::<Mv>(&["a.txt", "sub"], shell_env), (0, String::new()));
+        assert!(!dir.join("a.txt").exists());
+        assert_eq!(fs::read_to_string(dir.join("sub").join("a.txt"))?, "a");
         Ok(())
     }
 
     #[test]
-    fn test_grep_trailing_context_a_1_isolated() -> Result<(), anyhow::Error> {
-        let content = "Line 1\nMATCH 1\nLine 3\nLine 4\nMATCH 2\nLine 6\nLine 7\nLine 8\n";
-        let env = setup_test_environment(content).context("Failed to set up environment")?;
+    fn test_rm_removes_a_file() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        fs::write(dir.join("a.txt"), "")?;
 
-        let mut shell_env = Environment::new();
-        let mut out = Vec::new();
-        let filename = env.file_path.to_string_lossy().to_string();
+        assert_eq!(run_builtin::<Rm>(&["a.txt"], shell_env), (0, String::new()));
+        assert!(!dir.join("a.txt").exists());
+        Ok(())
+    }
 
-        let grep = Grep {
-            pattern: "MATCH".to_string(),
-            files: vec![filename.clone()],
-            word_regexp: false,
-            ignore_case: false,
-            after_context: 1, // <- -A 1
-        };
+    #[test]
+    fn test_rm_missing_file_fails_unless_forced() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let shell_env = &mut temp.env;
+
+        let (code, err) = run_builtin::<Rm>(&["missing.txt"], shell_env);
+        assert_eq!(code, 1);
+        assert!(err.starts_with("rm: missing.txt: "));
+
+        assert_eq!(run_builtin::<Rm>(&["-f", "missing.txt"], shell_env), (0, String::new()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rm_needs_recursive_for_directories() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        fs::create_dir_all(dir.join("sub").join("nested"))?;
+        fs::write(dir.join("sub").join("nested").join("file.txt"), "")?;
 
         assert_eq!(
-            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut shell_env)?,
-            0
+            run_builtin::<Rm>(&["sub"], shell_env),
+            (1, "rm: sub: is a directory\n".to_string())
         );
-        let s = String::from_utf8(out).unwrap();
+        assert!(dir.join("sub").exists());
 
-        let expected = format!(
-            "{}:MATCH 1\n\
-            {}:Line 3\n\
-            --\n\
-            {}:MATCH 2\n\
-            {}:Line 6\n",
-            filename, filename, filename, filename
-        );
+        assert_eq!(run_builtin::<Rm>(&["-r", "sub"], shell_env), (0, String::new()));
+        assert!(!dir.join("sub").exists());
+        Ok(())
+    }
 
-        assert_eq!(s, expected);
+    #[test]
+    fn test_mkdir_creates_a_directory() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
 
-        fs::remove_dir_all(env.temp_dir)?;
+        assert_eq!(run_builtin::<Mkdir>(&["new"], shell_env), (0, String::new()));
+        assert!(dir.join("new").is_dir());
+
+        let (code, err) = run_builtin::<Mkdir>(&["new"], shell_env);
+        assert_eq!(code, 1, "The directory exists already");
+        assert!(err.starts_with("mkdir: new: "));
         Ok(())
     }
 
     #[test]
-    fn test_grep_context_overlap_a_2_isolated() -> Result<(), anyhow::Error> {
-        let content = "MATCH 1\nLine 2\nMATCH 2\nLine 4\nLine 5\nLine 6\n";
-        let env = setup_test_environment(content).context("Failed to set up environment")?;
+    fn test_mkdir_parents_creates_nested_directories() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        let nested = Path::new("a").join("b").join("c");
+        let nested = nested.to_str().unwrap();
+
+        assert_eq!(run_builtin::<Mkdir>(&["-p", nested], shell_env), (0, String::new()));
+        assert!(dir.join(nested).is_dir());
+        assert_eq!(run_builtin::<Mkdir>(&["-p", nested], shell_env), (0, String::new()));
+        Ok(())
+    }
 
-        let mut shell_env = Environment::new();
-        let mut out = Vec::new();
-        let filename = env.file_path.to_string_lossy().to_string();
+    #[test]
+    fn test_mkdir_without_parents_needs_the_parent() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+        let nested = Path::new("missing").join("child");
+
+        let (code, err) = run_builtin::<Mkdir>(&[nested.to_str().unwrap()], shell_env);
+        assert_eq!(code, 1);
+        assert!(err.starts_with("mkdir: "));
+        assert!(!dir.join("missing").exists());
+        Ok(())
+    }
 
-        let grep = Grep {
-            pattern: "MATCH".to_string(),
-            files: vec![filename.clone()],
-            word_regexp: false,
-            ignore_case: false,
-            after_context: 2, // <- -A 2
-        };
+    #[test]
+    fn test_touch_creates_and_updates_files() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
+
+        assert_eq!(run_builtin::<Touch>(&["new.txt"], shell_env), (0, String::new()));
+        assert_eq!(fs::read(dir.join("new.txt"))?, b"");
+
+        fs::write(dir.join("old.txt"), "keep")?;
+        let old = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        File::options().write(true).open(dir.join("old.txt"))?.set_modified(old)?;
+        assert_eq!(run_builtin::<Touch>(&["old.txt"], shell_env), (0, String::new()));
+        assert!(fs::metadata(dir.join("old.txt"))?.modified()? > old);
+        assert_eq!(fs::read_to_string(dir.join("old.txt"))?, "keep");
+        Ok(())
+    }
 
-        assert_eq!(
-            grep.execute(&mut Cursor::new(Vec::new()), &mut out, &mut shell_env)?,
-            0
-        );
-        let s = String::from_utf8(out).unwrap();
+    #[test]
+    fn test_touch_no_create_leaves_missing_file_absent() -> Result<(), anyhow::Error> {
+        let mut temp = TempDirEnv::new()?;
+        let (dir, shell_env) = (&temp.dir, &mut temp.env);
 
-        let expected = format!(
-            "{}:MATCH 1\n\
-            {}:Line 2\n\
-            {}:MATCH 2\n\
-            {}:Line 4\n\
-            {}:Line 5\n",
-            filename, filename, filename, filename, filename
-        );
+        let result = run_builtin::<Touch>(&["-c", "missing.txt"], shell_env);
+        assert_eq!(result, (0, String::new()));
+        assert!(!dir.join("missing.txt").exists());
+        Ok(())
+    }
 
-        assert_eq!(s, expected);
+    fn run_tr(args: &[&str], input: &str) -> (ExitCode, String, String) {
+        run_with_input(&Factory::<Tr>::default(), "tr", args, input)
+    }
 
-        fs::remove_dir_all(env.temp_dir)?;
-        Ok(())
+    #[test]
+    fn test_tr_translates_ranges() {
+        assert_eq!(run_tr(&["a-z", "A-Z"], "Hello, world!\n").1, "HELLO, WORLD!\n");
+        assert_eq!(run_tr(&["abc", "x"], "aabbcc d\n").1, "xxxxxx d\n");
+        assert_eq!(run_tr(&[" ", "\\n"], "a b c").1, "a\nb\nc");
     }
 
     #[test]
-    fn test_grep_stdin_pipe_isolated() -> Result<(), anyhow::Error> {
-        let mut shell_env = Environment::new();
-        let mut out = Vec::new();
+    fn test_tr_deletes_characters() {
+        assert_eq!(run_tr(&["-d", "0-9"], "a1b22c333\n").1, "abc\n");
+        assert_eq!(run_tr(&["-d", "-s", "0-9", " "], "a1  2 b\n").1, "a b\n");
+    }
 
-        let grep = Grep {
-            pattern: "pipe".to_string(),
-            files: Vec::new(), // <- stdin
-            word_regexp: false,
-            ignore_case: false,
-            after_context: 0,
-        };
+    #[test]
+    fn test_tr_squeezes_repeats() {
+        assert_eq!(run_tr(&["-s", " "], "a   b  c\n").1, "a b c\n");
+        assert_eq!(run_tr(&["-s", "a-z", "A-Z"], "aabb  cc\n").1, "AB  C\n");
+        assert_eq!(run_tr(&["-s", "\\n"], "a\n\n\nb\n").1, "a\nb\n");
+    }
 
-        let input = b"Line 1\nLine with pipe target\nLine 3\n".to_vec();
+    #[test]
+    fn test_tr_checks_operands() {
+        assert_eq!(run_tr(&["abc"], ""), (1, String::new(), "tr: missing operand\n".into()));
+        let (code, _, err) = run_tr(&["-d", "a", "b"], "");
+        assert_eq!((code, err.as_str()), (1, "tr: extra operand: b\n"));
+    }
 
-        assert_eq!(
-            grep.execute(&mut Cursor::new(input), &mut out, &mut shell_env)?,
-            0
-        );
-        let s = String::from_utf8(out).unwrap();
+    fn run_cut(args: &[&str], input: &str) -> (ExitCode, String, String) {
+        run_with_input(&Factory::<Cut>::default(), "cut", args, input)
+    }
 
-        // При чтении из stdin префикс файла отсутствует
-        assert_eq!(s, "Line with pipe target\n");
-        Ok(())
+    #[test]
+    fn test_cut_selects_fields() {
+        let passwd = "root:x:0:0:root:/root\nuser:x:1000:1000::/home/user\n";
+        assert_eq!(run_cut(&["-d", ":", "-f", "1,3"], passwd).1, "root:0\nuser:1000\n");
+        assert_eq!(run_cut(&["-d", ":", "-f", "5-"], passwd).1, "root:/root\n:/home/user\n");
+        assert_eq!(run_cut(&["-f", "2"], "a\tb\tc\nno tabs\n").1, "b\nno tabs\n");
+    }
+
+    #[test]
+    fn test_cut_selects_characters() {
+        assert_eq!(run_cut(&["-c", "2-4"], "abcdef\nxy\n").1, "bcd\ny\n");
+        assert_eq!(run_cut(&["-c", "-2,5"], "abcdef").1, "abe\n");
+        assert_eq!(run_cut(&["-c", "1,3"], "привет\n").1, "пи\n");
+    }
+
+    #[test]
+    fn test_cut_rejects_bad_lists() {
+        assert!(parse_cut_list("3-1").is_err());
+        assert!(parse_cut_list("0").is_err());
+        assert!(parse_cut_list("1,x").is_err());
+        assert_eq!(parse_cut_list("2,4-"), Ok(CutList(vec![(2, 2), (4, usize::MAX)])));
+
+        let (code, _, err) = run_cut(&[], "text\n");
+        assert_eq!((code, err.as_str()), (1, "cut: specify exactly one of -f and -c\n"));
+    }
+
+    fn run_sort(args: &[&str], input: &str) -> (ExitCode, String, String) {
+        run_with_input(&Factory::<Sort>::default(), "sort", args, input)
+    }
+
+    #[test]
+    fn test_sort_orders_lines() {
+        assert_eq!(run_sort(&[], "pear\napple\nfig\n").1, "apple\nfig\npear\n");
+        assert_eq!(run_sort(&["-r"], "pear\napple\nfig").1, "pear\nfig\napple\n");
+        assert_eq!(run_sort(&["-n"], "10\n9\n-1.5\nx\n").1, "-1.5\nx\n9\n10\n");
+    }
+
+    #[test]
+    fn test_sort_unique_removes_duplicates() {
+        assert_eq!(run_sort(&["-u"], "b\na\nb\na\nc\n").1, "a\nb\nc\n");
+        assert_eq!(run_sort(&["-u", "-r"], "b\na\nb\n").1, "b\na\n");
+    }
+
+    #[test]
+    fn test_sort_unique_numeric_collapses_equal_numbers() {
+        assert_eq!(run_sort(&["-n", "-u"], "2 b\n1\n2 a\n01\n").1, "1\n2 b\n");
+        assert_eq!(run_sort(&["-n"], "2 b\n1\n2 a\n01\n").1, "01\n1\n2 a\n2 b\n");
     }
 }
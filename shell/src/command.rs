@@ -1,6 +1,7 @@
 use crate::env::Environment;
 use anyhow::Result;
 use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::process::Stdio;
 
 /// Conventional process exit code type used by this crate.
@@ -9,6 +10,17 @@ use std::process::Stdio;
 /// This mirrors the convention used by POSIX shells and many command-line tools.
 pub type ExitCode = i32;
 
+/// What a command name refers to, as reported by `type` and `which`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    /// A command implemented by the shell itself.
+    Builtin,
+    /// A program found at the given path.
+    External(PathBuf),
+    /// Neither a builtin nor a program in PATH.
+    NotFound,
+}
+
 /// Abstraction over a readable input stream that can also be converted into
 /// a [`Stdio`] handle for spawning external processes.
 ///
@@ -32,6 +44,18 @@ impl<T: Read + Into<Stdio>> Stdin for T {
 pub trait Stdout: Write {
     /// Convert this output into a [`Stdio`] handle suitable for `std::process::Command`.
     fn stdio(self: Box<Self>) -> Stdio;
+
+    /// Another handle writing to the same place, which a command gets as its stderr
+    /// with `2>&1`. Returns `None` by default, when the stream can't be shared.
+    fn try_clone(&self) -> Option<Box<dyn Stdout>> {
+        None
+    }
+
+    /// Whether the stream only exists in the shell's memory. A program can't write to
+    /// such a stream itself, so its output is copied over from a pipe instead.
+    fn is_in_memory(&self) -> bool {
+        false
+    }
 }
 
 impl<T: Write + Into<Stdio>> Stdout for T {
@@ -45,10 +69,14 @@ impl<T: Write + Into<Stdio>> Stdout for T {
 /// This is implemented by built-ins via a blanket impl and by external commands.
 pub trait ExecutableCommand {
     /// Executes the command.
+    ///
+    /// Regular output goes to `stdout` and diagnostics to `stderr`, so error messages
+    /// don't end up in the data passed down a pipeline.
     fn execute(
         self: Box<Self>,
         stdin: Box<dyn Stdin>,
         stdout: Box<dyn Stdout>,
+        stderr: Box<dyn Stdout>,
         env: &mut Environment,
     ) -> Result<ExitCode>;
 }
@@ -65,4 +93,15 @@ pub trait CommandFactory {
         name: &str,
         args: &[&str],
     ) -> Option<Box<dyn ExecutableCommand>>;
+
+    /// Name of the builtin this factory creates, or `None` if it doesn't create a builtin
+    /// (e.g. it launches external programs).
+    fn builtin_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// One-line description of the builtin this factory creates, shown by `help`.
+    fn description(&self) -> Option<String> {
+        None
+    }
 }
@@ -1,6 +1,10 @@
-use std::collections::HashMap;
+use crate::command::ExitCode;
+use crate::external::exit_code;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env as stdenv;
 use std::path::PathBuf;
+use std::process::Child;
+use std::sync::{Arc, Mutex};
 
 /// Mutable, user-level view of the process environment used by the interpreter.
 ///
@@ -8,6 +12,13 @@ use std::path::PathBuf;
 /// - `vars`: a map of environment variables that will be visible to executed commands.
 /// - `current_dir`: the working directory for command execution.
 /// - `should_exit`: a flag that a REPL loop can check to know when to terminate.
+/// - `last_status`: the exit code of the most recently executed statement (`$?`).
+/// - `builtins`: names and descriptions of the builtin commands the interpreter knows about.
+/// - `aliases`: command aliases defined with `alias`.
+/// - `dir_stack`: directories saved by `pushd`, the most recent last.
+/// - `unset_vars`: names removed with [`Environment::remove_var`].
+/// - `positional`: the script name and arguments, `$0`, `$1`, ...
+/// - `jobs`: commands started in the background with `&`.
 ///
 /// Note: fields are public for simplicity to keep the teaching example small.
 /// Production code would prefer accessor methods over public fields.
@@ -19,46 +30,246 @@ pub struct Environment {
     pub current_dir: PathBuf,
     /// When set to true, indicates that an interactive loop should exit.
     pub should_exit: bool,
+    /// Exit code of the last executed statement, exposed to scripts as `$?`.
+    pub last_status: i32,
+    /// Registered builtin commands mapped to their one-line descriptions. Used by `which`
+    /// to tell builtins apart from programs in PATH and by `help` to list them.
+    /// Kept up to date by the interpreter.
+    pub builtins: BTreeMap<String, String>,
+    /// Aliases defined with `alias name=value`, expanded when `name` starts a command.
+    pub aliases: HashMap<String, String>,
+    /// Directory stack of `pushd`/`popd`; the top of the stack is the last element.
+    pub dir_stack: Vec<PathBuf>,
+    /// Variables removed with `remove_var`. They stay hidden even if the process
+    /// environment still has them, both from `get_var` and from spawned programs.
+    pub unset_vars: HashSet<String>,
+    /// Positional parameters: the script name (`$0`) followed by its arguments
+    /// (`$1`, `$2`, ...). Empty when no script is running.
+    pub positional: Vec<String>,
+    /// Background jobs, listed by `jobs` and reaped by `wait` or between prompts.
+    pub jobs: JobTable,
+    /// Lines run by the interpreter, oldest first, listed by `history`.
+    pub history: Vec<String>,
 }
 
 impl Environment {
     /// Capture the current process state into a new `Environment` instance.
     ///
     /// This copies variables from `std::env::vars()` and initializes `current_dir`
-    /// from `std::env::current_dir()`. The `should_exit` flag is initialized to `false`.
+    /// from `std::env::current_dir()`, with `PWD` set to match it. The `should_exit` flag
+    /// is initialized to `false` and `last_status` to 0. No builtins are known until
+    /// an interpreter registers them.
     pub fn new() -> Self {
         let mut vars = HashMap::new();
         for (k, v) in stdenv::vars() {
             vars.insert(k, v);
         }
         let current_dir = stdenv::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        vars.insert("PWD".to_string(), current_dir.to_string_lossy().into_owned());
         Self {
             vars,
             current_dir,
             should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: JobTable::default(),
+            history: Vec::new(),
         }
     }
 
     /// Get the value of an environment variable.
     ///
-    /// Looks up the key in `self.vars` first, falling back to `std::env::var`.
+    /// Looks up the key in `self.vars` first, falling back to `std::env::var`
+    /// unless the variable was removed with `remove_var`.
     pub fn get_var(&self, key: &str) -> Option<String> {
-        self.vars
-            .get(key)
-            .cloned()
-            .or_else(|| stdenv::var(key).ok())
+        self.vars.get(key).cloned().or_else(|| {
+            if self.unset_vars.contains(key) {
+                None
+            } else {
+                stdenv::var(key).ok()
+            }
+        })
     }
 
     /// Set or override an environment variable in `self.vars`.
     pub fn set_var(&mut self, key: impl Into<String>, val: impl Into<String>) {
-        self.vars.insert(key.into(), val.into());
+        let key = key.into();
+        self.unset_vars.remove(&key);
+        self.vars.insert(key, val.into());
+    }
+
+    /// Apply a `name=value` assignment from a command line.
+    ///
+    /// Every variable of the shell is exported, so programs it spawns see the new value
+    /// too, including when `name` was hidden with `remove_var` before.
+    pub fn apply_assignment(&mut self, name: &str, value: impl Into<String>) {
+        self.set_var(name, value);
+    }
+
+    /// Remove an environment variable, returning its previous value.
+    ///
+    /// The process environment can't be changed from here, so the name is also
+    /// remembered in `unset_vars` to mask the value `get_var` would fall back to.
+    pub fn remove_var(&mut self, key: &str) -> Option<String> {
+        let old = self.get_var(key);
+        self.vars.remove(key);
+        self.unset_vars.insert(key.to_string());
+        old
+    }
+
+    /// Remember the current state of the variables `names`, to put it back later with
+    /// [`Environment::restore`]. Only the named variables are saved, so this stays cheap.
+    pub fn snapshot<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> EnvSnapshot {
+        EnvSnapshot(
+            names
+                .into_iter()
+                .map(|name| SavedVar {
+                    name: name.to_string(),
+                    value: self.vars.get(name).cloned(),
+                    unset: self.unset_vars.contains(name),
+                })
+                .collect(),
+        )
+    }
+
+    /// Put the variables saved in `snapshot` back the way they were, leaving every other
+    /// change to the environment in place.
+    pub fn restore(&mut self, snapshot: EnvSnapshot) {
+        // In reverse, so a name saved twice ends up with its oldest state
+        for saved in snapshot.0.into_iter().rev() {
+            match saved.value {
+                Some(value) => self.vars.insert(saved.name.clone(), value),
+                None => self.vars.remove(&saved.name),
+            };
+            if saved.unset {
+                self.unset_vars.insert(saved.name);
+            } else {
+                self.unset_vars.remove(&saved.name);
+            }
+        }
+    }
+}
+
+/// Saved state of some variables of an [`Environment`], made by [`Environment::snapshot`].
+#[derive(Debug)]
+pub struct EnvSnapshot(Vec<SavedVar>);
+
+#[derive(Debug)]
+struct SavedVar {
+    name: String,
+    value: Option<String>,
+    unset: bool,
+}
+
+/// A command or a pipeline started in the background with `&`.
+#[derive(Debug)]
+pub struct Job {
+    /// Job number, shown as `[n]`.
+    pub id: usize,
+    /// The command line that started the job.
+    pub command: String,
+    /// The running programs, one per pipeline stage.
+    pub children: Vec<Child>,
+}
+
+impl Job {
+    /// The exit code of the job once all of its programs have finished: the one of the
+    /// last program, like for a pipeline run in the foreground.
+    fn try_wait(&mut self) -> std::io::Result<Option<ExitCode>> {
+        let mut code = 0;
+        for child in &mut self.children {
+            match child.try_wait()? {
+                Some(status) => code = exit_code(status),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(code))
+    }
+
+    /// Wait for all programs of the job and return its exit code, see [`Job::try_wait`].
+    fn wait(&mut self) -> ExitCode {
+        let mut code = 0;
+        for child in &mut self.children {
+            code = child.wait().map_or(1, exit_code);
+        }
+        code
+    }
+}
+
+/// Background jobs of the shell.
+///
+/// Clones share the same jobs, so a job started with a cloned environment (like in a
+/// pipeline stage) is still seen by `jobs` and `wait`.
+#[derive(Debug, Clone, Default)]
+pub struct JobTable(Arc<Mutex<Vec<Job>>>);
+
+impl JobTable {
+    /// Add a started job and return its number: one more than the highest number in use.
+    pub fn add(&self, command: String, children: Vec<Child>) -> usize {
+        let mut jobs = self.0.lock().unwrap();
+        let id = jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        jobs.push(Job {
+            id,
+            command,
+            children,
+        });
+        id
+    }
+
+    /// Numbers and command lines of the jobs that haven't been reaped yet.
+    pub fn list(&self) -> Vec<(usize, String)> {
+        let jobs = self.0.lock().unwrap();
+        jobs.iter().map(|job| (job.id, job.command.clone())).collect()
+    }
+
+    /// Remove the jobs that have finished, returning their numbers, command lines and
+    /// exit codes.
+    pub fn reap(&self) -> Vec<(usize, String, ExitCode)> {
+        let mut jobs = self.0.lock().unwrap();
+        let mut finished = Vec::new();
+        jobs.retain_mut(|job| match job.try_wait() {
+            Ok(Some(code)) => {
+                finished.push((job.id, job.command.clone(), code));
+                false
+            }
+            Ok(None) => true,
+            // The job can't be waited for anymore, so there is nothing left to track
+            Err(_) => {
+                finished.push((job.id, job.command.clone(), 1));
+                false
+            }
+        });
+        finished
+    }
+
+    /// Wait for all jobs to finish and remove them, returning their numbers, command lines
+    /// and exit codes.
+    pub fn wait_all(&self) -> Vec<(usize, String, ExitCode)> {
+        // Don't hold the lock while waiting
+        let jobs = std::mem::take(&mut *self.0.lock().unwrap());
+        jobs.into_iter()
+            .map(|mut job| {
+                let code = job.wait();
+                (job.id, job.command, code)
+            })
+            .collect()
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::env::Environment;
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap, HashSet};
     use std::env as stdenv;
 
     #[test]
@@ -67,6 +278,14 @@ mod tests {
             vars: HashMap::new(),
             current_dir: stdenv::current_dir().unwrap(),
             should_exit: false,
+            last_status: 0,
+            builtins: BTreeMap::new(),
+            aliases: HashMap::new(),
+            dir_stack: Vec::new(),
+            unset_vars: HashSet::new(),
+            positional: Vec::new(),
+            jobs: Default::default(),
+            history: Vec::new(),
         };
 
         // initially absent
@@ -82,4 +301,70 @@ mod tests {
         let env = Environment::new();
         assert!(env.get_var("PATH").is_some());
     }
+
+    #[test]
+    fn test_env_remove_var() {
+        let mut env = Environment::new();
+        env.set_var("KEY", "VALUE");
+
+        assert_eq!(env.remove_var("KEY"), Some("VALUE".to_string()));
+        assert_eq!(env.get_var("KEY"), None);
+        assert_eq!(env.remove_var("KEY"), None);
+
+        env.set_var("KEY", "AGAIN");
+        assert_eq!(env.get_var("KEY"), Some("AGAIN".to_string()));
+    }
+
+    #[test]
+    fn test_env_remove_var_masks_process_env() {
+        let mut env = Environment::new();
+        // Inherited variables are also found through the process environment fallback
+        env.vars.clear();
+        assert!(env.get_var("PATH").is_some());
+
+        assert!(env.remove_var("PATH").is_some());
+        assert_eq!(env.get_var("PATH"), None);
+    }
+
+    #[test]
+    fn test_env_initializes_pwd() {
+        let env = Environment::new();
+        assert_eq!(
+            env.get_var("PWD"),
+            Some(env.current_dir.to_string_lossy().into_owned())
+        );
+    }
+
+    #[test]
+    fn test_env_apply_assignment_exports_removed_var() {
+        let mut env = Environment::new();
+        env.remove_var("PATH");
+        env.apply_assignment("PATH", "/opt/bin");
+        assert_eq!(env.get_var("PATH"), Some("/opt/bin".to_string()));
+        assert!(!env.unset_vars.contains("PATH"));
+        assert_eq!(env.vars.get("PATH"), Some(&"/opt/bin".to_string()));
+
+        env.apply_assignment("EMPTY", "");
+        assert_eq!(env.get_var("EMPTY"), Some(String::new()));
+    }
+
+    #[test]
+    fn test_env_restore_puts_back_snapshotted_vars() {
+        let mut env = Environment::new();
+        env.set_var("SNAPSHOT_SET", "before");
+        env.remove_var("SNAPSHOT_UNSET");
+        let snapshot = env.snapshot(["SNAPSHOT_SET", "SNAPSHOT_UNSET", "SNAPSHOT_NEW"]);
+
+        env.apply_assignment("SNAPSHOT_SET", "after");
+        env.apply_assignment("SNAPSHOT_UNSET", "after");
+        env.apply_assignment("SNAPSHOT_NEW", "after");
+        env.set_var("SNAPSHOT_OTHER", "kept");
+        env.restore(snapshot);
+
+        assert_eq!(env.get_var("SNAPSHOT_SET").as_deref(), Some("before"));
+        assert_eq!(env.get_var("SNAPSHOT_UNSET"), None);
+        assert!(env.unset_vars.contains("SNAPSHOT_UNSET"));
+        assert_eq!(env.get_var("SNAPSHOT_NEW"), None);
+        assert_eq!(env.get_var("SNAPSHOT_OTHER").as_deref(), Some("kept"));
+    }
 }
@@ -1,10 +1,13 @@
 use crate::command::{CommandFactory, ExecutableCommand, ExitCode, Stdin, Stdout};
 use crate::env::Environment;
+use crate::signals::{self, Foreground};
 use anyhow::Result;
 use std::borrow::Cow;
 use std::ffi::{OsStr, OsString};
+use std::io::{ErrorKind, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::ExitStatus;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc;
 use crate::interpreter::Factory;
 
 /// Command that is not a builtin.
@@ -27,36 +30,177 @@ impl CommandFactory for Factory<ExternalCommand> {
         args: &[&str],
     ) -> Option<Box<dyn ExecutableCommand>> {
         let search_paths = env.get_var("PATH")?;
-        match find_command_path(OsStr::new(&search_paths), Path::new(&name)) {
-            Some(executable) => Some(Box::new(ExternalCommand::new(
-                executable.as_os_str().to_owned(),
-                args.iter().map(|x| x.into()).collect(),
-            ))),
-            None => None,
+        let executable = match find_command_path(OsStr::new(&search_paths), Path::new(&name)) {
+            Some(executable) => executable.as_os_str().to_owned(),
+            // Paths that exist but can't be executed still get run, so that they fail with 126
+            None if name.contains('/') && Path::new(name).exists() => OsString::from(name),
+            None => return None,
+        };
+        Some(Box::new(ExternalCommand::new(
+            executable,
+            args.iter().map(|x| x.into()).collect(),
+        )))
+    }
+}
+
+impl ExternalCommand {
+    /// Start the program without waiting for it, for a job run in the background.
+    ///
+    /// The job gets its own process group, so Ctrl-C meant for the foreground
+    /// command doesn't reach it.
+    pub(crate) fn spawn(
+        self,
+        stdin: Box<dyn Stdin>,
+        stdout: Box<dyn Stdout>,
+        stderr: Box<dyn Stdout>,
+        env: &Environment,
+    ) -> std::io::Result<Child> {
+        self.command(env)
+            .stdin(stdin.stdio())
+            .stdout(stdout.stdio())
+            .stderr(stderr.stdio())
+            .spawn()
+    }
+
+    /// The program with its arguments, environment and working directory, in a process
+    /// group of its own.
+    fn command(&self, env: &Environment) -> Command {
+        let mut command = Command::new(&self.name);
+        for name in &env.unset_vars {
+            command.env_remove(name);
         }
+        signals::set_process_group(&mut command, 0);
+        command
+            .args(&self.args)
+            .envs(env.vars.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+            .current_dir(&env.current_dir);
+        command
     }
 }
 
 impl ExecutableCommand for ExternalCommand {
+    /// Returns 126 if the program exists but can't be executed.
     fn execute(
         self: Box<Self>,
         stdin: Box<dyn Stdin>,
         stdout: Box<dyn Stdout>,
+        mut stderr: Box<dyn Stdout>,
         env: &mut Environment,
     ) -> Result<ExitCode> {
-        let mut cmd = std::process::Command::new(&self.name)
-            .args(&self.args)
+        let path = Path::new(&self.name);
+        if !is_executable(path) {
+            let reason = if path.is_dir() { "Is a directory" } else { "Permission denied" };
+            writeln!(stderr, "{}: {}", path.display(), reason)?;
+            return Ok(126);
+        }
+
+        // The program gets `stderr` itself, so keep a handle for the error below
+        let error_stream = stderr.try_clone();
+        let (stdout_stdio, stdout_copy) = child_stdio(stdout);
+        let (stderr_stdio, stderr_copy) = child_stdio(stderr);
+        let cmd = self
+            .command(env)
             .stdin(stdin.stdio())
-            .stdout(stdout.stdio())
-            .envs(env.vars.iter().map(|(k, v)| (k.as_str(), v.as_str())))
-            .current_dir(&env.current_dir)
-            .spawn()?;
-        let exit_status = cmd.wait()?;
-        match exit_status.code() {
-            Some(x) => Ok(x),
-            None => Ok(terminated_by_signal(exit_status)),
+            .stdout(stdout_stdio)
+            .stderr(stderr_stdio)
+            .spawn();
+        let mut cmd = match cmd {
+            Ok(cmd) => cmd,
+            Err(e) if is_not_executable_error(&e) => {
+                match error_stream {
+                    Some(mut stream) => writeln!(stream, "{}: {}", path.display(), e)?,
+                    // Streams that can't be shared are the shell's own stderr
+                    None => eprintln!("{}: {}", path.display(), e),
+                }
+                return Ok(126);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let exit_status = {
+            let _foreground = Foreground::enter(cmd.id());
+            copy_output(&mut cmd, stdout_copy, stderr_copy)?;
+            cmd.wait()?
+        };
+        Ok(exit_code(exit_status))
+    }
+}
+
+/// How a program gets `stream`: directly, or through a pipe if the stream is in memory.
+/// In the latter case the stream is returned as well, to copy the output into.
+fn child_stdio(stream: Box<dyn Stdout>) -> (Stdio, Option<Box<dyn Stdout>>) {
+    if stream.is_in_memory() {
+        (Stdio::piped(), Some(stream))
+    } else {
+        (stream.stdio(), None)
+    }
+}
+
+/// Copy what the program writes to its piped stdout and stderr into the in-memory
+/// streams until it closes both pipes.
+///
+/// Each pipe is drained by a thread of its own, so the program can't get stuck on a full
+/// stderr pipe while the shell waits for its stdout. The chunks are written in the order
+/// they arrive, which keeps the output of `2>&1` interleaved.
+fn copy_output(
+    child: &mut Child,
+    mut stdout: Option<Box<dyn Stdout>>,
+    mut stderr: Option<Box<dyn Stdout>>,
+) -> std::io::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let pipes: [(bool, Option<Box<dyn Read + Send>>); 2] = [
+        (false, child.stdout.take().map(|pipe| Box::new(pipe) as _)),
+        (true, child.stderr.take().map(|pipe| Box::new(pipe) as _)),
+    ];
+    for (is_stderr, pipe) in pipes {
+        let Some(mut pipe) = pipe else {
+            continue;
+        };
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0; 8192];
+            loop {
+                let chunk = match pipe.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => buf[..n].to_vec(),
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                };
+                if sender.send((is_stderr, chunk)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(sender);
+
+    for (is_stderr, chunk) in receiver {
+        let target = if is_stderr { &mut stderr } else { &mut stdout };
+        if let Some(target) = target {
+            target.write_all(&chunk)?;
         }
     }
+    for target in [stdout, stderr].iter_mut().flatten() {
+        target.flush()?;
+    }
+    Ok(())
+}
+
+/// Exit code of a finished program, `128 + signal` if it was killed by a signal.
+pub(crate) fn exit_code(exit_status: ExitStatus) -> ExitCode {
+    match exit_status.code() {
+        Some(x) => x,
+        None => terminated_by_signal(exit_status),
+    }
+}
+
+/// Whether spawning failed because the program can't be executed, as opposed to
+/// e.g. running out of resources.
+fn is_not_executable_error(error: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    if error.raw_os_error() == Some(libc::ENOEXEC) {
+        return true;
+    }
+    matches!(error.kind(), ErrorKind::PermissionDenied | ErrorKind::IsADirectory)
 }
 
 #[cfg(unix)]
@@ -79,13 +223,16 @@ fn terminated_by_signal(_exit_status: ExitStatus) -> i32 {
 /// Resolve a command path the way a typical shell would.
 ///
 /// Behavior:
-/// - Absolute path: returns it if it exists.
-/// - Relative with multiple components (e.g., `bin/sh`): returns it if it exists.
-/// - `./foo` on Unix or any `./`-prefixed path on other platforms: returns it if it exists.
+/// - Absolute path: returns it if it is executable.
+/// - Relative with multiple components (e.g., `bin/sh`): returns it if it is executable.
+/// - `./foo` on Unix or any `./`-prefixed path on other platforms: returns it if it is executable.
 /// - Single path component (no separators): search each directory in `search_paths` (PATH)
-///   and return the first existing match.
+///   and return the first executable match.
 /// - Empty path: returns `None`.
 ///
+/// On Unix a file counts as executable when it is a regular file with at least one
+/// execute permission bit set; on other platforms it only has to exist.
+///
 /// Returns either a borrowed reference to the provided `path` or an owned `PathBuf`
 /// when the result is discovered via PATH lookup.
 pub fn find_command_path<'a>(search_paths: &OsStr, path: &'a Path) -> Option<Cow<'a, Path>> {
@@ -94,7 +241,7 @@ pub fn find_command_path<'a>(search_paths: &OsStr, path: &'a Path) -> Option<Cow
     }
 
     let search_in_current_dir = cfg!(not(unix)) || path.starts_with("./");
-    if search_in_current_dir && path.exists() {
+    if search_in_current_dir && is_executable(path) {
         return Some(Cow::Borrowed(path));
     }
 
@@ -128,7 +275,22 @@ fn find_in_path(search_paths: &OsStr, cmd: &OsStr) -> Option<PathBuf> {
 }
 
 fn find_by_path(path: &Path) -> Option<&Path> {
-    if path.exists() { Some(path) } else { None }
+    if is_executable(path) { Some(path) } else { None }
+}
+
+/// A regular file with at least one execute permission bit set.
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    match std::fs::metadata(path) {
+        Ok(meta) => meta.is_file() && meta.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.exists()
 }
 
 #[cfg(test)]
@@ -143,6 +305,12 @@ mod tests {
         OsStr::new(s)
     }
 
+    #[cfg(unix)]
+    fn make_executable(path: &Path) {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).expect("chmod +x");
+    }
+
     #[test]
     #[cfg(unix)]
     fn absolute_existing_true() {
@@ -195,6 +363,7 @@ mod tests {
     #[cfg(unix)]
     fn multiple_components_relative_existing() {
         // Create a temporary working directory with a nested file: bin/sh
+        let _lock = crate::test_util::lock_current_dir();
         let cwd_before = std::env::current_dir().expect("cwd");
         let tmp_base =
             std::env::temp_dir().join(format!("external_tests_{}_mc", std::process::id()));
@@ -202,6 +371,7 @@ mod tests {
         fs::create_dir_all(tmp_base.join("bin")).expect("create temp bin dir");
         let file_path = tmp_base.join("bin").join("sh");
         File::create(&file_path).expect("touch bin/sh");
+        make_executable(&file_path);
 
         std::env::set_current_dir(&tmp_base).expect("set cwd");
         let res = find_command_path(osstr("/does/not/matter"), Path::new("bin/sh"));
@@ -218,6 +388,7 @@ mod tests {
     #[cfg(unix)]
     fn current_dir_with_dot_prefix() {
         // Create a temporary working directory with a file: ./foo
+        let _lock = crate::test_util::lock_current_dir();
         let cwd_before = std::env::current_dir().expect("cwd");
         let tmp_base =
             std::env::temp_dir().join(format!("external_tests_{}_dot", std::process::id()));
@@ -225,6 +396,7 @@ mod tests {
         fs::create_dir_all(&tmp_base).expect("create temp dir");
         let file_path = tmp_base.join("foo");
         File::create(&file_path).expect("touch foo");
+        make_executable(&file_path);
 
         std::env::set_current_dir(&tmp_base).expect("set cwd");
         let res = find_command_path(osstr("/bin"), Path::new("./foo"));
@@ -243,4 +415,35 @@ mod tests {
         let res = find_command_path(osstr("/bin"), Path::new(""));
         assert!(res.is_none(), "Empty path should not resolve to anything");
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn single_component_requires_exec_bit() {
+        let tmp_base =
+            std::env::temp_dir().join(format!("external_tests_{}_exec", std::process::id()));
+        let _ = fs::remove_dir_all(&tmp_base);
+        fs::create_dir_all(&tmp_base).expect("create temp dir");
+        File::create(tmp_base.join("plain")).expect("touch plain");
+        let runnable = tmp_base.join("runnable");
+        File::create(&runnable).expect("touch runnable");
+        make_executable(&runnable);
+
+        let search_paths = tmp_base.as_os_str();
+        assert!(
+            find_command_path(search_paths, Path::new("plain")).is_none(),
+            "Non-executable file must not be resolved"
+        );
+        let found = find_command_path(search_paths, Path::new("runnable"))
+            .expect("Expected to find executable 'runnable' in PATH");
+        assert_eq!(found.as_ref(), runnable.as_path());
+
+        let _ = fs::remove_dir_all(tmp_base);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn directory_is_not_a_command() {
+        let res = find_command_path(osstr("/"), Path::new("bin"));
+        assert!(res.is_none(), "Directories must not be resolved as commands");
+    }
 }
@@ -0,0 +1,207 @@
+//! Filename expansion (globbing) for unquoted words containing `*`, `?` or `[...]`.
+//!
+//! Patterns use `\` to escape characters that must match literally, which is how
+//! quoted parts of a word are passed in (see [`escape`]).
+
+use std::fs;
+use std::path::Path;
+
+/// Escape glob metacharacters in `text` so it only matches itself.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Expand `pattern` into the sorted list of matching paths, relative to `current_dir`
+/// unless the pattern is absolute. Returns an empty list when nothing matches.
+///
+/// Like in bash, wildcards and bracket expressions don't match a leading `.` of a file name.
+pub(crate) fn expand(pattern: &str, current_dir: &Path) -> Vec<String> {
+    let (mut candidates, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (vec!["/".to_string()], rest),
+        None => (vec![String::new()], pattern),
+    };
+
+    for component in rest.split('/') {
+        let mut next = Vec::new();
+        for prefix in &candidates {
+            if !is_pattern(component) {
+                next.push(join(prefix, &unescape(component)));
+                continue;
+            }
+            let dir = current_dir.join(if prefix.is_empty() { "." } else { prefix });
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            let component: Vec<char> = component.chars().collect();
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.starts_with('.') && component.first() != Some(&'.') {
+                    continue;
+                }
+                let name_chars: Vec<char> = name.chars().collect();
+                if matches(&component, &name_chars) {
+                    next.push(join(prefix, &name));
+                }
+            }
+        }
+        candidates = next;
+    }
+
+    // Literal components after a pattern (as in `*/Cargo.toml`) may not exist
+    candidates.retain(|path| fs::symlink_metadata(current_dir.join(path)).is_ok());
+    candidates.sort();
+    candidates
+}
+
+/// Whether `component` contains an unescaped metacharacter.
+fn is_pattern(component: &str) -> bool {
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '*' | '?' | '[' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn unescape(component: &str) -> String {
+    let mut result = String::with_capacity(component.len());
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else if prefix.ends_with('/') {
+        format!("{}{}", prefix, name)
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+/// Match a single path component against a pattern component.
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+        Some(('[', rest)) => match parse_class(rest) {
+            Some((class, negated, rest)) => {
+                name.first().is_some_and(|&c| class_contains(class, c) != negated)
+                    && matches(rest, &name[1..])
+            }
+            // An unterminated bracket matches itself
+            None => name.first() == Some(&'[') && matches(rest, &name[1..]),
+        },
+        Some(('\\', [escaped, rest @ ..])) => {
+            name.first() == Some(escaped) && matches(rest, &name[1..])
+        }
+        Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+    }
+}
+
+/// Split a bracket expression (the part after `[`) into its class, whether it is
+/// negated with `!` or `^`, and the rest of the pattern after the closing `]`.
+fn parse_class(pattern: &[char]) -> Option<(&[char], bool, &[char])> {
+    let (negated, body) = match pattern.split_first() {
+        Some(('!' | '^', body)) => (true, body),
+        _ => (false, pattern),
+    };
+    // A `]` right after the opening bracket is a member of the class, not its end
+    let end = body.iter().skip(1).position(|&c| c == ']')? + 1;
+    Some((&body[..end], negated, &body[end + 1..]))
+}
+
+/// Whether `c` is one of the characters or ranges (like `a-z`) listed in `class`.
+fn class_contains(class: &[char], c: char) -> bool {
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            if (class[i]..=class[i + 2]).contains(&c) {
+                return true;
+            }
+            i += 3;
+        } else {
+            if class[i] == c {
+                return true;
+            }
+            i += 1;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches_str(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        matches(&pattern, &name)
+    }
+
+    #[test]
+    fn test_star_and_question_mark() {
+        assert!(matches_str("*.txt", "a.txt"));
+        assert!(matches_str("*.txt", ".txt"));
+        assert!(!matches_str("*.txt", "a.txt.bak"));
+        assert!(matches_str("a?c", "abc"));
+        assert!(!matches_str("a?c", "ac"));
+        assert!(matches_str("*b*", "abc"));
+    }
+
+    #[test]
+    fn test_escaped_metacharacters_match_literally() {
+        assert!(matches_str(&escape("a*"), "a*"));
+        assert!(!matches_str(&escape("a*"), "ab"));
+        assert!(!is_pattern(&escape("what?")));
+        assert_eq!(unescape(&escape("a\\b*")), "a\\b*");
+    }
+
+    #[test]
+    fn test_bracket_classes() {
+        assert!(matches_str("file[0-9].log", "file7.log"));
+        assert!(!matches_str("file[0-9].log", "filex.log"));
+        assert!(matches_str("[abc]*", "banana"));
+        assert!(!matches_str("[abc]*", "dog"));
+        assert!(matches_str("[!abc]?", "dx"));
+        assert!(!matches_str("[^abc]?", "ax"));
+        assert!(matches_str("[]x]", "]"));
+        assert!(matches_str("a[b", "a[b"));
+        assert!(!is_pattern(&escape("[x]")));
+    }
+
+    #[test]
+    fn test_expand_bracket_patterns_in_dir() {
+        let temp = std::env::temp_dir().join(format!("glob_bracket_test_{}", std::process::id()));
+        fs::create_dir_all(&temp).unwrap();
+        for name in ["f1.txt", "f2.txt", "f3.txt"] {
+            fs::write(temp.join(name), "").unwrap();
+        }
+
+        assert_eq!(expand("f[12].txt", &temp), vec!["f1.txt", "f2.txt"]);
+        assert_eq!(expand("f[!12].*", &temp), vec!["f3.txt"]);
+        assert!(expand("f[4-9].txt", &temp).is_empty());
+
+        let _ = fs::remove_dir_all(&temp);
+    }
+}
@@ -1,16 +1,25 @@
-use crate::command::{CommandFactory, ExitCode, Stdin};
+use crate::arith;
+use crate::brace;
+use crate::builtin::job_status_line;
+use crate::command::{CommandFactory, CommandKind, ExecutableCommand, ExitCode, Stdin, Stdout};
 use crate::env::Environment;
-use crate::external::find_command_path;
+use crate::external::{ExternalCommand, exit_code, find_command_path};
+use crate::glob;
 use crate::lexer;
-use crate::lexer::WordPart;
-use crate::parser::{self, AstNode, Word};
+use crate::lexer::{LexingError, Token, WordPart};
+use crate::parser::{self, AstNode, RedirectKind, Word};
+use crate::signals::{self, Foreground};
 use crate::{MemReader, MemWriter};
 use rustyline::error::ReadlineError;
 use rustyline::{DefaultEditor, Result};
+use std::borrow::Cow;
 use std::ffi::OsStr;
-use std::io::{Read, Write};
-use std::path::Path;
-use std::process::Stdio;
+use std::fs::{File, OpenOptions};
+use std::io::{PipeReader, PipeWriter, Read, Write};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::thread::JoinHandle;
 
 /// Factory allows creating instances of ExecutableCommand.
 ///
@@ -43,56 +52,680 @@ impl<T> Default for Factory<T> {
 pub struct Interpreter {
     env: Environment,
     commands: Vec<Box<dyn CommandFactory>>,
+    /// How many command substitutions are currently being evaluated.
+    subst_depth: usize,
+    /// Locations of commands already found in PATH.
+    path_cache: PathCache,
+    /// Whether a pipeline fails when any of its stages fails, like bash `set -o pipefail`.
+    pipefail: bool,
+    /// Where the REPL keeps its history; see [`Interpreter::set_history_path`].
+    history_path: Option<PathBuf>,
+    /// The REPL prompt, unless `$PS1` is set; see [`Interpreter::set_prompt`].
+    prompt: String,
+    /// Where the shell's own messages go; see [`Interpreter::set_stderr`].
+    stderr: Box<dyn Write>,
 }
 
+/// Cache of PATH lookups keyed by command name.
+///
+/// Only bare command names are cached: paths like `./foo` or `bin/foo` depend on
+/// the current directory. The cache must be cleared whenever PATH changes.
+#[derive(Default)]
+struct PathCache {
+    entries: HashMap<String, PathBuf>,
+    /// Number of times PATH was actually scanned, used by tests.
+    scans: usize,
+}
+
+impl PathCache {
+    fn resolve(&mut self, search_paths: &OsStr, name: &str) -> Option<PathBuf> {
+        let cacheable = !name.contains(std::path::MAIN_SEPARATOR);
+        if cacheable && let Some(path) = self.entries.get(name) {
+            return Some(path.clone());
+        }
+        self.scans += 1;
+        let path = find_command_path(search_paths, Path::new(name))?.into_owned();
+        if cacheable {
+            self.entries.insert(name.to_string(), path.clone());
+        }
+        Some(path)
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// The name and description under which a factory's builtin is listed in `env.builtins`.
+fn builtin_entry(factory: &dyn CommandFactory) -> Option<(String, String)> {
+    let name = factory.builtin_name()?;
+    Some((name.to_string(), factory.description().unwrap_or_default()))
+}
+
+/// Prefix that runs the rest of a command from PATH, even if a builtin has the same name.
+const COMMAND_PREFIX: &str = "command";
+
+/// Remove a leading `command` from the expanded words of a command that has more words
+/// after it. Returns whether it was removed, meaning builtins must be skipped.
+fn strip_command_prefix(words: &mut Vec<String>) -> bool {
+    if words.len() > 1 && words[0] == COMMAND_PREFIX {
+        words.remove(0);
+        true
+    } else {
+        false
+    }
+}
+
+/// Report a missing command on `stderr` and return 127, the exit code shells use for it.
+fn command_not_found(name: &str, stderr: &mut dyn Write) -> ExitCode {
+    let _ = writeln!(stderr, "{}: command not found", name);
+    127
+}
+
+/// Whether `part` is `$@` (or `${@}`), which expands to one word per positional parameter.
+fn is_all_params(part: &WordPart) -> bool {
+    matches!(part, WordPart::ParamSubst(name) | WordPart::QuotedParamSubst(name) if name == "@")
+}
+
+/// Split the value of an unquoted expansion into fields on the characters of `ifs`.
+///
+/// Runs of whitespace separators count as one and are ignored at both ends, while every
+/// other separator ends a field, so `a,,b` with `IFS=,` gives an empty field in between.
+fn split_fields(value: &str, ifs: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    for c in value.chars() {
+        if !ifs.contains(c) {
+            field.push(c);
+        } else if !c.is_whitespace() || !field.is_empty() {
+            fields.push(std::mem::take(&mut field));
+        }
+    }
+    if !field.is_empty() {
+        fields.push(field);
+    }
+    fields
+}
+
+/// Whether `text` starts a here-document whose delimiter line hasn't been read yet.
+fn is_unfinished_here_doc(text: &str) -> bool {
+    matches!(
+        lexer::split_into_tokens(text.to_string()),
+        Err(LexingError::UnfinishedHereDoc(_))
+    )
+}
+
+/// Remove the line continuations (an unescaped `\` before a newline) from `text`.
+///
+/// Also tells whether `text` ends with an unescaped `\`, meaning the next line should be
+/// appended. Backslashes inside quotes never continue a line.
+fn join_continued_lines(text: &str) -> (String, bool) {
+    let mut joined = String::with_capacity(text.len());
+    let mut quote = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quote != Some('\'') => match chars.next() {
+                None if quote.is_none() => return (joined, true),
+                Some('\n') if quote.is_none() => {}
+                next => {
+                    // An escaped character, like `\"`, never opens or closes a quote
+                    joined.push(c);
+                    joined.extend(next);
+                }
+            },
+            '\'' | '"' => {
+                match quote {
+                    None => quote = Some(c),
+                    Some(open) if open == c => quote = None,
+                    Some(_) => {}
+                }
+                joined.push(c);
+            }
+            c => joined.push(c),
+        }
+    }
+    (joined, false)
+}
+
+/// Whether `text` ends with an unescaped `\` outside quotes, continuing on the next line.
+fn ends_with_line_continuation(text: &str) -> bool {
+    join_continued_lines(text).1
+}
+
+/// Load REPL history from `path`. A missing file just means there is no history yet.
+fn load_history(rl: &mut DefaultEditor, path: &Path) {
+    match rl.load_history(path) {
+        Ok(()) => {}
+        Err(ReadlineError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => eprintln!("history: {}: {}", path.display(), e),
+    }
+}
+
+/// Save REPL history to `path`, reporting (but otherwise ignoring) failures.
+fn save_history(rl: &mut DefaultEditor, path: &Path) {
+    if let Err(e) = rl.save_history(path) {
+        eprintln!("history: {}: {}", path.display(), e);
+    }
+}
+
+/// The REPL prompt used when neither [`Interpreter::set_prompt`] nor `$PS1` changed it.
+const DEFAULT_PROMPT: &str = "🐒$ ";
+
+/// Maximum nesting of `$(...)` substitutions before evaluation is aborted.
+const MAX_SUBST_DEPTH: usize = 64;
+
+/// Errors that can occur while expanding the substitutions of a word.
+///
+/// They reach callers wrapped in [`anyhow::Error`], so use `downcast_ref` to match on them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpansionError {
+    /// The text of a `$(...)` substitution isn't a command that can be run;
+    /// holds the syntax error.
+    UnsupportedCmdSubst(String),
+    /// Command substitutions are nested deeper than the interpreter allows.
+    RecursionLimit,
+    /// The expression inside `${...}` can't be expanded.
+    BadParamExpr(String),
+    /// The expression inside `$((...))` can't be evaluated, e.g. because it divides
+    /// by zero; holds the reason.
+    BadArithmetic(String),
+}
+
+impl std::fmt::Display for ExpansionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpansionError::UnsupportedCmdSubst(reason) => {
+                write!(f, "command substitution: {}", reason)
+            }
+            ExpansionError::RecursionLimit => write!(
+                f,
+                "command substitution nested deeper than {} levels",
+                MAX_SUBST_DEPTH
+            ),
+            ExpansionError::BadParamExpr(text) => write!(f, "bad substitution: {}", text),
+            ExpansionError::BadArithmetic(reason) => write!(f, "arithmetic expansion: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ExpansionError {}
+
 impl Interpreter {
     /// Create a new interpreter with a custom set of command factories.
     pub fn new(commands: Vec<Box<dyn CommandFactory>>) -> Self {
+        let mut env = Environment::new();
+        env.builtins = commands
+            .iter()
+            .filter_map(|factory| builtin_entry(factory.as_ref()))
+            .collect();
+        env.builtins.insert(
+            COMMAND_PREFIX.to_string(),
+            "Run a program from PATH, even if a builtin has the same name.".to_string(),
+        );
         Self {
-            env: Environment::new(),
+            env,
             commands,
+            subst_depth: 0,
+            path_cache: PathCache::default(),
+            pipefail: false,
+            history_path: None,
+            prompt: DEFAULT_PROMPT.to_string(),
+            stderr: Box::new(std::io::stderr()),
+        }
+    }
+
+    /// The factories used by [`Interpreter::default`], in lookup order:
+    /// - built-ins: `pwd`, `cd`, `echo`, `exit`, `cat`, `wc`, `grep`, `head`, `tail`, `tr`, `cut`,
+    ///   `sort`, `ls`, `cp`, `mv`, `rm`, `mkdir`, `touch`, `env`, `which`, `type`, `help`, `alias`,
+    ///   `pushd`, `popd`, `dirs`, `jobs`, `wait`, `yes`, `basename`, `dirname`, `seq`, `printf`,
+    ///   `history`
+    /// - external command launcher
+    ///
+    /// Insert custom factories in front of the list to let them take priority.
+    pub fn default_commands() -> Vec<Box<dyn CommandFactory>> {
+        use crate::builtin::*;
+        vec![
+            Box::new(Factory::<Pwd>::default()),
+            Box::new(Factory::<Cd>::default()),
+            Box::new(Factory::<Echo>::default()),
+            Box::new(Factory::<Exit>::default()),
+            Box::new(Factory::<Cat>::default()),
+            Box::new(Factory::<WC>::default()),
+            Box::new(Factory::<Grep>::default()),
+            Box::new(Factory::<Head>::default()),
+            Box::new(Factory::<Tail>::default()),
+            Box::new(Factory::<Tr>::default()),
+            Box::new(Factory::<Cut>::default()),
+            Box::new(Factory::<Sort>::default()),
+            Box::new(Factory::<Ls>::default()),
+            Box::new(Factory::<Cp>::default()),
+            Box::new(Factory::<Mv>::default()),
+            Box::new(Factory::<Rm>::default()),
+            Box::new(Factory::<Mkdir>::default()),
+            Box::new(Factory::<Touch>::default()),
+            Box::new(Factory::<Env>::default()),
+            Box::new(Factory::<Which>::default()),
+            Box::new(Factory::<Type>::default()),
+            Box::new(Factory::<Help>::default()),
+            Box::new(Factory::<Alias>::default()),
+            Box::new(Factory::<Pushd>::default()),
+            Box::new(Factory::<Popd>::default()),
+            Box::new(Factory::<Dirs>::default()),
+            Box::new(Factory::<Jobs>::default()),
+            Box::new(Factory::<Wait>::default()),
+            Box::new(Factory::<Yes>::default()),
+            Box::new(Factory::<Basename>::default()),
+            Box::new(Factory::<Dirname>::default()),
+            Box::new(Factory::<Seq>::default()),
+            Box::new(Factory::<Printf>::default()),
+            Box::new(Factory::<History>::default()),
+            Box::new(Factory::<ExternalCommand>::default()),
+        ]
+    }
+
+    /// Register an additional command factory.
+    ///
+    /// Factories are queried in registration order and the first one that recognizes
+    /// a command name wins. The factory is appended after the existing ones, so it
+    /// cannot shadow commands they already handle (including programs found in PATH
+    /// for [`Interpreter::default`]).
+    ///
+    /// Example
+    /// ```
+    /// use shell_commands::Interpreter;
+    /// use shell_commands::command::{CommandFactory, ExecutableCommand, ExitCode, Stdin, Stdout};
+    /// use shell_commands::env::Environment;
+    ///
+    /// struct Hello;
+    ///
+    /// impl ExecutableCommand for Hello {
+    ///     fn execute(
+    ///         self: Box<Self>,
+    ///         _stdin: Box<dyn Stdin>,
+    ///         mut stdout: Box<dyn Stdout>,
+    ///         _stderr: Box<dyn Stdout>,
+    ///         _env: &mut Environment,
+    ///     ) -> anyhow::Result<ExitCode> {
+    ///         writeln!(stdout, "hello")?;
+    ///         Ok(0)
+    ///     }
+    /// }
+    ///
+    /// struct HelloFactory;
+    ///
+    /// impl CommandFactory for HelloFactory {
+    ///     fn try_create(
+    ///         &self,
+    ///         _env: &Environment,
+    ///         name: &str,
+    ///         _args: &[&str],
+    ///     ) -> Option<Box<dyn ExecutableCommand>> {
+    ///         (name == "hello-from-doctest").then(|| Box::new(Hello) as Box<dyn ExecutableCommand>)
+    ///     }
+    /// }
+    ///
+    /// let mut sh = Interpreter::default();
+    /// sh.register(Box::new(HelloFactory));
+    /// assert_eq!(sh.run("hello-from-doctest", &[]).unwrap(), 0);
+    /// ```
+    pub fn register(&mut self, factory: Box<dyn CommandFactory>) {
+        if let Some((name, description)) = builtin_entry(factory.as_ref()) {
+            self.env.builtins.insert(name, description);
         }
+        self.commands.push(factory);
+    }
+
+    /// Builder-style variant of [`Interpreter::register`].
+    ///
+    /// Example
+    /// ```
+    /// use shell_commands::Interpreter;
+    /// let mut sh = Interpreter::default_commands()
+    ///     .into_iter()
+    ///     .fold(Interpreter::new(Vec::new()), Interpreter::with_command);
+    /// assert_eq!(sh.run("pwd", &[]).unwrap(), 0);
+    /// ```
+    pub fn with_command(mut self, factory: Box<dyn CommandFactory>) -> Self {
+        self.register(factory);
+        self
     }
 
     /// Run a single command invocation by name with arguments.
     ///
-    /// Returns the command's exit code or an error if the command cannot be created
-    /// or fails to execute.
+    /// Returns the command's exit code, 127 if there is no such command, or an error
+    /// if the command fails to execute.
     pub fn run(&mut self, name: &str, args: &[&str]) -> anyhow::Result<ExitCode> {
         let stdin = InheritedStdin(std::io::stdin().lock());
+        self.run_with_io(name, args, Box::new(stdin), Box::new(std::io::stdout()))
+    }
+
+    /// Like [`Interpreter::run`], but reads from `stdin` and writes to `stdout` instead of
+    /// the process streams, e.g. to capture the output in a [`MemWriter`].
+    pub fn run_with_io(
+        &mut self,
+        name: &str,
+        args: &[&str],
+        stdin: Box<dyn Stdin>,
+        stdout: Box<dyn Stdout>,
+    ) -> anyhow::Result<ExitCode> {
         for factory in &self.commands {
             if let Some(cmd) = factory.try_create(&self.env, name, args) {
-                return cmd.execute(Box::new(stdin), Box::new(std::io::stdout()), &mut self.env);
+                return cmd.execute(stdin, stdout, Box::new(std::io::stderr()), &mut self.env);
+            }
+        }
+        Ok(command_not_found(name, &mut std::io::stderr()))
+    }
+
+    /// Tell what running `name` would execute: a registered builtin or a program in PATH.
+    ///
+    /// Example
+    /// ```
+    /// use shell_commands::Interpreter;
+    /// use shell_commands::command::CommandKind;
+    /// let mut sh = Interpreter::default();
+    /// assert_eq!(sh.classify("cd"), CommandKind::Builtin);
+    /// ```
+    pub fn classify(&mut self, name: &str) -> CommandKind {
+        if self.env.builtins.contains_key(name) {
+            return CommandKind::Builtin;
+        }
+        match self.resolve_external(name) {
+            Some(path) => CommandKind::External(path),
+            None => CommandKind::NotFound,
+        }
+    }
+
+    /// Set the positional parameters: `$0` to `script_name` and `$1`, `$2`, ... to `args`.
+    pub fn set_positional_params(&mut self, script_name: &str, args: &[&str]) {
+        self.env.positional = std::iter::once(script_name)
+            .chain(args.iter().copied())
+            .map(String::from)
+            .collect();
+    }
+
+    /// Enable or disable `pipefail`.
+    ///
+    /// By default a pipeline's exit code is the one of its last stage. With `pipefail`
+    /// enabled it is the exit code of the rightmost stage that failed, or 0 if all succeeded.
+    pub fn set_pipefail(&mut self, enabled: bool) {
+        self.pipefail = enabled;
+    }
+
+    /// Store REPL history in `path` instead of the default location.
+    ///
+    /// By default history goes to `$MONKESH_HISTORY` if it is set, or to
+    /// `~/.monkesh_history` otherwise.
+    pub fn set_history_path(&mut self, path: impl Into<PathBuf>) {
+        self.history_path = Some(path.into());
+    }
+
+    /// Show `prompt` before each line the REPL reads. A `$PS1` variable, if set,
+    /// still takes precedence.
+    pub fn set_prompt(&mut self, prompt: impl Into<String>) {
+        self.prompt = prompt.into();
+    }
+
+    /// Write the shell's own messages, like errors of statements or the `[1] 4242` report
+    /// of a background job, to `stderr` instead of the process stderr. Commands still write
+    /// their errors to the process stderr unless it is redirected.
+    pub fn set_stderr(&mut self, stderr: impl Write + 'static) {
+        self.stderr = Box::new(stderr);
+    }
+
+    /// The prompt set with [`Interpreter::set_prompt`], or the default one.
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    /// The lines run so far with [`Interpreter::eval_line`] or read by the REPL, oldest
+    /// first. This is what the `history` builtin prints.
+    pub fn history(&self) -> &[String] {
+        &self.env.history
+    }
+
+    /// The file the REPL history is loaded from and saved to, if one can be determined.
+    fn history_path(&self) -> Option<PathBuf> {
+        if let Some(path) = &self.history_path {
+            return Some(path.clone());
+        }
+        if let Some(path) = self.env.get_var("MONKESH_HISTORY") {
+            return Some(PathBuf::from(path));
+        }
+        self.env
+            .get_var("HOME")
+            .map(|home| Path::new(&home).join(".monkesh_history"))
+    }
+
+    /// Forget all remembered PATH lookups, e.g. after installing a new program.
+    pub fn clear_path_cache(&mut self) {
+        self.path_cache.clear();
+    }
+
+    /// Set a shell variable, invalidating cached PATH lookups when PATH changes.
+    fn set_var(&mut self, name: &str, value: String) {
+        if name == "PATH" {
+            self.path_cache.clear();
+        }
+        self.env.apply_assignment(name, value);
+    }
+
+    /// Start `name`, the external program at `path`, as a background job and report its
+    /// number and process id like `[1] 4242`. The report goes to the shell's stderr even if
+    /// the job's stderr is redirected, like in bash.
+    fn start_job(
+        &mut self,
+        name: &str,
+        path: PathBuf,
+        args: Vec<String>,
+        stdin: Box<dyn Stdin>,
+        stdout: Box<dyn Stdout>,
+        stderr: Box<dyn Stdout>,
+    ) -> anyhow::Result<ExitCode> {
+        let command_line = std::iter::once(name)
+            .chain(args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let command = ExternalCommand::new(path.into(), args.into_iter().map(Into::into).collect());
+        let child = command.spawn(stdin, stdout, stderr, &self.env)?;
+        let pid = child.id();
+        let id = self.env.jobs.add(command_line, vec![child]);
+        writeln!(self.stderr, "[{}] {}", id, pid)?;
+        Ok(0)
+    }
+
+    /// Report that the builtin `name` can't run in the background. Jobs are child
+    /// processes, so a builtin followed by `&`, alone or in a pipeline, fails instead of
+    /// silently running to completion.
+    fn refuse_background(&mut self, name: &str) -> anyhow::Result<ExitCode> {
+        writeln!(self.stderr, "{}: only programs can run in the background", name)?;
+        Ok(1)
+    }
+
+    /// Register the started programs of a pipeline run with `&` as one job and report
+    /// it like [`Interpreter::start_job`] does, with the process id of the last program.
+    fn add_pipeline_job(
+        &mut self,
+        command_line: String,
+        stages: Vec<StageStatus>,
+    ) -> anyhow::Result<ExitCode> {
+        let mut last_exit = 0;
+        let mut children = Vec::new();
+        for stage in stages {
+            match stage {
+                StageStatus::Done(code) => last_exit = code,
+                StageStatus::Running(child) => children.push(child),
+            }
+        }
+        // Every stage failed to start, so there is nothing left to wait for
+        let Some(pid) = children.last().map(Child::id) else {
+            return Ok(last_exit);
+        };
+        let id = self.env.jobs.add(command_line, children);
+        writeln!(self.stderr, "[{}] {}", id, pid)?;
+        Ok(0)
+    }
+
+    /// Expand the values of a command's `name=value` assignments, in order.
+    /// `VAR=` assigns the empty string.
+    fn eval_assignments(
+        &mut self,
+        assignments: &[AstNode],
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        let mut result = Vec::with_capacity(assignments.len());
+        for assignment in assignments {
+            if let AstNode::Assignment { name, value } = assignment {
+                let value = match value {
+                    Some(word) => self.word_to_string(word)?,
+                    None => String::new(),
+                };
+                result.push((name.clone(), value));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Find an external command in PATH, consulting the lookup cache first.
+    fn resolve_external(&mut self, name: &str) -> Option<PathBuf> {
+        let search_paths = self.env.get_var("PATH")?;
+        self.path_cache.resolve(OsStr::new(&search_paths), name)
+    }
+
+    /// Find an external command in the PATH of `env`, the environment of a pipeline stage.
+    /// Only lookups in the shell's own PATH are cached, `PATH=... cmd` may differ from it.
+    fn resolve_external_in(&mut self, env: &Environment, name: &str) -> Option<PathBuf> {
+        let search_paths = env.get_var("PATH")?;
+        if self.env.get_var("PATH").as_ref() == Some(&search_paths) {
+            return self.resolve_external(name);
+        }
+        find_command_path(OsStr::new(&search_paths), Path::new(name)).map(Cow::into_owned)
+    }
+
+    /// Tokenize, parse and execute one line of input, writing output to the real stdout.
+    ///
+    /// Syntax errors are reported as `syntax error: ...` instead of being executed.
+    /// A blank line (or one holding only a comment) does nothing and succeeds.
+    /// `line` may span several lines joined with a trailing `\`.
+    ///
+    /// Lines that aren't blank are added to the [history](Interpreter::history) first.
+    ///
+    /// Example
+    /// ```
+    /// use shell_commands::Interpreter;
+    /// let mut sh = Interpreter::default();
+    /// assert_eq!(sh.eval_line("echo hello | wc -l").unwrap(), 0);
+    /// assert!(sh.eval_line("echo 'oops").is_err());
+    /// ```
+    pub fn eval_line(&mut self, line: &str) -> anyhow::Result<ExitCode> {
+        if !line.trim().is_empty() {
+            self.env.history.push(line.to_string());
+        }
+        self.eval_statement(line)
+    }
+
+    /// Run one line of input like [`Interpreter::eval_line`], without recording it
+    /// in the history.
+    fn eval_statement(&mut self, line: &str) -> anyhow::Result<ExitCode> {
+        let (line, _) = join_continued_lines(line);
+        let tokens = lexer::split_into_tokens(line)
+            .map_err(|e| anyhow::anyhow!("syntax error: {}", e))?;
+        if tokens.is_empty() {
+            return Ok(0);
+        }
+        let ast = parser::construct_ast(tokens).map_err(|e| anyhow::anyhow!("syntax error: {}", e))?;
+        self.execute_ast(&ast)
+    }
+
+    /// Run a script: evaluate `src` statement by statement with [`Interpreter::eval_line`],
+    /// except that the statements aren't added to the history.
+    ///
+    /// A line ending with `\` is joined with the next one, and here-documents take the
+    /// lines up to their delimiter. Errors are reported on stderr and give the statement
+    /// exit code 1 without stopping the script; `exit` does stop it.
+    /// Returns the exit code of the last statement run.
+    ///
+    /// Example
+    /// ```
+    /// use shell_commands::Interpreter;
+    /// let mut sh = Interpreter::default();
+    /// assert_eq!(sh.run_script("NAME=world\necho hello \\\n  $NAME\n").unwrap(), 0);
+    /// ```
+    pub fn run_script(&mut self, src: &str) -> anyhow::Result<ExitCode> {
+        let mut last_exit = 0;
+        let mut lines = src.lines();
+        while let Some(first) = lines.next() {
+            let mut statement = first.to_string();
+            while is_unfinished_here_doc(&statement) || ends_with_line_continuation(&statement) {
+                match lines.next() {
+                    Some(next) => {
+                        statement.push('\n');
+                        statement.push_str(next);
+                    }
+                    None => break,
+                }
+            }
+
+            last_exit = match self.eval_statement(&statement) {
+                Ok(code) => code,
+                Err(e) => {
+                    writeln!(self.stderr, "{}", e)?;
+                    1
+                }
+            };
+            self.env.last_status = last_exit;
+            if self.env.should_exit {
+                break;
             }
         }
-        Err(anyhow::anyhow!("command not found: {}", name))
+        Ok(last_exit)
     }
 
     /// A placeholder Read-Eval-Print Loop implementation.
-    pub fn repl(&mut self) -> Result<()> {
+    ///
+    /// Returns the status passed to `exit`, or 0 when the input ends.
+    pub fn repl(&mut self) -> Result<ExitCode> {
         // Stolen from basic example in: https://github.com/kkawakam/rustyline
         let mut rl = DefaultEditor::new()?;
+        signals::install_interrupt_handler();
+        let history_path = self.history_path();
+        if let Some(path) = &history_path {
+            load_history(&mut rl, path);
+        }
+        // Lines saved by earlier sessions come first, like in the editor
+        let previous = rl.history().iter().cloned();
+        self.env.history.splice(0..0, previous);
 
         loop {
-            // Added monke
-            let readline = rl.readline("🐒$ ");
+            for (id, command, code) in self.env.jobs.reap() {
+                writeln!(self.stderr, "{}", job_status_line(id, &command, Some(code)))?;
+            }
+            // Looked up on every iteration, so assigning PS1 changes the prompt right away
+            let prompt = self.env.get_var("PS1").unwrap_or_else(|| self.prompt.clone());
+            let readline = rl.readline(&prompt);
             // if cfg!(debug_assertions) {
             //    println!("Read line: {:?}", readline);
             // }
             match readline {
-                Ok(line) => {
+                Ok(mut line) => {
+                    // Keep reading until a here-document ends or the line isn't continued
+                    while is_unfinished_here_doc(&line) || ends_with_line_continuation(&line) {
+                        match rl.readline("> ") {
+                            Ok(next) => {
+                                line.push('\n');
+                                line.push_str(&next);
+                            }
+                            Err(_) => break,
+                        }
+                    }
                     rl.add_history_entry(line.as_str())?;
-                    let tokens = lexer::split_into_tokens(line).unwrap();
-                    // if cfg!(debug_assertions) {
-                    //     println!("Tokens = {:?}", tokens);
-                    // }
-                    let ast = parser::construct_ast(tokens).unwrap();
-                    // if cfg!(debug_assertions) {
-                    //     println!("Ast = {:?}", ast);
-                    // }
-                    let err = self.execute_ast(&ast);
-                    if err.is_err() {
-                        println!("Execution error: {:?}", err.err());
+                    if let Some(path) = &history_path {
+                        save_history(&mut rl, path);
+                    }
+                    if let Err(e) = self.eval_line(&line) {
+                        writeln!(self.stderr, "{}", e)?;
+                    }
+                    if self.env.should_exit {
+                        break;
                     }
                 }
                 Err(ReadlineError::Interrupted) => {
@@ -109,51 +742,236 @@ impl Interpreter {
                 }
             }
         }
+        if let Some(path) = &history_path {
+            save_history(&mut rl, path);
+        }
 
-        Ok(())
+        Ok(if self.env.should_exit { self.env.last_status } else { 0 })
+    }
+
+    /// Run a simple command: open its redirects, expand its words and run it as a builtin,
+    /// an external program or a background job.
+    ///
+    /// Like in POSIX shells, the words are expanded before the `assigned` variables are set,
+    /// so `X=1 echo $X` prints an empty line. The assignments only last while the command
+    /// runs, unless there is no command, as in a bare `X=1`.
+    fn execute_simple_command(
+        &mut self,
+        argv: &[Word],
+        assigned: Vec<(String, String)>,
+        redirects: &[AstNode],
+        background: bool,
+        final_stdout: Box<dyn Stdout>,
+    ) -> anyhow::Result<ExitCode> {
+        // Open redirect targets before running anything, like a real shell would
+        let Some(mut streams) = self.open_redirects(redirects, final_stdout)? else {
+            return Ok(1);
+        };
+
+        // Convert words to the command name and arguments with environment
+        // substitution and filename expansion
+        let argv = self.expand_aliases(argv)?;
+        let mut args = self.expand_words(&argv)?;
+        if args.is_empty() {
+            // Either there are no words, or like an unset `$CMD` alone they expanded
+            // to nothing. Then the assignments change the shell itself.
+            for (name, value) in assigned {
+                self.set_var(&name, value);
+            }
+            return Ok(0);
+        }
+        let skip_builtins = strip_command_prefix(&mut args);
+        let name = args.remove(0);
+        if name == COMMAND_PREFIX && !skip_builtins {
+            return Ok(0); // `command` alone does nothing
+        }
+        if name.is_empty() {
+            return Ok(command_not_found(&argv[0].render(), &mut streams.stderr));
+        }
+
+        // `FOO=bar cmd` only sets FOO for cmd
+        let snapshot = self.env.snapshot(assigned.iter().map(|(name, _)| name.as_str()));
+        let path_assigned = assigned.iter().any(|(name, _)| name == "PATH");
+        for (name, value) in assigned {
+            self.set_var(&name, value);
+        }
+        let result = self.run_command(&name, args, skip_builtins, background, streams);
+        self.env.restore(snapshot);
+        if path_assigned {
+            self.path_cache.clear();
+        }
+        result
+    }
+
+    /// Open the `redirects` of a command. They are applied left to right, so the last one
+    /// for a stream wins and `2>&1` copies wherever stdout points at that moment. Streams
+    /// that aren't redirected are `stdout` and the shell's stderr.
+    ///
+    /// Returns `None` after reporting a redirect that fails, which fails the command.
+    fn open_redirects(
+        &mut self,
+        redirects: &[AstNode],
+        stdout: Box<dyn Stdout>,
+    ) -> anyhow::Result<Option<Streams>> {
+        let mut stdin: Option<Box<dyn Stdin>> = None;
+        let mut stdout_file: Option<File> = None;
+        let mut stderr: Box<dyn Stdout> = Box::new(std::io::stderr());
+        for redirect in redirects {
+            let AstNode::Redirect { kind, target } = redirect else {
+                continue;
+            };
+            let opened = match kind {
+                RedirectKind::Dup { from_fd: 2, to_fd: 1 } => {
+                    // Without a file, stderr goes wherever the command's output does, e.g.
+                    // into a `$(...)` capture. Streams that can't be shared are the shell's
+                    // own stdout.
+                    stderr = match &stdout_file {
+                        Some(file) => Box::new(SharedOut(file.try_clone()?)),
+                        None => stdout
+                            .try_clone()
+                            .unwrap_or_else(|| Box::new(std::io::stdout())),
+                    };
+                    continue;
+                }
+                RedirectKind::Dup { from_fd, to_fd } => {
+                    writeln!(self.stderr, "{}>&{}: unsupported redirection", from_fd, to_fd)?;
+                    return Ok(None);
+                }
+                RedirectKind::HereString => {
+                    let mut text = self.word_to_string(target)?;
+                    text.push('\n');
+                    stdin = Some(Box::new(MemReader::new(text.into_bytes())));
+                    continue;
+                }
+                RedirectKind::HereDoc => {
+                    let body = self.word_to_string(target)?;
+                    stdin = Some(Box::new(MemReader::new(body.into_bytes())));
+                    continue;
+                }
+                _ => {
+                    let path = self.word_to_string(target)?;
+                    let full_path = self.env.current_dir.join(&path);
+                    let file = match kind {
+                        RedirectKind::Input => File::open(&full_path),
+                        RedirectKind::Append | RedirectKind::AppendErr => OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(&full_path),
+                        _ => File::create(&full_path),
+                    };
+                    file.map_err(|e| (path, e))
+                }
+            };
+            match (kind, opened) {
+                (RedirectKind::Input, Ok(file)) => stdin = Some(Box::new(file)),
+                (RedirectKind::OutputErr | RedirectKind::AppendErr, Ok(file)) => {
+                    stderr = Box::new(SharedOut(file))
+                }
+                (_, Ok(file)) => stdout_file = Some(file),
+                (_, Err((path, e))) => {
+                    writeln!(self.stderr, "{}: {}", path, e)?;
+                    return Ok(None);
+                }
+            }
+        }
+        let stdout: Box<dyn Stdout> = match stdout_file {
+            Some(file) => Box::new(SharedOut(file)),
+            None => stdout,
+        };
+        Ok(Some(Streams {
+            stdin,
+            stdout,
+            stderr,
+        }))
+    }
+
+    /// Run the command `name` with the expanded `args` as a builtin, an external program
+    /// or a background job.
+    fn run_command(
+        &mut self,
+        name: &str,
+        args: Vec<String>,
+        skip_builtins: bool,
+        background: bool,
+        streams: Streams,
+    ) -> anyhow::Result<ExitCode> {
+        let Streams {
+            stdin,
+            stdout,
+            mut stderr,
+        } = streams;
+        // Convert Vec<String> to Vec<&str> for the factories
+        let args_ref: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+        if background {
+            // Resolved like in the foreground, so a builtin is refused even if there is
+            // a program with the same name
+            if !skip_builtins && self.env.builtins.contains_key(name) {
+                return self.refuse_background(name);
+            }
+            let Some(path) = self.resolve_external(name) else {
+                return Ok(command_not_found(name, &mut stderr));
+            };
+            // Background jobs don't read from the terminal
+            let stdin = stdin.unwrap_or_else(|| Box::new(MemReader::new(Vec::new())));
+            return self.start_job(name, path, args, stdin, stdout, stderr);
+        }
+
+        // The words are expanded already, so the inherited stdin can be locked now
+        let stdin =
+            stdin.unwrap_or_else(|| Box::new(InheritedStdin(std::io::stdin().lock())));
+        if skip_builtins {
+            let Some(path) = self.resolve_external(name) else {
+                return Ok(command_not_found(name, &mut stderr));
+            };
+            let args = args.into_iter().map(Into::into).collect();
+            let cmd = Box::new(ExternalCommand::new(path.into(), args));
+            return cmd.execute(stdin, stdout, stderr, &mut self.env);
+        }
+        for factory in &self.commands {
+            if let Some(cmd) = factory.try_create(&self.env, name, &args_ref) {
+                return cmd.execute(stdin, stdout, stderr, &mut self.env);
+            }
+        }
+        Ok(command_not_found(name, &mut stderr))
     }
 
+    /// Execute `root`, writing the output of the command (or the last pipeline stage)
+    /// to `final_stdout`.
+    ///
+    /// Adjacent external commands in a pipeline are connected with OS pipes and run
+    /// concurrently, so `yes | head` terminates. Builtins still run in-process one after
+    /// another: they read their whole input and buffer their whole output, so an endless
+    /// producer piped into a builtin never finishes.
+    ///
+    /// Every stage opens its own redirects. A redirected stdin replaces the output of the
+    /// previous stage, and a stage whose stdout is redirected passes nothing on.
+    ///
+    /// A pipeline followed by `&` is started as one job, so all of its stages have to be
+    /// programs. They share a process group that doesn't get the terminal.
+    ///
+    /// A single command runs against the shell's own environment, so `cd` and bare `X=1`
+    /// assignments persist, while the assignments of `X=1 cmd` are undone once cmd exits.
+    /// Every stage of a pipeline of two or more commands gets a copy of the environment,
+    /// so, like in bash, `cd dir | cat` or `X=1 env | sort` don't change the shell.
     fn execute_ast_with_redifined_output(
         &mut self,
         root: &AstNode,
-        final_stdout: &mut dyn Write,
+        final_stdout: Box<dyn Stdout>,
     ) -> anyhow::Result<ExitCode> {
+        // Only programs can run in the background, alone or in a pipeline
+        let (root, background) = match root {
+            AstNode::Background(inner) => (inner.as_ref(), true),
+            root => (root, false),
+        };
         match root {
             AstNode::Command {
                 argv,
                 assignments,
-                redirects: _,
+                redirects,
             } => {
-                // First handle variable assignments
-                for assignment in assignments {
-                    if let AstNode::Assignment { name, value } = assignment {
-                        let value_str = if let Some(word) = value {
-                            self.word_to_string(word)?
-                        } else {
-                            String::new() // Empty string for assignments like VAR=
-                        };
-                        self.env.set_var(name, value_str);
-                    }
-                }
-
-                if argv.is_empty() {
-                    return Ok(0); // Empty command, just return success
-                }
-
-                // Convert the first word to command name
-                let name = self.word_to_string(&argv[0])?;
-
-                // Convert arguments from Word to String with environment substitution
-                let args: Vec<String> = argv
-                    .iter()
-                    .skip(1)
-                    .map(|word| self.word_to_string(word))
-                    .collect::<anyhow::Result<Vec<String>>>()?;
-
-                // Convert Vec<String> to Vec<&str> for the run method
-                let args_ref: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-
-                self.run(&name, &args_ref)
+                let assigned = self.eval_assignments(assignments)?;
+                self.execute_simple_command(argv, assigned, redirects, background, final_stdout)
             }
 
             AstNode::Pipeline(commands) => {
@@ -161,11 +979,19 @@ impl Interpreter {
                     return Err(anyhow::anyhow!("empty pipeline"));
                 }
 
-                let mut previous_output: Option<Vec<u8>> = None;
-                let mut last_exit: crate::command::ExitCode = 0;
+                let mut previous_output: Option<StageOutput> = None;
+                // Stopped on every early return, so that an error leaves nothing behind
+                let mut started = StartedStages::default();
+                // External stages share the process group of the first one
+                let mut pgid: Option<u32> = None;
+                let mut foreground: Option<Foreground> = None;
+                // The last stage of a job writes to the final stdout itself, since nobody
+                // waits to copy its output there
+                let mut final_stdout = Some(final_stdout);
+                let mut job_command = Vec::new();
 
-                for node in commands {
-                    let (argv_vec, assignments_ref, _redirects_ref) = match node {
+                for (index, node) in commands.iter().enumerate() {
+                    let (argv_vec, assignments_ref, redirects_ref) = match node {
                         AstNode::Command {
                             argv,
                             assignments,
@@ -174,114 +1000,195 @@ impl Interpreter {
                         _ => return Err(anyhow::anyhow!("pipeline contains non-command node")),
                     };
 
-                    // apply assignments in a local env clone
-                    let mut local_env = self.env.clone();
-                    for assign in assignments_ref.iter() {
-                        if let AstNode::Assignment { name, value } = assign {
-                            let val = if let Some(w) = value {
-                                self.word_to_string(w)?
-                            } else {
-                                String::new()
-                            };
-                            local_env.set_var(name.clone(), val);
-                        }
-                    }
-
                     if argv_vec.is_empty() {
                         return Err(anyhow::anyhow!("empty command in pipeline"));
                     }
 
-                    // Resolve name and args with local_env by temporarily swapping self.env
-                    let saved_env = std::mem::replace(&mut self.env, local_env.clone());
-                    let name = self.word_to_string(&argv_vec[0])?;
-                    let args: Vec<String> = argv_vec
-                        .iter()
-                        .skip(1)
-                        .map(|w| self.word_to_string(w))
-                        .collect::<anyhow::Result<Vec<String>>>()?;
-                    self.env = saved_env;
+                    // Like for a single command, the words are expanded before the stage's
+                    // assignments are applied, here to its own copy of the environment
+                    let argv_vec = self.expand_aliases(&argv_vec)?;
+                    let mut args = self.expand_words(&argv_vec)?;
+                    let skip_builtins = strip_command_prefix(&mut args);
+                    let name = if args.is_empty() { String::new() } else { args.remove(0) };
+                    let mut stage_env = self.env.clone();
+                    for (name, value) in self.eval_assignments(assignments_ref)? {
+                        stage_env.apply_assignment(&name, value);
+                    }
+                    let stage_command = std::iter::once(name.as_str())
+                        .chain(args.iter().map(String::as_str))
+                        .collect::<Vec<_>>();
+                    job_command.push(stage_command.join(" "));
 
                     // Determine if command is external by PATH lookup
-                    let is_external = match self.env.get_var("PATH") {
-                        Some(paths) => {
-                            find_command_path(OsStr::new(&paths), Path::new(&name)).is_some()
-                        }
-                        None => false,
-                    };
-
-                    if is_external {
-                        // External process: spawn, feed previous_output, read stdout
-                        let path = {
-                            let p = find_command_path(
-                                OsStr::new(&self.env.get_var("PATH").unwrap()),
-                                Path::new(&name),
-                            )
-                            .unwrap();
-                            p.into_owned()
+                    if let Some(path) = self.resolve_external_in(&stage_env, &name) {
+                        // External process: its stdout goes into a pipe to the next stage,
+                        // unless it is redirected. Then the next stage reads nothing.
+                        let is_last = index + 1 == commands.len();
+                        let (reader, stdout): (_, Box<dyn Stdout>) =
+                            match final_stdout.take_if(|_| background && is_last) {
+                                Some(stdout) => (None, stdout),
+                                None => {
+                                    let (reader, writer) = std::io::pipe()?;
+                                    (Some(reader), Box::new(SharedOut(writer)))
+                                }
+                            };
+                        let Some(streams) = self.open_redirects(redirects_ref, stdout)? else {
+                            started.stages.push(StageStatus::Done(1));
+                            previous_output = Some(StageOutput::Buffer(Vec::new()));
+                            continue;
                         };
 
                         let mut cmd = std::process::Command::new(path);
+                        for name in &stage_env.unset_vars {
+                            cmd.env_remove(name);
+                        }
                         cmd.args(&args)
-                            .envs(self.env.vars.iter().map(|(k, v)| (k.as_str(), v.as_str())))
-                            .current_dir(&self.env.current_dir)
-                            .stdin(std::process::Stdio::piped())
-                            .stdout(std::process::Stdio::piped());
+                            .envs(stage_env.vars.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+                            .current_dir(&stage_env.current_dir)
+                            .stdout(streams.stdout.stdio())
+                            .stderr(streams.stderr.stdio());
+                        signals::set_process_group(&mut cmd, pgid.unwrap_or(0));
+                        // A redirected stdin replaces the output of the previous stage
+                        let mut buffered_input = None;
+                        match (streams.stdin, previous_output.take()) {
+                            (Some(stdin), _) => {
+                                cmd.stdin(stdin.stdio());
+                            }
+                            (None, Some(StageOutput::Stream(pipe))) => {
+                                cmd.stdin(Stdio::from(pipe));
+                            }
+                            (None, Some(StageOutput::Buffer(buf))) => {
+                                cmd.stdin(Stdio::piped());
+                                buffered_input = Some(buf);
+                            }
+                            (None, None) => {
+                                cmd.stdin(Stdio::null());
+                            }
+                        }
 
                         let mut child = cmd
                             .spawn()
                             .map_err(|e| anyhow::anyhow!("failed spawn: {}", e))?;
-
-                        if let Some(buf) = previous_output.take() {
-                            if let Some(mut child_stdin) = child.stdin.take() {
-                                child_stdin
-                                    .write_all(&buf)
-                                    .map_err(|e| anyhow::anyhow!(e))?;
-                                drop(child_stdin);
+                        if pgid.is_none() {
+                            pgid = Some(child.id());
+                            if !background {
+                                foreground = Some(Foreground::enter(child.id()));
                             }
-                        } else {
-                            drop(child.stdin.take());
                         }
 
-                        let output = child.wait_with_output().map_err(|e| anyhow::anyhow!(e))?;
-                        previous_output = Some(output.stdout);
-                        last_exit = output.status.code().unwrap_or(1);
+                        if let Some(buf) = buffered_input
+                            && let Some(mut child_stdin) = child.stdin.take()
+                        {
+                            // Feed from a separate thread, so that a child filling up its
+                            // stdout pipe can't deadlock with us filling up its stdin
+                            started.writers.push(std::thread::spawn(move || {
+                                // The child may exit without reading everything
+                                let _ = child_stdin.write_all(&buf);
+                            }));
+                        }
+
+                        previous_output = reader.map(StageOutput::Stream);
+                        started.stages.push(StageStatus::Running(child));
                     } else {
+                        // prepare stdout via with_handle()
+                        let (mw, out_rc) = MemWriter::with_handle();
+                        let Some(streams) = self.open_redirects(redirects_ref, Box::new(mw))? else {
+                            started.stages.push(StageStatus::Done(1));
+                            previous_output = Some(StageOutput::Buffer(Vec::new()));
+                            continue;
+                        };
+                        let Streams {
+                            stdin,
+                            stdout,
+                            mut stderr,
+                        } = streams;
+
                         let mut created: Option<Box<dyn crate::command::ExecutableCommand>> = None;
                         let args_ref_vec: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-                        for factory in &self.commands {
-                            if let Some(c) = factory.try_create(&self.env, &name, &args_ref_vec) {
+                        let factories = if skip_builtins { &[][..] } else { &self.commands[..] };
+                        for factory in factories {
+                            if let Some(c) = factory.try_create(&stage_env, &name, &args_ref_vec) {
                                 created = Some(c);
                                 break;
                             }
                         }
 
-                        let cmd = created
-                            .ok_or_else(|| anyhow::anyhow!("command not found: {}", name))?;
+                        let Some(cmd) = created else {
+                            // Show the word that expanded to an empty name as it was written
+                            let shown = if name.is_empty() { argv_vec[0].render() } else { name };
+                            let code = command_not_found(&shown, &mut stderr);
+                            started.stages.push(StageStatus::Done(code));
+                            previous_output = Some(StageOutput::Buffer(Vec::new()));
+                            continue;
+                        };
+                        if background {
+                            return self.refuse_background(&name);
+                        }
 
+                        // A redirected stdin replaces the output of the previous stage
                         let stdin_box: Box<dyn crate::command::Stdin> =
-                            if let Some(buf) = previous_output.take() {
-                                Box::new(MemReader::new(buf))
-                            } else {
-                                Box::new(InheritedStdin(std::io::stdin().lock()))
+                            match (stdin, previous_output.take()) {
+                                (Some(stdin), _) => stdin,
+                                (None, Some(StageOutput::Buffer(buf))) => {
+                                    Box::new(MemReader::new(buf))
+                                }
+                                (None, Some(StageOutput::Stream(mut pipe))) => {
+                                    // Builtins run in-process, so they get the whole input at once
+                                    let mut buf = Vec::new();
+                                    pipe.read_to_end(&mut buf)?;
+                                    Box::new(MemReader::new(buf))
+                                }
+                                (None, None) => Box::new(InheritedStdin(std::io::stdin().lock())),
                             };
 
-                        // prepare stdout via with_handle()
-                        let (mw, out_rc) = MemWriter::with_handle();
-                        let stdout_box: Box<dyn crate::command::Stdout> = Box::new(mw);
-
-                        // execute
-                        let mut exec_env = self.env.clone();
-                        match cmd.execute(stdin_box, stdout_box, &mut exec_env) {
-                            Ok(code) => last_exit = code,
-                            Err(_) => last_exit = 1,
+                        let code = cmd
+                            .execute(stdin_box, stdout, stderr, &mut stage_env)
+                            .unwrap_or(1);
+                        started.stages.push(StageStatus::Done(code));
+                        if stage_env.current_dir != self.env.current_dir {
+                            // `cd` moves the whole process, but only this stage's copy of
+                            // the environment is meant to change
+                            let _ = std::env::set_current_dir(&self.env.current_dir);
                         }
 
-                        previous_output = Some(out_rc.borrow().clone());
+                        previous_output = Some(StageOutput::Buffer(out_rc.borrow().clone()));
+                    }
+                }
+
+                if background {
+                    // The job's feeding threads finish on their own
+                    started.writers.clear();
+                    let stages = std::mem::take(&mut started.stages);
+                    return self.add_pipeline_job(job_command.join(" | "), stages);
+                }
+                let Some(mut final_stdout) = final_stdout else {
+                    unreachable!("only the last stage of a job takes the final stdout");
+                };
+                match previous_output {
+                    Some(StageOutput::Buffer(out)) => final_stdout.write_all(&out)?,
+                    Some(StageOutput::Stream(mut child_stdout)) => {
+                        std::io::copy(&mut child_stdout, &mut final_stdout)?;
+                    }
+                    None => {}
+                }
+                final_stdout.flush()?;
+
+                let mut exit_codes = Vec::with_capacity(started.stages.len());
+                for stage in &mut started.stages {
+                    if let StageStatus::Running(child) = stage {
+                        *stage = StageStatus::Done(exit_code(child.wait()?));
+                    }
+                    if let StageStatus::Done(code) = stage {
+                        exit_codes.push(*code);
                     }
                 }
+                drop(started);
+                drop(foreground);
 
-                if let Some(out) = previous_output {
-                    final_stdout.write_all(&out)?;
+                let last_exit = exit_codes.last().copied().unwrap_or(0);
+                if self.pipefail {
+                    let failed = exit_codes.iter().rev().find(|&&code| code != 0);
+                    return Ok(failed.copied().unwrap_or(0));
                 }
                 Ok(last_exit)
             }
@@ -293,11 +1200,43 @@ impl Interpreter {
     }
 
     fn execute_ast(&mut self, root: &AstNode) -> anyhow::Result<ExitCode> {
-        self.execute_ast_with_redifined_output(root, &mut std::io::stdout())
+        self.execute_statements(root, &|| Box::new(std::io::stdout()))
     }
 
-    /// Helper method to convert a Word to a String with environment variable substitution
-    fn word_to_string(&self, word: &Word) -> anyhow::Result<String> {
+    /// Execute each statement of a `;` sequence in order, giving every statement its own
+    /// stdout from `make_stdout`. Returns the exit code of the last statement.
+    /// Every statement's exit code is recorded in `env.last_status` for `$?`.
+    ///
+    /// Like in bash, a failing statement doesn't stop the ones after it.
+    fn execute_statements(
+        &mut self,
+        root: &AstNode,
+        make_stdout: &dyn Fn() -> Box<dyn Stdout>,
+    ) -> anyhow::Result<ExitCode> {
+        let AstNode::Sequence(statements) = root else {
+            let result = self.execute_ast_with_redifined_output(root, make_stdout());
+            self.env.last_status = *result.as_ref().unwrap_or(&1);
+            return result;
+        };
+        let mut last_exit = 0;
+        for statement in statements {
+            last_exit = match self.execute_ast_with_redifined_output(statement, make_stdout()) {
+                Ok(code) => code,
+                Err(e) => {
+                    writeln!(self.stderr, "{}", e)?;
+                    1
+                }
+            };
+            self.env.last_status = last_exit;
+            if self.env.should_exit {
+                break;
+            }
+        }
+        Ok(last_exit)
+    }
+
+    /// Helper method to convert a Word to a String with environment variable substitution
+    fn word_to_string(&mut self, word: &Word) -> anyhow::Result<String> {
         match word {
             Word::Literal(s) => Ok(s.clone()),
             Word::Compound(parts) => {
@@ -305,16 +1244,21 @@ impl Interpreter {
                 let mut result = String::new();
                 for part in parts {
                     match part {
-                        WordPart::Literal(text) => result.push_str(text),
-                        WordPart::ParamSubst(var_name) => {
-                            // Handle parameter substitution ${VAR} or $VAR
-                            if let Some(value) = self.env.get_var(var_name) {
-                                result.push_str(&value);
-                            }
-                            // If variable doesn't exist, substitute with empty string (like bash)
+                        WordPart::Literal(text) | WordPart::Glob(text) => result.push_str(text),
+                        WordPart::ParamSubst(content) | WordPart::QuotedParamSubst(content) => {
+                            // Handle parameter substitution ${VAR}, ${VAR:-word}, ... or $VAR
+                            result.push_str(&self.expand_param(content)?);
+                        }
+                        WordPart::CmdSubst(command) => {
+                            result.push_str(&self.substitute_command(command)?);
                         }
-                        WordPart::CmdSubst(_) => {
-                            return Err(anyhow::anyhow!("Command substitutions not yet supported"));
+                        WordPart::ArithSubst(expression) => {
+                            result.push_str(&self.evaluate_arithmetic(expression)?.to_string());
+                        }
+                        WordPart::Tilde => {
+                            // Like bash, keep the `~` as is when HOME is not set
+                            let home = self.env.get_var("HOME");
+                            result.push_str(home.as_deref().unwrap_or("~"));
                         }
                     }
                 }
@@ -322,25 +1266,381 @@ impl Interpreter {
             }
         }
     }
+
+    /// Replace a leading alias name in `argv` with the words of the alias body.
+    ///
+    /// The result is checked for aliases again, but each alias is expanded at most once,
+    /// so `alias ls='ls -a'` and aliases referring to each other can't loop forever.
+    fn expand_aliases(&self, argv: &[Word]) -> anyhow::Result<Vec<Word>> {
+        let mut argv = argv.to_vec();
+        let mut expanded = HashSet::new();
+        // Words with substitutions are never alias names
+        while let Some(Word::Literal(name)) = argv.first()
+            && let Some(body) = self.env.aliases.get(name)
+            && expanded.insert(name.clone())
+        {
+            let tokens = lexer::split_into_tokens(body.clone())
+                .map_err(|e| anyhow::anyhow!("alias {}: {}", name, e))?;
+            let body_argv = match parser::construct_ast(tokens) {
+                Ok(AstNode::Command {
+                    argv,
+                    assignments,
+                    redirects,
+                }) if assignments.is_empty() && redirects.is_empty() => argv,
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "alias {}: only simple commands are supported",
+                        name
+                    ));
+                }
+            };
+            argv.splice(..1, body_argv);
+        }
+        Ok(argv)
+    }
+
+    /// Expand command words into arguments, replacing brace expressions like `{a,b}` with
+    /// their alternatives and glob patterns with the matching file names. A pattern that
+    /// matches nothing is kept as is, like in bash.
+    ///
+    /// A word containing `$@` becomes one argument per positional parameter, and the values
+    /// of unquoted parameter expansions are split into several arguments on `IFS`.
+    fn expand_words(&mut self, words: &[Word]) -> anyhow::Result<Vec<String>> {
+        let mut result = Vec::with_capacity(words.len());
+        for word in words {
+            match word {
+                // Brace expansion comes first, so `{a,b}*` is two glob patterns
+                Word::Compound(parts) if brace::has_braces(parts) => {
+                    for parts in brace::expand(parts) {
+                        self.expand_word(&Word::Compound(parts), &mut result)?;
+                    }
+                }
+                word => self.expand_word(word, &mut result)?,
+            }
+        }
+        Ok(result)
+    }
+
+    /// Expand a word without braces into `result`, see [`Self::expand_words`].
+    fn expand_word(&mut self, word: &Word, result: &mut Vec<String>) -> anyhow::Result<()> {
+        let Word::Compound(parts) = word else {
+            result.push(self.word_to_string(word)?);
+            return Ok(());
+        };
+        let needs_splitting =
+            |part: &WordPart| is_all_params(part) || matches!(part, WordPart::ParamSubst(_));
+        if parts.iter().any(needs_splitting) {
+            result.extend(self.expand_fields(parts)?);
+            return Ok(());
+        }
+        if !parts.iter().any(|part| matches!(part, WordPart::Glob(_))) {
+            result.push(self.word_to_string(word)?);
+            return Ok(());
+        }
+        // Everything except the glob parts has to match literally
+        let mut pattern = String::new();
+        for part in parts {
+            match part {
+                WordPart::Glob(text) => pattern.push_str(text),
+                part => {
+                    let text = self.word_to_string(&Word::Compound(vec![part.clone()]))?;
+                    pattern.push_str(&glob::escape(&text));
+                }
+            }
+        }
+        let matches = glob::expand(&pattern, &self.env.current_dir);
+        if matches.is_empty() {
+            result.push(self.word_to_string(word)?);
+        } else {
+            result.extend(matches);
+        }
+        Ok(())
+    }
+
+    /// Expand a word containing `$@` or unquoted parameter expansions into fields.
+    ///
+    /// `$@` gives one field per positional parameter and unquoted values are split on `IFS`
+    /// (space, tab and newline when unset). The text before such an expansion joins its
+    /// first field and the text after it joins the last one, like `"x$@y"` in bash.
+    /// A word made only of expansions that produce nothing expands to no arguments at all.
+    ///
+    /// Fields holding a glob part, like the one of `$DIR/*.txt`, are then matched against
+    /// file names, with the expanded values taken literally.
+    fn expand_fields(&mut self, parts: &[WordPart]) -> anyhow::Result<Vec<String>> {
+        let ifs = self.env.get_var("IFS").unwrap_or_else(|| " \t\n".to_string());
+        let mut fields = vec![Field::default()];
+        let mut only_expansions = true;
+        for part in parts {
+            let pieces = if is_all_params(part) {
+                self.env.positional.get(1..).unwrap_or_default().to_vec()
+            } else if let WordPart::ParamSubst(content) = part {
+                split_fields(&self.expand_param(content)?, &ifs)
+            } else {
+                only_expansions = false;
+                if let (WordPart::Glob(pattern), Some(last)) = (part, fields.last_mut()) {
+                    last.text.push_str(pattern);
+                    last.pattern.push_str(pattern);
+                    last.has_glob = true;
+                    continue;
+                }
+                vec![self.word_to_string(&Word::Compound(vec![part.clone()]))?]
+            };
+            let mut pieces = pieces.into_iter();
+            if let (Some(first), Some(last)) = (pieces.next(), fields.last_mut()) {
+                last.push_literal(&first);
+            }
+            fields.extend(pieces.map(|piece| {
+                let mut field = Field::default();
+                field.push_literal(&piece);
+                field
+            }));
+        }
+        if only_expansions && fields.iter().all(|field| field.text.is_empty()) {
+            return Ok(Vec::new());
+        }
+        let mut result = Vec::with_capacity(fields.len());
+        for field in fields {
+            let matches = match field.has_glob {
+                true => glob::expand(&field.pattern, &self.env.current_dir),
+                false => Vec::new(),
+            };
+            if matches.is_empty() {
+                result.push(field.text);
+            } else {
+                result.extend(matches);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Expand the contents of a parameter substitution.
+    ///
+    /// Besides plain `${VAR}` the POSIX forms `${VAR:-word}`, `${VAR-word}`, `${VAR:=word}`,
+    /// `${VAR=word}`, `${VAR:+word}` and `${VAR+word}` are supported. With a colon an empty
+    /// variable is treated as unset. Unset variables expand to an empty string (like bash).
+    fn expand_param(&mut self, content: &str) -> anyhow::Result<String> {
+        let name_len = match content.chars().next() {
+            Some('?' | '#' | '@' | '*') => 1,
+            Some(c) if c.is_ascii_digit() => content
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(content.len()),
+            _ => content
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(content.len()),
+        };
+        let (name, rest) = content.split_at(name_len);
+        let (treat_empty_as_unset, operation) = match rest.strip_prefix(':') {
+            Some(operation) => (true, operation),
+            None => (false, rest),
+        };
+
+        let mut operation_chars = operation.chars();
+        let operator = operation_chars.next();
+        let word = operation_chars.as_str();
+        let value = match operator {
+            Some('-' | '=' | '+') => self.lookup_param(name),
+            // Not one of the supported forms: look the whole thing up as a name
+            _ => return Ok(self.lookup_param(content).unwrap_or_default()),
+        };
+        let is_set = value
+            .as_ref()
+            .is_some_and(|v| !(treat_empty_as_unset && v.is_empty()));
+
+        match (operator, value) {
+            (Some('-'), Some(value)) if is_set => Ok(value),
+            (Some('-'), _) => self.expand_text(word),
+            (Some('='), Some(value)) if is_set => Ok(value),
+            (Some('='), _) => {
+                let expanded = self.expand_text(word)?;
+                self.set_var(name, expanded.clone());
+                Ok(expanded)
+            }
+            (Some('+'), _) if is_set => self.expand_text(word),
+            _ => Ok(String::new()),
+        }
+    }
+
+    /// The value of a variable, or of a special (`?`, `#`, `@`, `*`) or positional
+    /// (`0`, `1`, ...) parameter.
+    ///
+    /// `$*` joins the parameters with the first character of `IFS` (a space by default),
+    /// and so does `$@` where a single string is needed.
+    fn lookup_param(&self, name: &str) -> Option<String> {
+        let params = self.env.positional.get(1..).unwrap_or_default();
+        match name {
+            "?" => return Some(self.env.last_status.to_string()),
+            "#" => return Some(params.len().to_string()),
+            "@" | "*" => {
+                let separator = match self.env.get_var("IFS") {
+                    Some(ifs) => ifs.chars().next().map(String::from).unwrap_or_default(),
+                    None => " ".to_string(),
+                };
+                return Some(params.join(&separator));
+            }
+            _ => {}
+        }
+        if let Ok(index) = name.parse::<usize>() {
+            return self.env.positional.get(index).cloned();
+        }
+        self.env.get_var(name)
+    }
+
+    /// Expand substitutions inside raw text as if it was written in double quotes.
+    fn expand_text(&mut self, text: &str) -> anyhow::Result<String> {
+        if text.is_empty() {
+            return Ok(String::new());
+        }
+        let bad_expr = || ExpansionError::BadParamExpr(text.to_string());
+        let tokens = lexer::split_into_tokens(format!("\"{}\"", text)).map_err(|_| bad_expr())?;
+        match tokens.as_slice() {
+            [Token::Word(parts)] => self.word_to_string(&Word::Compound(parts.clone())),
+            _ => Err(bad_expr().into()),
+        }
+    }
+
+    /// Evaluate the expression of a `$((...))` expansion. Substitutions like `$X` inside it
+    /// are expanded first, then bare names are looked up as variables.
+    fn evaluate_arithmetic(&mut self, expression: &str) -> anyhow::Result<i64> {
+        let expression = self.expand_text(expression)?;
+        arith::evaluate(&expression, |name| self.env.get_var(name))
+            .map_err(|e| ExpansionError::BadArithmetic(e.to_string()).into())
+    }
+
+    /// Execute the text of a `$(...)` substitution and return its captured stdout
+    /// with trailing newlines removed.
+    fn substitute_command(&mut self, command: &str) -> anyhow::Result<String> {
+        if self.subst_depth >= MAX_SUBST_DEPTH {
+            return Err(ExpansionError::RecursionLimit.into());
+        }
+
+        let tokens = lexer::split_into_tokens(command.to_string())
+            .map_err(|e| ExpansionError::UnsupportedCmdSubst(e.to_string()))?;
+        let ast = parser::construct_ast(tokens)
+            .map_err(|e| ExpansionError::UnsupportedCmdSubst(e.to_string()))?;
+
+        let (out, out_rc) = MemWriter::with_handle();
+        self.subst_depth += 1;
+        let result = self.execute_statements(&ast, &|| Box::new(out.clone()));
+        self.subst_depth -= 1;
+        result?;
+
+        let output = String::from_utf8_lossy(&out_rc.borrow()).into_owned();
+        Ok(output.trim_end_matches('\n').to_string())
+    }
 }
 
 impl Default for Interpreter {
-    /// Create an interpreter with the default set of commands:
-    /// - built-ins: `pwd`, `cd`, `echo`
-    /// - external command launcher
+    /// Create an interpreter with [`Interpreter::default_commands`].
     fn default() -> Self {
-        use crate::builtin::*;
-        use crate::external::ExternalCommand;
-        Self::new(vec![
-            Box::new(Factory::<Pwd>::default()),
-            Box::new(Factory::<Cd>::default()),
-            Box::new(Factory::<Echo>::default()),
-            Box::new(Factory::<Exit>::default()),
-            Box::new(Factory::<Cat>::default()),
-            Box::new(Factory::<WC>::default()),
-            Box::new(Factory::<Grep>::default()),
-            Box::new(Factory::<ExternalCommand>::default()),
-        ])
+        Self::new(Self::default_commands())
+    }
+}
+
+/// Output of a pipeline stage waiting to be consumed by the next one.
+///
+/// It is kept as raw bytes, since programs may write anything, not just UTF-8. Builtins
+/// that work with text convert it lossily themselves.
+enum StageOutput {
+    /// Everything a builtin has written.
+    Buffer(Vec<u8>),
+    /// The read end of the pipe a still running external process writes to.
+    Stream(PipeReader),
+}
+
+/// A field of a word being split by [`Interpreter::expand_fields`].
+#[derive(Default)]
+struct Field {
+    /// The field as it is passed on when it isn't globbed or nothing matches.
+    text: String,
+    /// The field as a glob pattern, with everything but the glob parts escaped.
+    pattern: String,
+    has_glob: bool,
+}
+
+impl Field {
+    fn push_literal(&mut self, text: &str) {
+        self.text.push_str(text);
+        self.pattern.push_str(&glob::escape(text));
+    }
+}
+
+/// The streams a command runs with, once its redirects are applied.
+struct Streams {
+    /// `None` unless stdin is redirected. The shell's own stdin is only locked right before
+    /// the command runs, since expanding its words may run command substitutions.
+    stdin: Option<Box<dyn Stdin>>,
+    stdout: Box<dyn Stdout>,
+    stderr: Box<dyn Stdout>,
+}
+
+/// A pipeline stage that either has finished already or is still running.
+enum StageStatus {
+    Done(ExitCode),
+    Running(Child),
+}
+
+/// The stages of a pipeline started so far, with the threads feeding buffered input to
+/// them. Dropping it stops the programs still running and waits for them and the
+/// threads, so that a pipeline that can't go on leaves none of them behind.
+#[derive(Default)]
+struct StartedStages {
+    stages: Vec<StageStatus>,
+    writers: Vec<JoinHandle<()>>,
+}
+
+impl Drop for StartedStages {
+    fn drop(&mut self) {
+        for stage in self.stages.drain(..) {
+            if let StageStatus::Running(mut child) = stage {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+        for writer in self.writers.drain(..) {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// A redirect target or the pipe to the next pipeline stage. Unlike the bare handle it
+/// can be shared, so that `2>&1` and errors about starting a program reach it too.
+struct SharedOut<W>(W);
+
+/// Handles that [`SharedOut`] can duplicate.
+trait Duplicate: Sized {
+    fn duplicate(&self) -> std::io::Result<Self>;
+}
+
+impl Duplicate for File {
+    fn duplicate(&self) -> std::io::Result<Self> {
+        self.try_clone()
+    }
+}
+
+impl Duplicate for PipeWriter {
+    fn duplicate(&self) -> std::io::Result<Self> {
+        self.try_clone()
+    }
+}
+
+impl<W: Write> Write for SharedOut<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write + Into<Stdio> + Duplicate + 'static> Stdout for SharedOut<W> {
+    fn stdio(self: Box<Self>) -> Stdio {
+        self.0.into()
+    }
+
+    fn try_clone(&self) -> Option<Box<dyn Stdout>> {
+        let writer = self.0.duplicate().ok()?;
+        Some(Box::new(SharedOut(writer)))
     }
 }
 
@@ -359,6 +1659,7 @@ impl Stdin for InheritedStdin<'_> {
 }
 
 #[cfg(test)]
+#[allow(clippy::vec_init_then_push)]
 mod pipeline_tests {
     use crate::Interpreter;
 
@@ -382,16 +1683,1334 @@ mod pipeline_tests {
         let tokens = crate::lexer::split_into_tokens(line).unwrap();
         let ast = crate::parser::construct_ast(tokens).unwrap();
 
-        let mut out_buf: Vec<u8> = Vec::new();
+        let (out, out_rc) = crate::MemWriter::with_handle();
         let code = interp
-            .execute_ast_with_redifined_output(&ast, &mut out_buf)
+            .execute_ast_with_redifined_output(&ast, Box::new(out))
             .unwrap();
         assert_eq!(code, 0);
 
-        let s = String::from_utf8(out_buf).expect("utf8");
+        let s = String::from_utf8(out_rc.borrow().clone()).expect("utf8");
 
         let normalized = s.split_whitespace().collect::<Vec<&str>>().join(" ");
 
         assert_eq!(normalized.trim(), "1 1 3");
     }
 }
+
+#[cfg(test)]
+mod history_tests {
+    use super::{load_history, save_history};
+    use rustyline::DefaultEditor;
+    use std::env as stdenv;
+    use std::fs;
+
+    #[test]
+    fn test_history_round_trip() {
+        let path = stdenv::temp_dir().join(format!("monkesh_history_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let mut rl = DefaultEditor::new().unwrap();
+        // A missing history file is not an error
+        load_history(&mut rl, &path);
+        rl.add_history_entry("echo first").unwrap();
+        rl.add_history_entry("ls | wc -l").unwrap();
+        save_history(&mut rl, &path);
+
+        let mut reloaded = DefaultEditor::new().unwrap();
+        load_history(&mut reloaded, &path);
+        let entries: Vec<&String> = reloaded.history().iter().collect();
+        assert_eq!(entries, vec!["echo first", "ls | wc -l"]);
+
+        let _ = fs::remove_file(&path);
+    }
+}
+
+#[cfg(test)]
+mod execution_tests {
+    use crate::test_util::lock_current_dir;
+    use crate::{Interpreter, MemReader, MemWriter};
+    use crate::command::CommandKind;
+    use std::env as stdenv;
+    use std::fs;
+
+    fn run_line(interp: &mut Interpreter, line: &str) -> (crate::command::ExitCode, String) {
+        let tokens = crate::lexer::split_into_tokens(line.to_string()).unwrap();
+        let ast = crate::parser::construct_ast(tokens).unwrap();
+        let (out, out_rc) = crate::MemWriter::with_handle();
+        let code = interp
+            .execute_statements(&ast, &|| Box::new(out.clone()))
+            .unwrap();
+        let s = String::from_utf8(out_rc.borrow().clone()).expect("utf8");
+        (code, s)
+    }
+
+    #[test]
+    fn test_input_redirect_feeds_wc() {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("redirect_input_{}", std::process::id()));
+        fs::write(&tmp, "one two\nthree\n").expect("write tmp file");
+
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, &format!("wc < \"{}\"", tmp.display()));
+
+        assert_eq!(code, 0);
+        assert_eq!(out, " 2  3 14\n");
+
+        let _ = fs::remove_file(tmp);
+    }
+
+    #[test]
+    fn test_input_redirect_missing_file_fails() {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("redirect_missing_{}", std::process::id()));
+
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, &format!("wc < \"{}\"", tmp.display()));
+
+        assert_ne!(code, 0);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_output_redirect_truncates_file() {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("redirect_output_{}", std::process::id()));
+        fs::write(&tmp, "stale contents that should disappear\n").expect("write tmp file");
+
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, &format!("echo hi > \"{}\"", tmp.display()));
+
+        assert_eq!(code, 0);
+        assert!(out.is_empty());
+        assert_eq!(fs::read_to_string(&tmp).unwrap(), "hi\n");
+
+        let _ = fs::remove_file(tmp);
+    }
+
+    #[test]
+    fn test_output_redirect_append_keeps_contents() {
+        let mut tmp = stdenv::temp_dir();
+        tmp.push(format!("redirect_append_{}", std::process::id()));
+        let _ = fs::remove_file(&tmp);
+
+        let mut interp = Interpreter::default();
+        let (code, _) = run_line(&mut interp, &format!("echo one >> \"{}\"", tmp.display()));
+        assert_eq!(code, 0);
+        let (code, _) = run_line(&mut interp, &format!("echo two >> \"{}\"", tmp.display()));
+        assert_eq!(code, 0);
+
+        assert_eq!(fs::read_to_string(&tmp).unwrap(), "one\ntwo\n");
+
+        let _ = fs::remove_file(tmp);
+    }
+
+    #[test]
+    fn test_output_redirect_last_one_wins() {
+        let mut first = stdenv::temp_dir();
+        first.push(format!("redirect_first_{}", std::process::id()));
+        let mut second = stdenv::temp_dir();
+        second.push(format!("redirect_second_{}", std::process::id()));
+
+        let mut interp = Interpreter::default();
+        let line = format!(
+            "echo hi > \"{}\" > \"{}\"",
+            first.display(),
+            second.display()
+        );
+        let (code, _) = run_line(&mut interp, &line);
+
+        assert_eq!(code, 0);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "hi\n");
+
+        let _ = fs::remove_file(first);
+        let _ = fs::remove_file(second);
+    }
+
+    #[test]
+    fn test_command_substitution_nested() {
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, "echo $(echo $(echo nested))");
+
+        assert_eq!(code, 0);
+        assert_eq!(out, "nested\n");
+    }
+
+    #[test]
+    fn test_param_default_colon_dash() {
+        let mut interp = Interpreter::default();
+        interp.env.set_var("SET_VAR", "value");
+        interp.env.set_var("EMPTY_VAR", "");
+        interp.env.set_var("FALLBACK", "fb");
+
+        assert_eq!(interp.expand_param("SET_VAR:-word").unwrap(), "value");
+        assert_eq!(interp.expand_param("EMPTY_VAR:-word").unwrap(), "word");
+        assert_eq!(interp.expand_param("UNSET_VAR_15:-${FALLBACK}!").unwrap(), "fb!");
+    }
+
+    #[test]
+    fn test_param_default_dash() {
+        let mut interp = Interpreter::default();
+        interp.env.set_var("SET_VAR", "value");
+        interp.env.set_var("EMPTY_VAR", "");
+
+        assert_eq!(interp.expand_param("SET_VAR-word").unwrap(), "value");
+        assert_eq!(interp.expand_param("EMPTY_VAR-word").unwrap(), "");
+        assert_eq!(interp.expand_param("UNSET_VAR_15-word").unwrap(), "word");
+    }
+
+    #[test]
+    fn test_param_assign_colon_equal() {
+        let mut interp = Interpreter::default();
+        interp.env.set_var("SET_VAR", "value");
+
+        assert_eq!(interp.expand_param("SET_VAR:=word").unwrap(), "value");
+        assert_eq!(interp.env.get_var("SET_VAR"), Some("value".to_string()));
+
+        assert_eq!(interp.expand_param("ASSIGNED_VAR_15:=word").unwrap(), "word");
+        assert_eq!(
+            interp.env.get_var("ASSIGNED_VAR_15"),
+            Some("word".to_string())
+        );
+    }
+
+    #[test]
+    fn test_param_alternative_colon_plus() {
+        let mut interp = Interpreter::default();
+        interp.env.set_var("SET_VAR", "value");
+        interp.env.set_var("EMPTY_VAR", "");
+
+        assert_eq!(interp.expand_param("SET_VAR:+[${SET_VAR}]").unwrap(), "[value]");
+        assert_eq!(interp.expand_param("EMPTY_VAR:+word").unwrap(), "");
+        assert_eq!(interp.expand_param("UNSET_VAR_15:+word").unwrap(), "");
+    }
+
+    #[test]
+    fn test_param_expansion_in_command_line() {
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, "echo ${UNSET_VAR_15:-fallback}");
+
+        assert_eq!(code, 0);
+        assert_eq!(out, "fallback\n");
+    }
+
+    #[test]
+    fn test_tilde_expands_in_cd_argument() {
+        let mut interp = Interpreter::default();
+        interp.env.set_var("HOME", "/home/tester");
+
+        let tokens = crate::lexer::split_into_tokens("cd ~".to_string()).unwrap();
+        let ast = crate::parser::construct_ast(tokens).unwrap();
+        let crate::parser::AstNode::Command { argv, .. } = ast else {
+            panic!("Expected Command node");
+        };
+        assert_eq!(interp.word_to_string(&argv[1]).unwrap(), "/home/tester");
+    }
+
+    #[test]
+    fn test_tilde_expands_as_path_prefix() {
+        let mut interp = Interpreter::default();
+        interp.env.set_var("HOME", "/home/tester");
+
+        let (code, out) = run_line(&mut interp, "echo ~/foo");
+        assert_eq!(code, 0);
+        assert_eq!(out, "/home/tester/foo\n");
+    }
+
+    #[test]
+    fn test_paths_are_separate_arguments() {
+        let mut interp = Interpreter::default();
+
+        let (_, out) = run_line(&mut interp, "echo a/b c /d");
+        assert_eq!(out, "a/b c /d\n");
+        let (code, out) = run_line(&mut interp, "X=/nonexistent; echo $X/file");
+        assert_eq!((code, out.as_str()), (0, "/nonexistent/file\n"));
+    }
+
+    #[test]
+    fn test_tilde_left_untouched_when_not_a_prefix() {
+        let mut interp = Interpreter::default();
+        interp.env.set_var("HOME", "/home/tester");
+
+        let (_, out) = run_line(&mut interp, "echo ~user a~ '~'");
+        assert_eq!(out, "~user a~ ~\n");
+    }
+
+    #[test]
+    fn test_path_lookups_are_cached() {
+        let mut interp = Interpreter::default();
+        interp.set_var("PATH", "/bin:/usr/bin".to_string());
+
+        let first = interp.resolve_external("sh").expect("sh should be in PATH");
+        let second = interp.resolve_external("sh").expect("sh should be in PATH");
+        assert_eq!(first, second);
+        assert_eq!(interp.path_cache.scans, 1);
+
+        // Changing PATH drops the remembered locations
+        interp.set_var("PATH", "/usr/bin:/bin".to_string());
+        interp.resolve_external("sh");
+        assert_eq!(interp.path_cache.scans, 2);
+
+        interp.clear_path_cache();
+        interp.resolve_external("sh");
+        assert_eq!(interp.path_cache.scans, 3);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipeline_streams_between_external_commands() {
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, "yes | head -n 3");
+
+        assert_eq!(code, 0);
+        assert_eq!(out, "y\ny\ny\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipeline_with_three_external_stages() {
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, "echo b a c | tr \" \" \"\\n\" | sort");
+
+        assert_eq!(code, 0);
+        assert_eq!(out, "a\nb\nc\n");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipeline_exit_code_without_pipefail() {
+        let mut interp = Interpreter::default();
+        let (code, _) = run_line(&mut interp, "true | false | true");
+        assert_eq!(code, 0);
+
+        let (code, _) = run_line(&mut interp, "true | true | false");
+        assert_eq!(code, 1);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipeline_exit_code_with_pipefail() {
+        let mut interp = Interpreter::default();
+        interp.set_pipefail(true);
+
+        let (code, _) = run_line(&mut interp, "true | false | true");
+        assert_eq!(code, 1);
+
+        let (code, _) = run_line(&mut interp, "sh -c \"exit 3\" | sh -c \"exit 2\" | true");
+        assert_eq!(code, 2);
+
+        let (code, _) = run_line(&mut interp, "true | true");
+        assert_eq!(code, 0);
+
+        // Stages killed by a signal fail with 128 + the signal number
+        let (code, _) = run_line(&mut interp, "sh -c 'kill -9 $$' | true");
+        assert_eq!(code, 137);
+        let (code, out) = run_line(&mut interp, "yes | head -n 1");
+        assert_eq!((code, out.as_str()), (141, "y\n"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipeline_stages_redirect_their_ends() {
+        let dir = stdenv::temp_dir().join(format!("pipeline_redirects_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("in.txt");
+        let output = dir.join("out.txt");
+        fs::write(&input, "one two\nthree\n").unwrap();
+
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, &format!("cat < \"{}\" | wc -w", input.display()));
+        assert_eq!(code, 0);
+        assert_eq!(out.trim(), "3");
+
+        let line = format!("echo hi | tr a-z A-Z > \"{}\"", output.display());
+        let (code, out) = run_line(&mut interp, &line);
+        assert_eq!(code, 0);
+        assert_eq!(out, "");
+        assert_eq!(fs::read_to_string(&output).unwrap(), "HI\n");
+
+        // A stage that writes to a file passes nothing down the pipeline
+        let line = format!("echo hi > \"{}\" | wc -c", output.display());
+        let (_, out) = run_line(&mut interp, &line);
+        assert_eq!(out.trim(), "0");
+        assert_eq!(fs::read_to_string(&output).unwrap(), "hi\n");
+
+        let (code, _) = run_line(&mut interp, "cat < missing_pipeline_input | wc -l");
+        assert_eq!(code, 0);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipeline_stages_redirect_stderr() {
+        let err_file = stdenv::temp_dir().join(format!("pipeline_stderr_{}", std::process::id()));
+
+        let mut interp = Interpreter::default();
+        let line = format!("sh -c \"echo err 1>&2\" 2> \"{}\" | wc -c", err_file.display());
+        let (_, out) = run_line(&mut interp, &line);
+        assert_eq!(out.trim(), "0");
+        assert_eq!(fs::read_to_string(&err_file).unwrap(), "err\n");
+
+        let (_, out) = run_line(&mut interp, "sh -c \"echo err 1>&2\" 2>&1 | wc -c");
+        assert_eq!(out.trim(), "4");
+
+        let (_, out) = run_line(&mut interp, "echo in | sh -c \"cat; echo err 1>&2\" 2>&1");
+        assert_eq!(out, "in\nerr\n");
+
+        // `cd` only exists as a builtin
+        let (_, out) = run_line(&mut interp, "cd missing_pipeline_dir 2>&1 | wc -l");
+        assert_eq!(out.trim(), "1");
+
+        let _ = fs::remove_file(err_file);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_stderr_redirect_to_file() {
+        let mut out_file = stdenv::temp_dir();
+        out_file.push(format!("redirect_stderr_{}", std::process::id()));
+        let _ = fs::remove_file(&out_file);
+
+        let mut interp = Interpreter::default();
+        let line = format!(
+            "sh -c \"echo oops 1>&2\" 2> \"{}\"",
+            out_file.display()
+        );
+        let (code, _) = run_line(&mut interp, &line);
+        assert_eq!(code, 0);
+        assert_eq!(fs::read_to_string(&out_file).unwrap(), "oops\n");
+
+        let line = format!(
+            "sh -c \"echo again 1>&2\" 2>> \"{}\"",
+            out_file.display()
+        );
+        run_line(&mut interp, &line);
+        assert_eq!(fs::read_to_string(&out_file).unwrap(), "oops\nagain\n");
+
+        let _ = fs::remove_file(out_file);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_stderr_dup_merges_into_stdout_file() {
+        let mut out_file = stdenv::temp_dir();
+        out_file.push(format!("redirect_dup_{}", std::process::id()));
+
+        let mut interp = Interpreter::default();
+        let line = format!(
+            "sh -c \"echo out; echo err 1>&2\" > \"{}\" 2>&1",
+            out_file.display()
+        );
+        let (code, _) = run_line(&mut interp, &line);
+
+        assert_eq!(code, 0);
+        assert_eq!(fs::read_to_string(&out_file).unwrap(), "out\nerr\n");
+
+        let _ = fs::remove_file(out_file);
+    }
+
+    #[test]
+    fn test_stderr_dup_follows_captured_stdout() {
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, "cat missing_dup_file 2>&1");
+
+        assert_eq!(code, 1);
+        assert!(out.contains("missing_dup_file"), "{:?}", out);
+
+        let (_, out) = run_line(&mut interp, "x=$(cat missing_dup_file 2>&1); echo \"[$x]\"");
+        assert!(out.starts_with("[cat: missing_dup_file"), "{:?}", out);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_program_output_is_captured() {
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, "sh -c \"echo out\"");
+
+        assert_eq!(code, 0);
+        assert_eq!(out, "out\n");
+
+        let (_, out) = run_line(&mut interp, "sh -c \"echo err 1>&2\" 2>&1");
+        assert_eq!(out, "err\n");
+
+        let (_, out) = run_line(&mut interp, "x=$(sh -c \"echo err 1>&2\" 2>&1); echo [$x]");
+        assert_eq!(out, "[err]\n");
+    }
+
+    #[test]
+    fn test_sequence_runs_statements_in_order() {
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, "echo a; echo b;");
+
+        assert_eq!(code, 0);
+        assert_eq!(out, "a\nb\n");
+    }
+
+    #[test]
+    fn test_last_status_is_expanded() {
+        let mut interp = Interpreter::default();
+        run_line(&mut interp, "false");
+        let (_, out) = run_line(&mut interp, "echo $?");
+        assert_eq!(out, "1\n");
+
+        let (_, out) = run_line(&mut interp, "echo $?");
+        assert_eq!(out, "0\n");
+    }
+
+    #[test]
+    fn test_single_quotes_suppress_expansion() {
+        let mut interp = Interpreter::default();
+        interp.env.set_var("QUOTED_VAR", "value");
+        let (code, out) = run_line(&mut interp, "echo '$QUOTED_VAR' \"${QUOTED_VAR}\"");
+
+        assert_eq!(code, 0);
+        assert_eq!(out, "$QUOTED_VAR value\n");
+    }
+
+    #[test]
+    fn test_classify_builtin_external_and_missing() {
+        let mut interp = Interpreter::default();
+
+        assert_eq!(interp.classify("echo"), CommandKind::Builtin);
+        match interp.classify("sh") {
+            CommandKind::External(path) => assert!(path.ends_with("sh"), "got {:?}", path),
+            kind => panic!("Expected sh to be external, got {:?}", kind),
+        }
+        assert_eq!(interp.classify("definitely_missing_cmd_12345"), CommandKind::NotFound);
+    }
+
+    #[test]
+    fn test_type_builtin_output() {
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, "type echo");
+        assert_eq!(code, 0);
+        assert_eq!(out, "echo is a shell builtin\n");
+
+        let (code, out) = run_line(&mut interp, "type definitely_missing_cmd_12345");
+        assert_eq!(code, 1);
+        assert_eq!(out, "", "The error goes to stderr");
+    }
+
+    #[test]
+    fn test_eval_line_reports_syntax_errors() {
+        let mut interp = Interpreter::default();
+
+        let err = interp.eval_line("echo 'unterminated").unwrap_err();
+        assert_eq!(err.to_string(), "syntax error: unterminated quote");
+
+        let err = interp.eval_line("echo a |").unwrap_err();
+        assert_eq!(err.to_string(), "syntax error: empty command");
+
+        let err = interp.eval_line("echo a & &").unwrap_err();
+        assert_eq!(err.to_string(), "syntax error: unexpected token `&`");
+
+        assert_eq!(interp.eval_line("   # only a comment").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_eval_line_runs_commands_and_pipelines() {
+        let mut interp = Interpreter::default();
+
+        assert_eq!(interp.eval_line("echo hi").unwrap(), 0);
+        assert_eq!(interp.eval_line("echo hi | grep hi").unwrap(), 0);
+        assert_eq!(interp.eval_line("echo hi | grep bye").unwrap(), 1);
+        assert!(interp.eval_line("echo \"hi").is_err());
+    }
+
+    #[test]
+    fn test_glob_expands_against_current_dir() {
+        let temp = stdenv::temp_dir().join(format!("glob_test_{}", std::process::id()));
+        fs::create_dir_all(&temp).unwrap();
+        for name in ["b.txt", "a.txt", "c.log", ".hidden.txt"] {
+            fs::write(temp.join(name), "").unwrap();
+        }
+
+        let mut interp = Interpreter::default();
+        interp.env.current_dir = temp.clone();
+
+        let (_, out) = run_line(&mut interp, "echo *.txt");
+        assert_eq!(out, "a.txt b.txt\n");
+
+        let (_, out) = run_line(&mut interp, "echo '*.txt' \"?.log\"");
+        assert_eq!(out, "*.txt ?.log\n", "Quoted patterns must not expand");
+
+        let (_, out) = run_line(&mut interp, "echo *.md");
+        assert_eq!(out, "*.md\n", "A pattern without matches is kept literally");
+
+        let _ = fs::remove_dir_all(&temp);
+    }
+
+    #[test]
+    fn test_glob_with_variable_expands() {
+        let temp = stdenv::temp_dir().join(format!("glob_var_test_{}", std::process::id()));
+        fs::create_dir_all(temp.join("sub")).unwrap();
+        for name in ["b.txt", "a.txt", "c.log"] {
+            fs::write(temp.join("sub").join(name), "").unwrap();
+        }
+
+        let mut interp = Interpreter::default();
+        interp.env.current_dir = temp.clone();
+
+        let (_, out) = run_line(&mut interp, "D=sub; echo $D/*.txt");
+        assert_eq!(out, "sub/a.txt sub/b.txt\n");
+
+        let (_, out) = run_line(&mut interp, "D=.; echo $D/*.md");
+        assert_eq!(out, "./*.md\n", "A pattern without matches is kept literally");
+
+        // The value of the variable is taken literally, `*` in it doesn't match anything
+        let (_, out) = run_line(&mut interp, "D='s*'; echo $D/*.txt");
+        assert_eq!(out, "s*/*.txt\n");
+
+        let _ = fs::remove_dir_all(&temp);
+    }
+
+    #[test]
+    fn test_alias_is_expanded() {
+        let mut interp = Interpreter::default();
+        run_line(&mut interp, "alias greet='echo hello'");
+
+        let (code, out) = run_line(&mut interp, "greet world");
+        assert_eq!(code, 0);
+        assert_eq!(out, "hello world\n");
+    }
+
+    #[test]
+    fn test_self_referential_alias_terminates() {
+        let mut interp = Interpreter::default();
+        run_line(&mut interp, "alias echo='echo again'");
+        let (_, out) = run_line(&mut interp, "echo hi");
+        assert_eq!(out, "again hi\n");
+
+        run_line(&mut interp, "alias ping=pong; alias pong=ping");
+        let tokens = crate::lexer::split_into_tokens("ping".to_string()).unwrap();
+        let ast = crate::parser::construct_ast(tokens).unwrap();
+        assert_eq!(interp.execute_ast(&ast).unwrap(), 127);
+    }
+
+    #[test]
+    fn test_help_lists_builtins() {
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, "help");
+
+        assert_eq!(code, 0);
+        for name in ["echo", "cd", "pwd"] {
+            assert!(
+                out.lines().any(|line| line.split_whitespace().next() == Some(name)),
+                "{} is missing from:\n{}",
+                name,
+                out
+            );
+        }
+        assert!(out.contains("Print the current working directory to standard output."));
+    }
+
+    #[test]
+    fn test_here_string_feeds_stdin() {
+        let mut interp = Interpreter::default();
+
+        let (code, out) = run_line(&mut interp, r#"wc <<< "one two three""#);
+        assert_eq!(code, 0);
+        let counts: Vec<&str> = out.split_whitespace().collect();
+        assert_eq!(counts[..2], ["1", "3"]);
+
+        interp.set_var("WORD", "hello".to_string());
+        let (_, out) = run_line(&mut interp, r#"cat <<< "$WORD there""#);
+        assert_eq!(out, "hello there\n");
+    }
+
+    #[test]
+    fn test_here_doc_feeds_stdin() {
+        let mut interp = Interpreter::default();
+
+        let (code, out) = run_line(&mut interp, "cat <<EOF\nhello\nEOF");
+        assert_eq!(code, 0);
+        assert_eq!(out, "hello\n");
+
+        let (_, out) = run_line(&mut interp, "cat <<-EOF\n\tone\n\ttwo\n\tEOF");
+        assert_eq!(out, "one\ntwo\n");
+
+        let (_, out) = run_line(&mut interp, "X=world; cat <<EOF\nhello $X $((1 + 1))\nEOF");
+        assert_eq!(out, "hello world 2\n");
+        let (_, out) = run_line(&mut interp, "cat <<EOF\n\"$(echo hi)\" \\$X\nEOF");
+        assert_eq!(out, "\"hi\" $X\n");
+        let (_, out) = run_line(&mut interp, "cat <<'EOF'\nhello $X\nEOF");
+        assert_eq!(out, "hello $X\n");
+    }
+
+    #[test]
+    fn test_redirected_input_inside_pipeline() {
+        let mut interp = Interpreter::default();
+
+        let (code, out) = run_line(&mut interp, "cat <<EOF | wc -l\none\ntwo\nEOF");
+        assert_eq!(code, 0);
+        assert_eq!(out.trim(), "2");
+
+        let (_, out) = run_line(&mut interp, r#"cat <<< "one two three" | wc -w"#);
+        assert_eq!(out.trim(), "3");
+
+        // Redirected input replaces what the previous stage writes
+        let (_, out) = run_line(&mut interp, r#"echo ignored | cat <<< "used""#);
+        assert_eq!(out, "used\n");
+
+        interp.set_pipefail(true);
+        let (code, out) = run_line(&mut interp, "cat < missing_pipeline_file | wc -l");
+        assert_eq!(code, 1);
+        assert_eq!(out.trim(), "0");
+    }
+
+    #[test]
+    fn test_run_with_io_captures_output() {
+        let mut interp = Interpreter::default();
+        let (out, buf) = MemWriter::with_handle();
+
+        let code = interp
+            .run_with_io("echo", &["hi"], Box::new(MemReader::new(Vec::new())), Box::new(out))
+            .unwrap();
+
+        assert_eq!(code, 0);
+        assert_eq!(String::from_utf8(buf.borrow().clone()).unwrap(), "hi\n");
+
+        let (out, buf) = MemWriter::with_handle();
+        let input = Box::new(MemReader::new(b"a b\n".to_vec()));
+        interp.run_with_io("wc", &["-w"], input, Box::new(out)).unwrap();
+        assert_eq!(String::from_utf8(buf.borrow().clone()).unwrap().trim(), "2");
+    }
+
+    #[test]
+    fn test_expansion_errors_can_be_matched() {
+        use super::ExpansionError;
+        use crate::lexer::WordPart;
+        use crate::parser::Word;
+
+        let mut interp = Interpreter::default();
+        let word = Word::Compound(vec![WordPart::CmdSubst("echo a |".to_string())]);
+        let err = interp.word_to_string(&word).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ExpansionError>(),
+            Some(ExpansionError::UnsupportedCmdSubst(_))
+        ));
+
+        let word = Word::Compound(vec![WordPart::CmdSubst("echo a".to_string())]);
+        interp.subst_depth = super::MAX_SUBST_DEPTH;
+        let err = interp.word_to_string(&word).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&ExpansionError::RecursionLimit));
+    }
+
+    #[test]
+    fn test_run_script() {
+        let dir = stdenv::temp_dir().join(format!("run_script_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut interp = Interpreter::default();
+        interp.env.current_dir = dir.clone();
+        let script = "\
+# count the words of a greeting
+GREETING=hello
+echo $GREETING
+COUNT=$(echo $GREETING \\
+  big world | wc -w)
+wc -l > lines.txt <<EOF
+one
+two
+EOF
+";
+        assert_eq!(interp.run_script(script).unwrap(), 0);
+        assert_eq!(interp.env.get_var("COUNT").as_deref(), Some("3"));
+        assert_eq!(fs::read_to_string(dir.join("lines.txt")).unwrap().trim(), "2");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_exit_sets_flag_instead_of_terminating() {
+        let mut interp = Interpreter::default();
+        assert_eq!(interp.eval_line("exit").unwrap(), 0);
+        assert!(interp.env.should_exit);
+
+        let mut interp = Interpreter::default();
+        assert_eq!(interp.eval_line("exit 3; AFTER=1").unwrap(), 3);
+        assert!(interp.env.should_exit);
+        assert_eq!(interp.env.get_var("AFTER"), None);
+
+        let mut interp = Interpreter::default();
+        assert_eq!(interp.eval_line("exit abc").unwrap(), 2);
+        assert!(interp.env.should_exit);
+    }
+
+    #[test]
+    fn test_run_script_stops_at_exit() {
+        let mut interp = Interpreter::default();
+        let code = interp.run_script("no_such_command_xyz\nexit 4\nAFTER=1\n").unwrap();
+
+        assert_eq!(code, 4);
+        assert_eq!(interp.env.get_var("AFTER"), None);
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        let mut interp = Interpreter::default();
+        assert_eq!(interp.eval_line("OUT=$(echo a \\\nb)").unwrap(), 0);
+        assert_eq!(interp.env.get_var("OUT").as_deref(), Some("a b"));
+
+        assert_eq!(interp.run_script("OUT=$(echo c \\\n  d \\\n  e)\n").unwrap(), 0);
+        assert_eq!(interp.env.get_var("OUT").as_deref(), Some("c d e"));
+    }
+
+    #[test]
+    fn test_backslash_in_quotes_does_not_continue_line() {
+        assert_eq!(
+            super::join_continued_lines("echo a \\\nb"),
+            ("echo a b".to_string(), false)
+        );
+        assert!(super::ends_with_line_continuation("echo a \\"));
+        assert!(!super::ends_with_line_continuation("echo a \\\\"));
+        assert!(!super::ends_with_line_continuation("echo 'a \\"));
+        assert!(!super::ends_with_line_continuation("echo \"a \\"));
+        assert_eq!(
+            super::join_continued_lines("echo 'a\\\nb' \"\\\"\" \\\nc"),
+            ("echo 'a\\\nb' \"\\\"\" c".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn test_positional_parameters() {
+        let mut interp = Interpreter::default();
+        let (_, out) = run_line(&mut interp, "echo [$0] [$1] [$#]");
+        assert_eq!(out, "[] [] [0]\n");
+
+        interp.set_positional_params("script.sh", &["first", "second"]);
+        let (_, out) = run_line(&mut interp, "echo $0 $1 \"$2\" $# ${2} ${3:-none} $10");
+        assert_eq!(out, "script.sh first second 2 second none first0\n");
+    }
+
+    #[test]
+    fn test_all_positional_parameters() {
+        use crate::lexer::WordPart;
+        use crate::parser::Word;
+
+        let mut interp = Interpreter::default();
+        let at = || WordPart::ParamSubst("@".to_string());
+        assert!(interp.expand_words(&[Word::Compound(vec![at()])]).unwrap().is_empty());
+
+        interp.set_positional_params("script.sh", &["one two", "three", "four"]);
+        let word = Word::Compound(vec![
+            WordPart::Literal("<".to_string()),
+            at(),
+            WordPart::Literal(">".to_string()),
+        ]);
+        assert_eq!(
+            interp.expand_words(&[word]).unwrap(),
+            vec!["<one two", "three", "four>"]
+        );
+
+        let (_, out) = run_line(&mut interp, "echo $@; echo \"$*\"");
+        assert_eq!(out, "one two three four\none two three four\n");
+
+        interp.set_var("IFS", ",".to_string());
+        let (_, out) = run_line(&mut interp, "echo \"$*\" ${*}");
+        // Unquoted, the joined parameters are split on IFS again
+        assert_eq!(out, "one two,three,four one two three four\n");
+    }
+
+    #[test]
+    fn test_unquoted_expansion_is_split_into_fields() {
+        let mut interp = Interpreter::default();
+        interp.set_var("X", " a  b\tc ".to_string());
+        interp.set_var("EMPTY", String::new());
+
+        let tokens = crate::lexer::split_into_tokens(
+            r#"echo $X "$X" x${X}y "${X}" $EMPTY "$EMPTY""#.to_string(),
+        )
+        .unwrap();
+        let crate::parser::AstNode::Command { argv, .. } =
+            crate::parser::construct_ast(tokens).unwrap()
+        else {
+            panic!("expected a command");
+        };
+        assert_eq!(
+            interp.expand_words(&argv).unwrap(),
+            vec!["echo", "a", "b", "c", " a  b\tc ", "xa", "b", "cy", " a  b\tc ", ""]
+        );
+
+        interp.set_var("IFS", ",".to_string());
+        interp.set_var("X", "a,,b c".to_string());
+        let (_, out) = run_line(&mut interp, "echo [$X] \"[$X]\"");
+        assert_eq!(out, "[a  b c] [a,,b c]\n");
+    }
+
+    #[test]
+    fn test_split_expansion_gives_separate_files() {
+        let dir = stdenv::temp_dir().join(format!("ifs_split_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("f1"), "one\n").unwrap();
+        fs::write(dir.join("f2"), "two\n").unwrap();
+
+        let mut interp = Interpreter::default();
+        let files = format!("{} {}", dir.join("f1").display(), dir.join("f2").display());
+        interp.set_var("FILES", files);
+        let (code, out) = run_line(&mut interp, "cat $FILES");
+        assert_eq!(code, 0);
+        assert_eq!(out, "one\ntwo\n");
+
+        let (code, _) = run_line(&mut interp, "cat \"$FILES\"");
+        assert_eq!(code, 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_custom_prompt() {
+        let mut interp = Interpreter::default();
+        assert_eq!(interp.prompt(), "🐒$ ");
+
+        interp.set_prompt("my-shell> ");
+        assert_eq!(interp.prompt(), "my-shell> ");
+    }
+
+    #[test]
+    fn test_missing_command_exits_with_127() {
+        let mut interp = Interpreter::default();
+        assert_eq!(interp.run("definitely_not_a_cmd", &[]).unwrap(), 127);
+
+        let (code, out) = run_line(&mut interp, "definitely_not_a_cmd; echo $?");
+        assert_eq!(code, 0);
+        assert_eq!(out, "127\n");
+        let (code, _) = run_line(&mut interp, "echo hi | definitely_not_a_cmd");
+        assert_eq!(code, 127);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_not_executable_file_exits_with_126() {
+        let dir = std::env::temp_dir().join(format!("not_executable_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("script.sh");
+        fs::write(&script, "echo hi\n").unwrap();
+
+        let mut interp = Interpreter::default();
+        assert_eq!(interp.run(script.to_str().unwrap(), &[]).unwrap(), 126);
+        assert_eq!(interp.run(dir.to_str().unwrap(), &[]).unwrap(), 126);
+        let missing = dir.join("missing");
+        assert_eq!(interp.run(missing.to_str().unwrap(), &[]).unwrap(), 127);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_exec_format_error_goes_to_the_command_stderr() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("exec_format_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let program = dir.join("program");
+        fs::write(&program, b"\x00\x01 not a program\n").unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+        let err_file = dir.join("err.txt");
+
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, &format!("\"{}\" 2>&1", program.display()));
+        assert_eq!(code, 126);
+        assert!(out.contains("Exec format error"), "{:?}", out);
+
+        let line = format!("\"{}\" 2> \"{}\"", program.display(), err_file.display());
+        let (code, out) = run_line(&mut interp, &line);
+        assert_eq!((code, out.as_str()), (126, ""));
+        assert!(fs::read_to_string(&err_file).unwrap().contains("Exec format error"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipeline_stops_started_stages_on_error() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = stdenv::temp_dir().join(format!("pipeline_error_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["sh", "sleep"] {
+            let path = crate::external::find_command_path(
+                std::ffi::OsStr::new(&stdenv::var("PATH").unwrap()),
+                std::path::Path::new(name),
+            )
+            .unwrap();
+            std::os::unix::fs::symlink(path, dir.join(name)).unwrap();
+        }
+        let program = dir.join("program");
+        fs::write(&program, b"\x00\x01 not a program\n").unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+        let pid_file = dir.join("pid");
+
+        // The builtin `cat` saves the pid of the sleeping first stage before the last one
+        // fails to start
+        let mut interp = Interpreter::default();
+        interp.set_var("PATH", dir.display().to_string());
+        let line = format!(
+            "sh -c 'echo $$; exec sleep 30 > /dev/null' | cat > \"{}\" | \"{}\"",
+            pid_file.display(),
+            program.display()
+        );
+        let result = interp.execute_statements(
+            &crate::parser::construct_ast(crate::lexer::split_into_tokens(line).unwrap()).unwrap(),
+            &|| Box::new(MemWriter::new()),
+        );
+        assert!(result.is_err());
+
+        let pid: i32 = fs::read_to_string(&pid_file).unwrap().trim().parse().unwrap();
+        // The first stage was killed and waited for, so its pid is gone
+        assert_eq!(unsafe { libc::kill(pid, 0) }, -1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_background_job_is_listed_until_waited_for() {
+        let mut interp = Interpreter::default();
+        let (errors, errors_rc) = MemWriter::with_handle();
+        interp.set_stderr(errors);
+        let (code, out) = run_line(&mut interp, "sleep 1 &");
+        assert_eq!(code, 0);
+        assert_eq!(out, "", "The job number goes to stderr");
+        let report = String::from_utf8(errors_rc.borrow().clone()).unwrap();
+        assert!(report.starts_with("[1] "), "{:?}", report);
+
+        let (_, out) = run_line(&mut interp, "jobs");
+        assert_eq!(out, "[1] Running sleep 1\n");
+
+        let (code, _) = run_line(&mut interp, "wait");
+        assert_eq!(code, 0);
+        let (_, out) = run_line(&mut interp, "jobs");
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_finished_background_job_is_reported_once() {
+        let mut interp = Interpreter::default();
+        run_line(&mut interp, "sh -c 'exit 3' & echo started");
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        let (_, out) = run_line(&mut interp, "jobs");
+        assert_eq!(out, "[1] Exit 3 sh -c exit 3\n");
+        let (_, out) = run_line(&mut interp, "jobs");
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    fn test_background_builtin_is_refused() {
+        let mut interp = Interpreter::default();
+        let (errors, errors_rc) = MemWriter::with_handle();
+        interp.set_stderr(errors);
+        let dir = interp.env.current_dir.clone();
+
+        let (code, _) = run_line(&mut interp, "cd .. &");
+        assert_eq!(code, 1);
+        assert_eq!(interp.env.current_dir, dir, "cd must not run");
+
+        // There is an `echo` program too, but without `&` the builtin runs
+        let (code, out) = run_line(&mut interp, "echo hi &");
+        assert_eq!(code, 1);
+        assert_eq!(out, "");
+
+        assert_eq!(
+            String::from_utf8(errors_rc.borrow().clone()).unwrap(),
+            "cd: only programs can run in the background\n\
+             echo: only programs can run in the background\n"
+        );
+        let (_, out) = run_line(&mut interp, "jobs");
+        assert_eq!(out, "");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_background_pipeline_is_one_job() {
+        let out_file = stdenv::temp_dir().join(format!("pipeline_job_{}", std::process::id()));
+        let mut interp = Interpreter::default();
+        let (errors, errors_rc) = MemWriter::with_handle();
+        interp.set_stderr(errors);
+
+        let line = format!(
+            "sh -c \"sleep 0.2; echo b a\" | tr \" \" \"\\n\" | sort > \"{}\" &",
+            out_file.display()
+        );
+        let (code, out) = run_line(&mut interp, &line);
+        assert_eq!(code, 0);
+        assert_eq!(out, "");
+        let report = String::from_utf8(errors_rc.borrow().clone()).unwrap();
+        assert!(report.starts_with("[1] "), "{:?}", report);
+
+        let (_, out) = run_line(&mut interp, "jobs");
+        assert!(out.starts_with("[1] Running sh -c sleep 0.2; echo b a | tr"), "{:?}", out);
+
+        run_line(&mut interp, "wait");
+        assert_eq!(fs::read_to_string(&out_file).unwrap(), "a\nb\n");
+        let (_, out) = run_line(&mut interp, "jobs");
+        assert_eq!(out, "");
+
+        // Builtins still can't be part of a job
+        let (code, _) = run_line(&mut interp, "sh -c \"echo x\" | cd .. &");
+        assert_eq!(code, 1);
+        let (_, out) = run_line(&mut interp, "jobs");
+        assert_eq!(out, "");
+
+        let _ = fs::remove_file(out_file);
+    }
+
+    #[test]
+    fn test_cd_persists_across_statements_but_not_from_pipelines() {
+        let _lock = lock_current_dir();
+        let base = std::env::temp_dir().join(format!("cd_sequence_{}", std::process::id()));
+        fs::create_dir_all(base.join("inner")).unwrap();
+        let orig = std::env::current_dir().unwrap();
+        let mut interp = Interpreter::default();
+        interp.env.current_dir = base.clone();
+
+        let (_, out) = run_line(&mut interp, "cd inner; pwd");
+        assert_eq!(out, format!("{}\n", base.join("inner").display()));
+        assert_eq!(interp.env.current_dir, base.join("inner"));
+
+        fs::write(base.join("inner").join("a.txt"), "a\n").unwrap();
+        let (_, out) = run_line(&mut interp, "cd .. | cat; pwd; cat a.txt");
+        assert_eq!(out, format!("{}\na\n", base.join("inner").display()));
+        assert_eq!(std::env::current_dir().unwrap(), base.join("inner"));
+
+        // `cd` also moves the test process
+        std::env::set_current_dir(orig).unwrap();
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_command_prefix_skips_builtins() {
+        use std::os::unix::fs::PermissionsExt;
+        let bin = std::env::temp_dir().join(format!("command_prefix_{}", std::process::id()));
+        fs::create_dir_all(&bin).unwrap();
+        let script = bin.join("echo");
+        fs::write(&script, "#!/bin/sh\necho external \"$@\"\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut interp = Interpreter::default();
+        interp.env.set_var("PATH", bin.to_string_lossy());
+        let (_, out) = run_line(&mut interp, "command echo hi");
+        assert_eq!(out, "external hi\n");
+        let (_, out) = run_line(&mut interp, "echo hi");
+        assert_eq!(out, "hi\n");
+
+        let (code, _) = run_line(&mut interp, "command dirs");
+        assert_eq!(code, 127, "dirs is only a builtin");
+        assert_eq!(interp.classify("command"), CommandKind::Builtin);
+
+        let _ = fs::remove_dir_all(&bin);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipeline_passes_non_utf8_bytes_to_builtins() {
+        // Pipeline stages prefer programs from PATH, so PATH only holds the byte emitter
+        // and `wc` and `grep` have to be the builtins
+        let dir = stdenv::temp_dir().join(format!("non_utf8_path_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let printf = crate::external::find_command_path(
+            std::ffi::OsStr::new(&stdenv::var("PATH").unwrap()),
+            std::path::Path::new("printf"),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(printf, dir.join("printf")).unwrap();
+
+        let mut interp = Interpreter::default();
+        interp.set_var("PATH", dir.display().to_string());
+        let (code, out) = run_line(&mut interp, r"command printf 'a\377\376\n' | wc -c");
+        assert_eq!(code, 0);
+        assert_eq!(out, "4\n");
+
+        let (_, out) = run_line(&mut interp, r"command printf 'a\377\nb\n' | grep -c a");
+        assert_eq!(out, "1\n");
+
+        // GNU grep would color the match as `\x1b[01;31m\x1b[K`
+        let (_, out) = run_line(&mut interp, "echo x | grep --color always x");
+        assert!(out.starts_with("\x1b[1;31m"), "{:?}", out);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_assignment_before_command_is_temporary() {
+        let mut interp = Interpreter::default();
+        interp.env.remove_var("TEMP_ASSIGNED");
+
+        assert_eq!(run_line(&mut interp, "TEMP_ASSIGNED=1 echo"), (0, "\n".to_string()));
+        assert_eq!(run_line(&mut interp, "echo [$TEMP_ASSIGNED]"), (0, "[]\n".to_string()));
+
+        assert_eq!(run_line(&mut interp, "TEMP_ASSIGNED=2").0, 0);
+        assert_eq!(run_line(&mut interp, "TEMP_ASSIGNED=3 echo").0, 0);
+        assert_eq!(run_line(&mut interp, "echo $TEMP_ASSIGNED"), (0, "2\n".to_string()));
+    }
+
+    #[test]
+    fn test_assignment_before_command_is_set_after_expansion() {
+        let mut interp = Interpreter::default();
+        interp.env.remove_var("X");
+
+        assert_eq!(run_line(&mut interp, "X=1 echo $X"), (0, "\n".to_string()));
+        let (_, out) = run_line(&mut interp, "X=1 env");
+        assert!(out.lines().any(|line| line == "X=1"), "The command still sees X");
+        assert_eq!(interp.env.get_var("X"), None);
+
+        // Words that expand to nothing leave no command, so the assignment stays
+        assert_eq!(run_line(&mut interp, "X=2 $UNSET_COMMAND"), (0, String::new()));
+        assert_eq!(interp.env.get_var("X"), Some("2".to_string()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipeline_stage_assignments_reach_the_program() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("stage_env_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("stage-only-cmd");
+        fs::write(&script, "#!/bin/sh\necho found\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let mut interp = Interpreter::default();
+        interp.env.set_var("STAGE_DIR", dir.to_string_lossy().into_owned());
+        interp.env.remove_var("STAGE_VAR");
+
+        let (_, out) = run_line(&mut interp, "STAGE_VAR=bar env | grep STAGE_VAR");
+        assert_eq!(out, "STAGE_VAR=bar\n");
+        assert_eq!(run_line(&mut interp, "STAGE_VAR=1 echo [$STAGE_VAR] | cat").1, "[]\n");
+        assert_eq!(run_line(&mut interp, "PATH=$STAGE_DIR stage-only-cmd | cat").1, "found\n");
+        assert_eq!(interp.env.get_var("STAGE_VAR"), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_command_expanding_to_nothing() {
+        let dir = std::env::temp_dir().join(format!("empty_command_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut interp = Interpreter::default();
+        interp.env.current_dir = dir.clone();
+        interp.env.remove_var("UNSET_CMD");
+        interp.env.set_var("EMPTY_CMD", "");
+
+        assert_eq!(run_line(&mut interp, "$UNSET_CMD"), (0, String::new()));
+        let (code, _) = run_line(&mut interp, "\"$EMPTY_CMD\" arg 2> err.txt");
+        assert_eq!(code, 127);
+        let err = fs::read_to_string(dir.join("err.txt")).unwrap();
+        assert_eq!(err, "\"${EMPTY_CMD}\": command not found\n");
+        assert_eq!(run_line(&mut interp, "echo a | \"$EMPTY_CMD\"").0, 127);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_redirect_to_quoted_file_name_with_spaces() {
+        let dir = std::env::temp_dir().join(format!("spaced_redirect_{}", std::process::id()));
+        fs::create_dir_all(dir.join("logs")).unwrap();
+        let mut interp = Interpreter::default();
+        interp.env.current_dir = dir.clone();
+
+        assert_eq!(run_line(&mut interp, "echo hi > \"out file.txt\"").0, 0);
+        assert_eq!(fs::read_to_string(dir.join("out file.txt")).unwrap(), "hi\n");
+        assert_eq!(run_line(&mut interp, "echo there >> 'logs/my log.txt'").0, 0);
+        assert_eq!(run_line(&mut interp, "echo again >> logs/'my log.txt'").0, 0);
+        let log = fs::read_to_string(dir.join("logs").join("my log.txt")).unwrap();
+        assert_eq!(log, "there\nagain\n");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_arithmetic_expansion() {
+        use super::ExpansionError;
+        use crate::lexer::WordPart;
+        use crate::parser::Word;
+
+        let mut interp = Interpreter::default();
+        interp.env.set_var("X", "4");
+
+        assert_eq!(run_line(&mut interp, "echo $((1 + 2 * 3))"), (0, "7\n".to_string()));
+        assert_eq!(run_line(&mut interp, "echo $(((1 + 2) * 3))"), (0, "9\n".to_string()));
+        assert_eq!(run_line(&mut interp, "echo $((X + 1)) $(($X * 2))"), (0, "5 8\n".to_string()));
+        let (_, out) = run_line(&mut interp, "Y=$((X - 10)); echo \"[$Y]\"");
+        assert_eq!(out, "[-6]\n");
+        assert_eq!(run_line(&mut interp, "echo $(( $(echo 6) / 4 ))"), (0, "1\n".to_string()));
+
+        let word = Word::Compound(vec![WordPart::ArithSubst("X / (X - 4)".to_string())]);
+        let err = interp.word_to_string(&word).unwrap_err();
+        assert_eq!(
+            err.downcast_ref(),
+            Some(&ExpansionError::BadArithmetic("division by zero".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_brace_expansion() {
+        let mut interp = Interpreter::default();
+        interp.env.set_var("X", "x");
+
+        let (_, out) = run_line(&mut interp, "echo {a,b}{1,2}");
+        assert_eq!(out, "a1 a2 b1 b2\n");
+        assert_eq!(run_line(&mut interp, "echo n{1..3}").1, "n1 n2 n3\n");
+        assert_eq!(run_line(&mut interp, "echo {$X,y}.txt").1, "x.txt y.txt\n");
+        let (_, out) = run_line(&mut interp, "echo \"{a,b}\" '{1..2}' \\{c,d}");
+        assert_eq!(out, "{a,b} {1..2} {c,d}\n");
+        assert_eq!(run_line(&mut interp, "echo {} {a}").1, "{} {a}\n");
+    }
+
+    #[test]
+    fn test_brace_expansion_happens_before_globbing() {
+        let dir = std::env::temp_dir().join(format!("brace_glob_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["a1.txt", "a2.txt", "b1.log"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let mut interp = Interpreter::default();
+        interp.env.current_dir = dir.clone();
+
+        let (_, out) = run_line(&mut interp, "echo {a,b}*.{txt,log}");
+        assert_eq!(out, "a1.txt a2.txt a*.log b*.txt b1.log\n");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pipeline_output_without_trailing_newline() {
+        // Without PATH every stage is a builtin writing to memory
+        let mut interp = Interpreter::default();
+        interp.env.remove_var("PATH");
+        assert_eq!(interp.resolve_external("cat"), None);
+        assert_eq!(run_line(&mut interp, "echo -n foo | cat"), (0, "foo".to_string()));
+        assert_eq!(run_line(&mut interp, "echo -n foo | cat | cat"), (0, "foo".to_string()));
+        assert_eq!(run_line(&mut interp, "echo foo | cat"), (0, "foo\n".to_string()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_external_pipeline_output_without_trailing_newline() {
+        let mut interp = Interpreter::default();
+        let (code, out) = run_line(&mut interp, "echo -n foo | sh -c cat");
+        assert_eq!((code, out.as_bytes()), (0, &b"foo"[..]));
+        let (code, out) = run_line(&mut interp, "printf foo | sh -c cat | cat");
+        assert_eq!((code, out.as_bytes()), (0, &b"foo"[..]));
+    }
+
+    #[test]
+    fn test_history_lists_evaluated_lines() {
+        let mut interp = Interpreter::default();
+        interp.eval_line("X=1").unwrap();
+        interp.eval_line("true").unwrap();
+        interp.eval_line("   ").unwrap();
+        interp.run_script("Y=2\n").unwrap();
+        assert_eq!(interp.history(), ["X=1", "true"]);
+
+        let (code, out) = run_line(&mut interp, "history");
+        assert_eq!(code, 0);
+        assert_eq!(out, "    1  X=1\n    2  true\n");
+
+        let (_, out) = run_line(&mut interp, "history 1");
+        assert_eq!(out, "    2  true\n");
+        let (_, out) = run_line(&mut interp, "history 5");
+        assert_eq!(out.lines().count(), 2);
+    }
+}
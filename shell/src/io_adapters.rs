@@ -26,14 +26,28 @@ impl Read for MemReader {
 }
 
 impl crate::command::Stdin for MemReader {
-    /// For in-memory reader return Stdio::null() because this adapter is used
-    /// only for builtin commands executed in-process.
-    fn stdio(self: Box<Self>) -> Stdio {
-        Stdio::null()
+    /// Feed the unread bytes to the process through a pipe, written from a separate
+    /// thread so that a child that doesn't read everything can't block the shell.
+    fn stdio(mut self: Box<Self>) -> Stdio {
+        let mut rest = Vec::new();
+        if self.cursor.read_to_end(&mut rest).is_err() || rest.is_empty() {
+            return Stdio::null();
+        }
+        let Ok((reader, mut writer)) = std::io::pipe() else {
+            return Stdio::null();
+        };
+        std::thread::spawn(move || {
+            // The child may exit without reading everything
+            let _ = writer.write_all(&rest);
+        });
+        reader.into()
     }
 }
 
 /// Memory-backed writer for capturing stdout from builtins.
+///
+/// Clones share the same buffer.
+#[derive(Clone)]
 pub struct MemWriter {
     buf: Rc<RefCell<Vec<u8>>>,
 }
@@ -59,6 +73,12 @@ impl MemWriter {
     }
 }
 
+impl Default for MemWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Write for MemWriter {
     fn write(&mut self, data: &[u8]) -> IoResult<usize> {
         self.buf.borrow_mut().extend_from_slice(data);
@@ -74,4 +94,12 @@ impl crate::command::Stdout for MemWriter {
     fn stdio(self: Box<Self>) -> Stdio {
         Stdio::null()
     }
+
+    fn try_clone(&self) -> Option<Box<dyn crate::command::Stdout>> {
+        Some(Box::new(self.clone()))
+    }
+
+    fn is_in_memory(&self) -> bool {
+        true
+    }
 }
@@ -7,8 +7,19 @@ pub enum WordPart {
     Literal(String),
     /// Command substitution in the format `$(...)`. Contains the text inside the parentheses.
     CmdSubst(String),
+    /// Arithmetic expansion in the format `$((...))`. Contains the expression inside.
+    ArithSubst(String),
     /// Parameter substitution in the format `${...}`. Contains the text inside the curly braces.
+    /// Unquoted, so the value is split into fields on `IFS`.
     ParamSubst(String),
+    /// Parameter substitution inside double quotes, whose value always stays one word.
+    QuotedParamSubst(String),
+    /// Unquoted literal text containing glob metacharacters (`*`, `?` or `[`) or braces.
+    /// Expands to its brace alternatives and the matching file names.
+    Glob(String),
+    /// An unquoted `~` at the start of a word, followed by `/` or the end of the word.
+    /// Expands to the home directory.
+    Tilde,
 }
 
 /// Represents a token resulting from lexical analysis.
@@ -18,18 +29,73 @@ pub enum Token {
     Word(Vec<WordPart>),
     /// The pipe operator, `|`.
     PipeOp,
+    /// The "and" list operator, `&&`.
+    AndIf,
+    /// The "or" list operator, `||`.
+    OrIf,
     /// The equality symbol, `=`.
     Equal,
-    /// The slash symbol (path separator), `/`.
-    Slash,
     /// Input redirection symbol, `<`.
     RedirectLeft,
     /// Output redirection symbol, `>`.
     RedirectRight,
+    /// Appending output redirection symbol, `>>`.
+    RedirectAppend,
+    /// Stderr redirection symbol, `2>`.
+    RedirectErr,
+    /// Appending stderr redirection symbol, `2>>`.
+    RedirectErrAppend,
+    /// Here-string symbol, `<<<`.
+    HereString,
+    /// A here-document (`<<DELIM` or `<<-DELIM`) with the body lines that followed it.
+    /// The body is a single literal part when the delimiter was quoted.
+    HereDoc(Vec<WordPart>),
+    /// The ampersand symbol, `&` (as in `2>&1`).
+    Ampersand,
+    /// The command separator, `;`.
+    Semicolon,
+}
+
+/// Writes the word part back in shell syntax, for error messages.
+impl std::fmt::Display for WordPart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WordPart::Literal(text) => write!(f, "{}", text),
+            WordPart::CmdSubst(command) => write!(f, "$({})", command),
+            WordPart::ArithSubst(expression) => write!(f, "$(({}))", expression),
+            WordPart::ParamSubst(content) => write!(f, "${{{}}}", content),
+            WordPart::QuotedParamSubst(content) => write!(f, "\"${{{}}}\"", content),
+            WordPart::Glob(pattern) => write!(f, "{}", pattern),
+            WordPart::Tilde => write!(f, "~"),
+        }
+    }
+}
+
+/// Writes the token back in shell syntax, for error messages.
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(parts) => parts.iter().try_for_each(|part| write!(f, "{}", part)),
+            Token::PipeOp => write!(f, "|"),
+            Token::AndIf => write!(f, "&&"),
+            Token::OrIf => write!(f, "||"),
+            Token::Equal => write!(f, "="),
+            Token::RedirectLeft => write!(f, "<"),
+            Token::RedirectRight => write!(f, ">"),
+            Token::RedirectAppend => write!(f, ">>"),
+            Token::RedirectErr => write!(f, "2>"),
+            Token::RedirectErrAppend => write!(f, "2>>"),
+            Token::HereString => write!(f, "<<<"),
+            Token::HereDoc(_) => write!(f, "<<"),
+            Token::Ampersand => write!(f, "&"),
+            Token::Semicolon => write!(f, ";"),
+        }
+    }
 }
 
 /// Errors that can occur during the lexical analysis process.
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum LexingError {
     /// A closing quote (single or double) was not found.
     UnfinishedQuote,
@@ -37,14 +103,37 @@ pub enum LexingError {
     UnfinishedCmdSubst,
     /// A closing brace for parameter substitution `${...}` was not found.
     UnfinishedParamSubst,
+    /// `<<` was not followed by a delimiter word.
+    MissingHereDocDelimiter,
+    /// No line equal to the here-document delimiter was found.
+    UnfinishedHereDoc(String),
 }
 
+impl std::fmt::Display for LexingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexingError::UnfinishedQuote => write!(f, "unterminated quote"),
+            LexingError::UnfinishedCmdSubst => write!(f, "unterminated command substitution `$(`"),
+            LexingError::UnfinishedParamSubst => {
+                write!(f, "unterminated parameter substitution `${{`")
+            }
+            LexingError::MissingHereDocDelimiter => write!(f, "expected a here-document delimiter"),
+            LexingError::UnfinishedHereDoc(delimiter) => {
+                write!(f, "here-document is not terminated by `{}`", delimiter)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LexingError {}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum LexingState {
     Start,
     ReadingWord,
     ReadingSingleQuote,
     ReadingDoubleQuote,
+    ReadingHereDoc,
     ReadingCmdSubst(usize),   // nesting depth
     ReadingParamSubst(usize), // nesting depth
 }
@@ -88,7 +177,9 @@ impl LexingFSM {
                 LexingState::Start => self.handle_start(ch, &mut out)?,
                 LexingState::ReadingWord => self.handle_word(ch, &mut out)?,
                 LexingState::ReadingSingleQuote => self.handle_single_quote(ch)?,
-                LexingState::ReadingDoubleQuote => self.handle_double_quote(ch)?,
+                LexingState::ReadingDoubleQuote | LexingState::ReadingHereDoc => {
+                    self.handle_double_quote(ch)?
+                }
                 LexingState::ReadingCmdSubst(depth) => self.handle_cmdsubst(ch, depth)?,
                 LexingState::ReadingParamSubst(depth) => self.handle_paramsubst(ch, depth)?,
             }
@@ -123,19 +214,130 @@ impl LexingFSM {
         self.input.get(self.pos).copied()
     }
 
+    /// The token for a `<` that was just read: `<<<` starts a here-string
+    /// and `<<` a here-document.
+    fn input_redirect_token(&mut self) -> Result<Token, LexingError> {
+        if self.input[self.pos..].starts_with(&['<', '<']) {
+            self.pos += 2;
+            Ok(Token::HereString)
+        } else if self.peek_char() == Some('<') {
+            self.read_char();
+            let strip_tabs = self.peek_char() == Some('-');
+            if strip_tabs {
+                self.read_char();
+            }
+            self.read_here_doc(strip_tabs)
+        } else {
+            Ok(Token::RedirectLeft)
+        }
+    }
+
+    /// The token for the operator character `ch` that was just read, taking the characters
+    /// that follow when they make up a longer operator like `>>` or `&&`.
+    fn operator_token(&mut self, ch: char) -> Result<Token, LexingError> {
+        let mut doubled = |single: Token, double: Token| {
+            if self.peek_char() == Some(ch) {
+                self.read_char();
+                double
+            } else {
+                single
+            }
+        };
+        Ok(match ch {
+            '|' => doubled(Token::PipeOp, Token::OrIf),
+            '&' => doubled(Token::Ampersand, Token::AndIf),
+            '>' => doubled(Token::RedirectRight, Token::RedirectAppend),
+            '<' => self.input_redirect_token()?,
+            '=' => Token::Equal,
+            ';' => Token::Semicolon,
+            _ => unreachable!("{:?} is not an operator", ch),
+        })
+    }
+
+    /// Read the delimiter after `<<` and take the body (the lines after the current one,
+    /// up to a line equal to the delimiter) out of the input. With `<<-` leading tabs
+    /// are stripped from the body and delimiter lines.
+    ///
+    /// Quotes around the delimiter are dropped and keep the body as is. Otherwise the body
+    /// is expanded like text in double quotes.
+    fn read_here_doc(&mut self, strip_tabs: bool) -> Result<Token, LexingError> {
+        while matches!(self.peek_char(), Some(' ' | '\t')) {
+            self.read_char();
+        }
+        let mut delimiter = String::new();
+        let mut quoted = false;
+        while let Some(c) = self.peek_char() {
+            if c.is_whitespace() || matches!(c, '|' | ';' | '&' | '<' | '>') {
+                break;
+            }
+            self.read_char();
+            if c == '\'' || c == '"' {
+                quoted = true;
+            } else {
+                delimiter.push(c);
+            }
+        }
+        if delimiter.is_empty() {
+            return Err(LexingError::MissingHereDocDelimiter);
+        }
+
+        let unfinished = || LexingError::UnfinishedHereDoc(delimiter.clone());
+        let line_end = self.input[self.pos..]
+            .iter()
+            .position(|&c| c == '\n')
+            .map(|offset| self.pos + offset)
+            .ok_or_else(unfinished)?;
+        let mut body = String::new();
+        let mut start = line_end + 1;
+        loop {
+            let end = self.input[start..]
+                .iter()
+                .position(|&c| c == '\n')
+                .map_or(self.input.len(), |offset| start + offset);
+            let line: String = self.input[start..end].iter().collect();
+            let line = if strip_tabs { line.trim_start_matches('\t') } else { &line };
+            if line == delimiter {
+                // The rest of the current line is lexed as usual
+                self.input.drain(line_end..end);
+                if quoted {
+                    return Ok(Token::HereDoc(vec![WordPart::Literal(body)]));
+                }
+                return Ok(Token::HereDoc(here_doc_parts(body)?));
+            }
+            body.push_str(line);
+            body.push('\n');
+            if end == self.input.len() {
+                return Err(unfinished());
+            }
+            start = end + 1;
+        }
+    }
+
     fn handle_start(&mut self, ch: char, out: &mut Vec<Token>) -> Result<(), LexingError> {
         match ch {
             ' ' | '\t' => {}
-            '|' => out.push(Token::PipeOp),
-            '=' => out.push(Token::Equal),
-            // NOTE: '.' is removed here to treat it as a word character (part of a file name)
-            '/' => out.push(Token::Slash),
-            '<' => out.push(Token::RedirectLeft),
-            '>' => out.push(Token::RedirectRight),
+            // NOTE: '.' and '/' are not operators, to treat them as word characters (in a path)
+            '|' | '=' | '<' | '>' | '&' | ';' => out.push(self.operator_token(ch)?),
+            // A `#` starting a word comments out the rest of the line
+            '#' => self.pos = self.input.len(),
+            '\\' => {
+                self.push_escaped()?;
+                self.state = LexingState::ReadingWord;
+            }
             '\'' => self.state = LexingState::ReadingSingleQuote,
             '"' => self.state = LexingState::ReadingDoubleQuote,
+            // `~user` is not supported, so only a lone `~` or `~/...` is a tilde prefix
+            '~' if matches!(self.peek_char(), None | Some(' ' | '\t' | '/' | '|' | '<' | '>')) => {
+                self.current_word.push(WordPart::Tilde);
+                self.state = LexingState::ReadingWord;
+            }
             '$' => {
-                if self.peek_char() == Some('(') {
+                if self.input[self.pos..].starts_with(&['(', '(']) {
+                    self.pos += 2;
+                    let part = self.collect_arithsubst()?;
+                    self.current_word.push(part);
+                    self.state = LexingState::ReadingWord;
+                } else if self.peek_char() == Some('(') {
                     self.read_char();
                     self.state = LexingState::ReadingCmdSubst(1);
                 } else if self.peek_char() == Some('{') {
@@ -167,30 +369,44 @@ impl LexingFSM {
                 out.push(Token::Word(std::mem::take(&mut self.current_word)));
                 self.state = LexingState::Start;
             }
-            // NOTE: '.' is removed from this list to treat it as a word character
-            '|' | '=' | '/' | '<' | '>' => {
+            // A word consisting of just an unquoted `2` makes `2>` a stderr redirect
+            '>' if self.buffer == "2" && self.current_word.is_empty() => {
+                self.buffer.clear();
+                out.push(match self.operator_token(ch)? {
+                    Token::RedirectAppend => Token::RedirectErrAppend,
+                    _ => Token::RedirectErr,
+                });
+                self.state = LexingState::Start;
+            }
+            // NOTE: '.' and '/' are removed from this list to treat them as word characters
+            '|' | '=' | '<' | '>' | '&' | ';' => {
                 // Finalize the current word
                 self.finalize_current_word_part()?;
                 if !self.current_word.is_empty() {
                     out.push(Token::Word(std::mem::take(&mut self.current_word)));
                 }
                 // Add the symbol token
-                let token = match ch {
-                    '|' => Token::PipeOp,
-                    '=' => Token::Equal,
-                    // '.' is handled as part of 'c =>' now
-                    '/' => Token::Slash,
-                    '<' => Token::RedirectLeft,
-                    '>' => Token::RedirectRight,
-                    _ => unreachable!(),
-                };
-                out.push(token);
+                out.push(self.operator_token(ch)?);
                 self.state = LexingState::Start;
             }
-            '"' => self.state = LexingState::ReadingDoubleQuote,
-            '\'' => self.state = LexingState::ReadingSingleQuote,
+            '"' => {
+                // Unquoted text before the quote may still be a glob pattern
+                self.finalize_current_word_part()?;
+                self.state = LexingState::ReadingDoubleQuote;
+            }
+            '\'' => {
+                // Keep a pending `$name` from swallowing the quoted text
+                self.finalize_current_word_part()?;
+                self.state = LexingState::ReadingSingleQuote;
+            }
+            '\\' => self.push_escaped()?,
             '$' => {
-                if self.peek_char() == Some('(') {
+                if self.input[self.pos..].starts_with(&['(', '(']) {
+                    self.pos += 2;
+                    self.finalize_current_word_part()?;
+                    let part = self.collect_arithsubst()?;
+                    self.current_word.push(part);
+                } else if self.peek_char() == Some('(') {
                     self.read_char();
                     self.finalize_current_word_part()?;
                     let nested = self.collect_cmdsubst(1)?;
@@ -210,7 +426,7 @@ impl LexingFSM {
                 // Check if we're starting a simple parameter substitution
                 if !self.buffer.is_empty() && self.buffer == "$" {
                     // We have a $ followed by a valid parameter name character
-                    if c.is_alphabetic() || c.is_digit(10) || c == '_' {
+                    if c.is_alphabetic() || c.is_ascii_digit() || c == '_' {
                         // Continue collecting the parameter name
                         self.buffer.push(c);
                     } else {
@@ -228,8 +444,9 @@ impl LexingFSM {
     fn handle_single_quote(&mut self, ch: char) -> Result<(), LexingError> {
         match ch {
             '\'' => {
-                self.current_word
-                    .push(WordPart::Literal(std::mem::take(&mut self.buffer)));
+                // Quoted text is always literal, it must never go through `finalize_current_word_part`
+                let text = std::mem::take(&mut self.buffer);
+                self.push_literal(text);
                 self.state = LexingState::ReadingWord
             }
             c => self.buffer.push(c),
@@ -238,11 +455,22 @@ impl LexingFSM {
     }
 
     fn handle_double_quote(&mut self, ch: char) -> Result<(), LexingError> {
+        let in_here_doc = self.state == LexingState::ReadingHereDoc;
         match ch {
-            '"' => {
+            '"' if !in_here_doc => {
                 self.finalize_current_word_part()?;
                 self.state = LexingState::ReadingWord;
             }
+            // Inside double quotes a backslash only escapes the characters special there.
+            // In a here-document a double quote is not one of them.
+            '\\' if matches!(self.peek_char(), Some('$' | '`' | '\\')) => self.push_escaped()?,
+            '\\' if self.peek_char() == Some('"') && !in_here_doc => self.push_escaped()?,
+            '$' if self.input[self.pos..].starts_with(&['(', '(']) => {
+                self.pos += 2;
+                self.finalize_current_word_part()?;
+                let part = self.collect_arithsubst()?;
+                self.current_word.push(part);
+            }
             '$' if self.peek_char() == Some('(') => {
                 self.read_char();
                 self.finalize_current_word_part()?;
@@ -253,7 +481,7 @@ impl LexingFSM {
                 self.read_char();
                 self.finalize_current_word_part()?;
                 let nested = self.collect_paramsubst(1)?;
-                self.current_word.push(WordPart::ParamSubst(nested));
+                self.current_word.push(WordPart::QuotedParamSubst(nested));
             }
             '$' => {
                 // Simple parameter substitution in double quotes
@@ -328,6 +556,17 @@ impl LexingFSM {
         Err(LexingError::UnfinishedCmdSubst)
     }
 
+    /// Collects an arithmetic expansion after its opening `$((`. Text that doesn't end
+    /// with `))`, like `$((cd dir) && ls)`, is a command substitution starting with a
+    /// subshell instead.
+    fn collect_arithsubst(&mut self) -> Result<WordPart, LexingError> {
+        let text = self.collect_cmdsubst(2)?;
+        Ok(match text.strip_suffix(')') {
+            Some(expression) => WordPart::ArithSubst(expression.to_string()),
+            None => WordPart::CmdSubst(format!("({}", text)),
+        })
+    }
+
     /// Recursively collects characters within a parameter substitution block `${...}`.
     /// Handles nested braces by tracking the `depth`.
     fn collect_paramsubst(&mut self, mut depth: usize) -> Result<String, LexingError> {
@@ -351,21 +590,81 @@ impl LexingFSM {
         Err(LexingError::UnfinishedParamSubst)
     }
 
+    /// Consume the character after a backslash and append it to the word as literal text,
+    /// so it can't act as a delimiter or start a substitution.
+    /// A trailing backslash at the end of input is kept as is.
+    fn push_escaped(&mut self) -> Result<(), LexingError> {
+        self.finalize_current_word_part()?;
+        let escaped = self.read_char().unwrap_or('\\');
+        self.push_literal(escaped.to_string());
+        Ok(())
+    }
+
+    /// Append literal text to the current word, merging it with a preceding literal part.
+    fn push_literal(&mut self, text: String) {
+        match self.current_word.last_mut() {
+            Some(WordPart::Literal(last)) => last.push_str(&text),
+            _ => self.current_word.push(WordPart::Literal(text)),
+        }
+    }
+
+    /// Append text read from the input: a literal, or a glob pattern when unquoted.
+    fn push_text(&mut self, text: String) {
+        let special = ['*', '?', '[', '{', '}'];
+        if !self.is_quoted() && text.contains(special) {
+            self.current_word.push(WordPart::Glob(text));
+        } else if !text.is_empty() {
+            self.push_literal(text);
+        }
+    }
+
+    /// Whether the text being read is inside double quotes or a here-document body.
+    fn is_quoted(&self) -> bool {
+        matches!(self.state, LexingState::ReadingDoubleQuote | LexingState::ReadingHereDoc)
+    }
+
+    /// A simple `$name` substitution, quoted if it was read inside double quotes.
+    fn param_subst(&self, name: String) -> WordPart {
+        if self.is_quoted() {
+            WordPart::QuotedParamSubst(name)
+        } else {
+            WordPart::ParamSubst(name)
+        }
+    }
+
     /// Helper method to finalize the current buffer as either Literal or ParamSubst
     fn finalize_current_word_part(&mut self) -> Result<(), LexingError> {
         if !self.buffer.is_empty() {
-            if self.buffer.starts_with('$') && self.buffer.len() > 1 {
+            if let Some(name) = self.buffer[1..].chars().next()
+                && self.buffer.starts_with('$')
+                && (matches!(name, '?' | '#' | '@' | '*') || name.is_ascii_digit())
+            {
+                // Special (`$?`, `$#`, `$@`, `$*`) and positional (`$1`) parameters are a single
+                // character, so `$10` is `$1` followed by `0`; the rest is plain text
+                let rest = self.buffer[2..].to_string();
+                let part = self.param_subst(name.to_string());
+                self.current_word.push(part);
+                self.push_text(rest);
+            } else if self.buffer.starts_with('$') && self.buffer.len() > 1 {
                 // This is a simple parameter substitution like $a or $var
                 let param_name = self.buffer[1..].to_string();
-                if !param_name.is_empty() && (param_name.chars().next().unwrap().is_alphabetic() || param_name.chars().next().unwrap() == '_') {
-                    self.current_word.push(WordPart::ParamSubst(param_name));
+                if !param_name.is_empty() && (param_name.chars().next().unwrap().is_alphabetic() || param_name.starts_with('_')) {
+                    // The name ends at the first character that can't be part of it,
+                    // as in "$name and more" inside double quotes
+                    let end = param_name
+                        .find(|c: char| !c.is_alphanumeric() && c != '_')
+                        .unwrap_or(param_name.len());
+                    let (name, rest) = param_name.split_at(end);
+                    let part = self.param_subst(name.to_string());
+                    self.current_word.push(part);
+                    self.push_text(rest.to_string());
                 } else {
                     // Not a valid parameter name, treat as literal
                     self.current_word.push(WordPart::Literal(std::mem::take(&mut self.buffer)));
                 }
             } else {
-                // Regular literal
-                self.current_word.push(WordPart::Literal(std::mem::take(&mut self.buffer)));
+                let text = std::mem::take(&mut self.buffer);
+                self.push_text(text);
             }
             self.buffer.clear();
         }
@@ -373,6 +672,19 @@ impl LexingFSM {
     }
 }
 
+/// Split the body of a here-document with an unquoted delimiter into parts. Like in double
+/// quotes, `$` starts substitutions and a backslash escapes `$`, `` ` `` and `\`, but
+/// double quotes are ordinary characters.
+fn here_doc_parts(body: String) -> Result<Vec<WordPart>, LexingError> {
+    let mut lexer = LexingFSM::new(body);
+    lexer.state = LexingState::ReadingHereDoc;
+    while let Some(ch) = lexer.read_char() {
+        lexer.handle_double_quote(ch)?;
+    }
+    lexer.finalize_current_word_part()?;
+    Ok(lexer.current_word)
+}
+
 /// The main entry point function to perform lexical analysis.
 ///
 /// Creates and runs the finite state machine to tokenize the input line.
@@ -387,3 +699,331 @@ pub fn split_into_tokens(line: String) -> Result<Vec<Token>, LexingError> {
     let mut lexer = LexingFSM::new(line);
     lexer.make_tokens()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(s: &str) -> Token {
+        Token::Word(vec![WordPart::Literal(s.to_string())])
+    }
+
+    #[test]
+    fn test_semicolon_separates_statements() {
+        let tokens = split_into_tokens("echo a; echo b;".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("echo"),
+                word("a"),
+                Token::Semicolon,
+                word("echo"),
+                word("b"),
+                Token::Semicolon,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quoted_semicolon_is_literal() {
+        let tokens = split_into_tokens("echo 'a;b'".to_string()).unwrap();
+        assert_eq!(tokens, vec![word("echo"), word("a;b")]);
+    }
+
+    #[test]
+    fn test_last_status_parameter() {
+        let tokens = split_into_tokens("echo $? \"$?x\"".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("echo"),
+                Token::Word(vec![WordPart::ParamSubst("?".to_string())]),
+                Token::Word(vec![
+                    WordPart::QuotedParamSubst("?".to_string()),
+                    WordPart::Literal("x".to_string()),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escaped_space_joins_words() {
+        let tokens = split_into_tokens(r"echo a\ b".to_string()).unwrap();
+        assert_eq!(tokens, vec![word("echo"), word("a b")]);
+    }
+
+    #[test]
+    fn test_escaped_dollar_is_literal() {
+        let tokens = split_into_tokens(r"echo \$HOME".to_string()).unwrap();
+        assert_eq!(tokens, vec![word("echo"), word("$HOME")]);
+
+        let tokens = split_into_tokens(r#"echo "\$HOME \a""#.to_string()).unwrap();
+        assert_eq!(tokens, vec![word("echo"), word(r"$HOME \a")]);
+    }
+
+    #[test]
+    fn test_escaped_operator_is_literal() {
+        let tokens = split_into_tokens(r"echo a\|b\;".to_string()).unwrap();
+        assert_eq!(tokens, vec![word("echo"), word("a|b;")]);
+    }
+
+    #[test]
+    fn test_single_quoted_dollar_is_literal() {
+        let tokens = split_into_tokens("echo '$HOME'".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("echo"),
+                Token::Word(vec![WordPart::Literal("$HOME".to_string())]),
+            ]
+        );
+
+        let tokens = split_into_tokens("echo $A'$B'".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("echo"),
+                Token::Word(vec![
+                    WordPart::ParamSubst("A".to_string()),
+                    WordPart::Literal("$B".to_string()),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_full_line_comment() {
+        let tokens = split_into_tokens("# echo a | wc".to_string()).unwrap();
+        assert_eq!(tokens, vec![]);
+    }
+
+    #[test]
+    fn test_trailing_comment() {
+        let tokens = split_into_tokens("echo a # the rest; is ignored".to_string()).unwrap();
+        assert_eq!(tokens, vec![word("echo"), word("a")]);
+    }
+
+    #[test]
+    fn test_hash_inside_word_is_literal() {
+        let tokens = split_into_tokens("echo foo#bar '#x'".to_string()).unwrap();
+        assert_eq!(tokens, vec![word("echo"), word("foo#bar"), word("#x")]);
+    }
+
+    #[test]
+    fn test_unquoted_metacharacters_make_a_glob() {
+        let tokens = split_into_tokens(r#"ls *.txt '*.txt' "a?" b\*"#.to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("ls"),
+                Token::Word(vec![WordPart::Glob("*.txt".to_string())]),
+                word("*.txt"),
+                word("a?"),
+                word("b*"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_here_string() {
+        let tokens = split_into_tokens(r#"wc <<< "one two" < in<<<x"#.to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("wc"),
+                Token::HereString,
+                word("one two"),
+                Token::RedirectLeft,
+                word("in"),
+                Token::HereString,
+                word("x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quoted_parameter_followed_by_text() {
+        let tokens = split_into_tokens(r#"echo "$X there""#.to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("echo"),
+                Token::Word(vec![
+                    WordPart::QuotedParamSubst("X".to_string()),
+                    WordPart::Literal(" there".to_string()),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_here_doc_takes_following_lines() {
+        let tokens = split_into_tokens("cat <<EOF | wc\nhello\n  world\nEOF".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("cat"),
+                Token::HereDoc(vec![WordPart::Literal("hello\n  world\n".to_string())]),
+                Token::PipeOp,
+                word("wc"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_here_doc_strips_tabs() {
+        let tokens = split_into_tokens("cat <<- 'END'\n\tone\n\t\ttwo\n\tEND".to_string()).unwrap();
+        let body = vec![WordPart::Literal("one\ntwo\n".to_string())];
+        assert_eq!(tokens, vec![word("cat"), Token::HereDoc(body)]);
+    }
+
+    #[test]
+    fn test_here_doc_body_substitutions() {
+        let tokens = split_into_tokens("cat <<EOF\n\"$X\" \\$Y $(pwd)\nEOF".to_string()).unwrap();
+        let body = vec![
+            WordPart::Literal("\"".to_string()),
+            WordPart::QuotedParamSubst("X".to_string()),
+            WordPart::Literal("\" $Y ".to_string()),
+            WordPart::CmdSubst("pwd".to_string()),
+            WordPart::Literal("\n".to_string()),
+        ];
+        assert_eq!(tokens, vec![word("cat"), Token::HereDoc(body)]);
+
+        // A quoted delimiter keeps the body as is
+        let tokens = split_into_tokens("cat <<'EOF'\n$X *\nEOF".to_string()).unwrap();
+        let body = vec![WordPart::Literal("$X *\n".to_string())];
+        assert_eq!(tokens, vec![word("cat"), Token::HereDoc(body)]);
+    }
+
+    #[test]
+    fn test_unterminated_here_doc() {
+        assert!(matches!(
+            split_into_tokens("cat <<EOF\nhello".to_string()),
+            Err(LexingError::UnfinishedHereDoc(delimiter)) if delimiter == "EOF"
+        ));
+        assert!(matches!(
+            split_into_tokens("cat <<".to_string()),
+            Err(LexingError::MissingHereDocDelimiter)
+        ));
+    }
+
+    #[test]
+    fn test_positional_parameters() {
+        let tokens = split_into_tokens("echo $1 $# \"$0\" $10 $@ $*".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("echo"),
+                Token::Word(vec![WordPart::ParamSubst("1".to_string())]),
+                Token::Word(vec![WordPart::ParamSubst("#".to_string())]),
+                Token::Word(vec![WordPart::QuotedParamSubst("0".to_string())]),
+                Token::Word(vec![
+                    WordPart::ParamSubst("1".to_string()),
+                    WordPart::Literal("0".to_string()),
+                ]),
+                Token::Word(vec![WordPart::ParamSubst("@".to_string())]),
+                Token::Word(vec![WordPart::ParamSubst("*".to_string())]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slashes_stay_inside_words() {
+        let tokens = split_into_tokens("cat /etc/hostname a/b c".to_string()).unwrap();
+        assert_eq!(tokens, vec![word("cat"), word("/etc/hostname"), word("a/b"), word("c")]);
+
+        let tokens = split_into_tokens("PATH=/bin ls $D/*.txt".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("PATH"),
+                Token::Equal,
+                word("/bin"),
+                word("ls"),
+                Token::Word(vec![
+                    WordPart::ParamSubst("D".to_string()),
+                    WordPart::Glob("/*.txt".to_string()),
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_operators_are_single_tokens() {
+        let table = [
+            ("|", Token::PipeOp),
+            ("||", Token::OrIf),
+            ("&", Token::Ampersand),
+            ("&&", Token::AndIf),
+            (";", Token::Semicolon),
+            (">", Token::RedirectRight),
+            (">>", Token::RedirectAppend),
+            ("<", Token::RedirectLeft),
+            ("<<<", Token::HereString),
+            ("2>", Token::RedirectErr),
+            ("2>>", Token::RedirectErrAppend),
+        ];
+        for (operator, token) in table {
+            let expected = vec![word("a"), token, word("b")];
+            let tokens = split_into_tokens(format!("a {} b", operator)).unwrap();
+            assert_eq!(tokens, expected, "{:?} after a space", operator);
+            if !operator.starts_with('2') {
+                let tokens = split_into_tokens(format!("a{}b", operator)).unwrap();
+                assert_eq!(tokens, expected, "{:?} without spaces", operator);
+            }
+        }
+
+        let tokens = split_into_tokens("a<<END b\nbody\nEND".to_string()).unwrap();
+        let body = vec![WordPart::Literal("body\n".to_string())];
+        assert_eq!(tokens, vec![word("a"), Token::HereDoc(body), word("b")]);
+
+        // Separated by a space, the characters are two operators
+        let tokens = split_into_tokens("a > > b & & c".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("a"),
+                Token::RedirectRight,
+                Token::RedirectRight,
+                word("b"),
+                Token::Ampersand,
+                Token::Ampersand,
+                word("c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_expansion() {
+        let arith = |text: &str| WordPart::ArithSubst(text.to_string());
+        let tokens = split_into_tokens("echo $((1 + 2 * 3)) x$(( (X) % 2 ))".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("echo"),
+                Token::Word(vec![arith("1 + 2 * 3")]),
+                Token::Word(vec![WordPart::Literal("x".to_string()), arith(" (X) % 2 ")]),
+            ]
+        );
+
+        let tokens = split_into_tokens("echo \"[$((X+1))]\"".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                word("echo"),
+                Token::Word(vec![
+                    WordPart::Literal("[".to_string()),
+                    arith("X+1"),
+                    WordPart::Literal("]".to_string()),
+                ]),
+            ]
+        );
+
+        // Without the closing `))` it is a command substitution running a subshell
+        let tokens = split_into_tokens("echo $((echo a) | cat)".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![word("echo"), Token::Word(vec![WordPart::CmdSubst("(echo a) | cat".to_string())])]
+        );
+    }
+}
@@ -10,17 +10,23 @@
 //! [`env`] expose traits and types for implementing your own commands and for
 //! interacting with the process environment.
 
+mod arith;
+mod brace;
 mod builtin;
 pub mod command;
 pub mod env;
 mod external;
+mod glob;
 mod interpreter;
 mod lexer;
 mod parser;
+mod signals;
 mod io_adapters;
+#[cfg(test)]
+mod test_util;
 
 /// Just a convenient re-export of the interactive command runner.
 ///
 /// See [`Interpreter`] for the high-level API and examples.
-pub use interpreter::Interpreter;
+pub use interpreter::{ExpansionError, Interpreter};
 pub use io_adapters::{MemReader, MemWriter};
@@ -2,5 +2,6 @@ use shell_commands::Interpreter;
 
 fn main() {
     let mut interpreter = Interpreter::default();
-    let _ = interpreter.repl().unwrap();
+    let code = interpreter.repl().unwrap();
+    std::process::exit(code);
 }
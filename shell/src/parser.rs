@@ -8,6 +8,17 @@ pub enum Word {
     Compound(Vec<WordPart>),
 }
 
+impl Word {
+    /// The word in shell syntax, with substitutions written back as `$(...)` and `${...}`.
+    /// Used to show a word that couldn't be expanded in error messages.
+    pub fn render(&self) -> String {
+        match self {
+            Word::Literal(text) => text.clone(),
+            Word::Compound(parts) => parts.iter().map(WordPart::to_string).collect(),
+        }
+    }
+}
+
 /// AST node for the shell
 ///
 /// Represents a single element in the Abstract Syntax Tree (AST) constructed
@@ -15,6 +26,14 @@ pub enum Word {
 /// components of a shell script or command line.
 #[derive(Debug)]
 pub enum AstNode {
+    /// A **sequence** of statements separated by `;`, executed one after another.
+    /// Each element is a pipeline or a single command.
+    Sequence(Vec<AstNode>),
+
+    /// A pipeline or a single command followed by `&`, run in the **background**
+    /// without waiting for it to finish.
+    Background(Box<AstNode>),
+
     /// A **pipeline** of commands connected by the pipe operator (`|`).
     /// The inner `Vec<AstNode>` contains the individual commands in execution order.
     Pipeline(Vec<AstNode>),
@@ -69,6 +88,16 @@ pub enum RedirectKind {
     Output,
     /// Output redirection with append (`>>`): Writes standard output to a file, **appending** to the file if it exists.
     Append,
+    /// Error output redirection (`2>`): Writes standard error to a file, **overwriting** the file if it exists.
+    OutputErr,
+    /// Error output redirection with append (`2>>`): Writes standard error to a file, **appending** to the file if it exists.
+    AppendErr,
+    /// Descriptor duplication (`2>&1`): Makes `from_fd` refer to the same destination as `to_fd`.
+    Dup { from_fd: u32, to_fd: u32 },
+    /// Here-string (`<<<`): Feeds the expanded target, followed by a newline, to standard input.
+    HereString,
+    /// Here-document (`<<DELIM`): Feeds the body, stored as the target, to standard input.
+    HereDoc,
 }
 
 /// Kind of substitution
@@ -103,6 +132,22 @@ pub enum ParsingError {
     UnsupportedSubstitution,
 }
 
+impl std::fmt::Display for ParsingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParsingError::UnexpectedToken(token) => write!(f, "unexpected token `{}`", token),
+            ParsingError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            ParsingError::ExpectedWord => write!(f, "expected a word"),
+            ParsingError::ExpectedAssignmentName => write!(f, "expected a variable name"),
+            ParsingError::InvalidAssignment => write!(f, "invalid assignment"),
+            ParsingError::EmptyPipeline => write!(f, "empty command"),
+            ParsingError::UnsupportedSubstitution => write!(f, "unsupported substitution"),
+        }
+    }
+}
+
+impl std::error::Error for ParsingError {}
+
 struct AstBuilder {
     tokens: Vec<Token>,
     pos: usize,
@@ -114,7 +159,7 @@ impl AstBuilder {
     }
 
     fn build_ast(mut self) -> Result<AstNode, ParsingError> {
-        let ast = self.parse_pipeline()?;
+        let ast = self.parse_sequence()?;
 
         // Ensure we consumed all tokens
         if self.pos < self.tokens.len() {
@@ -144,6 +189,40 @@ impl AstBuilder {
         }
     }
 
+    /// Parse a sequence: pipeline ((';' | '&') pipeline)*, where empty statements are skipped
+    fn parse_sequence(&mut self) -> Result<AstNode, ParsingError> {
+        let mut statements = Vec::new();
+
+        loop {
+            while let Some(Token::Semicolon) = self.peek() {
+                self.consume(); // skip empty statements
+            }
+            match self.peek() {
+                None => break,
+                Some(Token::Ampersand) => {
+                    return Err(ParsingError::UnexpectedToken(Token::Ampersand));
+                }
+                Some(_) => {}
+            }
+            let statement = self.parse_pipeline()?;
+            match self.peek() {
+                // Like `;`, a trailing `&` ends the statement
+                Some(Token::Ampersand) => {
+                    self.consume();
+                    statements.push(AstNode::Background(Box::new(statement)));
+                }
+                Some(Token::Semicolon) | None => statements.push(statement),
+                Some(token) => return Err(ParsingError::UnexpectedToken(token.clone())),
+            }
+        }
+
+        match statements.len() {
+            0 => Err(ParsingError::EmptyPipeline),
+            1 => Ok(statements.remove(0)),
+            _ => Ok(AstNode::Sequence(statements)),
+        }
+    }
+
     /// Parse a pipeline: command ('|' command)*
     fn parse_pipeline(&mut self) -> Result<AstNode, ParsingError> {
         let mut commands = Vec::new();
@@ -173,57 +252,54 @@ impl AstBuilder {
         // Parse tokens until we hit a pipe or end
         while let Some(token) = self.peek() {
             match token {
-                Token::PipeOp => break, // End of command
-                Token::Equal => return Err(ParsingError::UnexpectedToken(token.clone())),
+                // End of command; `&` inside `2>&1` is consumed with the redirect
+                Token::PipeOp
+                | Token::Semicolon
+                | Token::Ampersand
+                | Token::AndIf
+                | Token::OrIf => break,
+                Token::Equal => {
+                    return Err(ParsingError::UnexpectedToken(token.clone()));
+                }
 
                 Token::Word(parts) => {
                     let is_potential_assignment = matches!(self.peek_n(1), Some(Token::Equal));
-                    // Check if the next token is a Slash, indicating a path needs parsing
-                    let is_path_start = matches!(self.peek_n(1), Some(Token::Slash));
 
                     if argv.is_empty() && is_potential_assignment {
 
                         // Check if it's a valid shell variable name start (starts with a letter)
                         let is_valid_name_start = parts.len() == 1
-                            && matches!(&parts[0], WordPart::Literal(s) if s.chars().next().map_or(false, |c| c.is_ascii_alphabetic()));
+                            && matches!(&parts[0], WordPart::Literal(s) if s.chars().next().is_some_and(|c| c.is_ascii_alphabetic()));
 
                         if is_valid_name_start {
                             // Valid assignment (e.g., VAR=value)
                             assignments.push(self.parse_assignment()?);
                         } else {
-                            // Invalid name before '=' is treated as a regular argument/path.
-                            // FIX: Use new helper function to consolidate path/equal components
-                            if is_path_start || is_potential_assignment {
-                                argv.push(self.parse_word_or_path_with_equal()?);
-                            } else {
-                                argv.push(self.parse_word()?);
-                            }
+                            // Invalid name before '=' is treated as a regular argument.
+                            argv.push(self.parse_word_with_equal()?);
                         }
-                    } else if is_path_start || is_potential_assignment {
-                        // FIX: If Word is followed by Slash OR Equal, consolidate path/equal components
-                        argv.push(self.parse_word_or_path_with_equal()?);
+                    } else if is_potential_assignment {
+                        // FIX: If Word is followed by Equal, consolidate the equal components
+                        argv.push(self.parse_word_with_equal()?);
                     } else {
                         // Regular argument or command name. Use parse_word.
                         argv.push(self.parse_word()?);
                     }
                 }
 
-                // When a Slash is seen, it must be the start of an absolute path, so we call the consolidation logic.
-                Token::Slash => {
-                    argv.push(self.parse_word_or_path_with_equal()?);
-                }
-
-                Token::RedirectLeft | Token::RedirectRight => {
+                Token::RedirectLeft
+                | Token::RedirectRight
+                | Token::RedirectAppend
+                | Token::RedirectErr
+                | Token::RedirectErrAppend
+                | Token::HereString
+                | Token::HereDoc(_) => {
                     // Logic for redirects
                     match self.peek() {
-                        Some(Token::RedirectRight) if matches!(self.peek_n(1), Some(Token::RedirectRight)) => {
-                            self.consume(); // consume first '>'
-                            self.consume(); // consume second '>'
-                            let target = self.parse_word()?;
-                            redirects.push(AstNode::Redirect {
-                                kind: RedirectKind::Append,
-                                target,
-                            });
+                        Some(Token::RedirectErr) if matches!(self.peek_n(1), Some(Token::Ampersand)) => {
+                            self.consume(); // consume '2>'
+                            self.consume(); // consume '&'
+                            redirects.push(self.parse_dup_target(2)?);
                         }
                         _ => {
                             redirects.push(self.parse_redirect()?);
@@ -245,36 +321,33 @@ impl AstBuilder {
         })
     }
 
-    /// Tries to parse a path (Word/Slash sequence) OR consolidate a Word = Word sequence.
-    /// Used for command arguments where "=" and "/" should be part of the word.
-    fn parse_word_or_path_with_equal(&mut self) -> Result<Word, ParsingError> {
-        let mut path_parts = Vec::new();
+    /// Consolidate a Word = Word sequence into one word.
+    /// Used for command arguments where "=" should be part of the word.
+    fn parse_word_with_equal(&mut self) -> Result<Word, ParsingError> {
+        let mut word_parts = Vec::new();
 
         while let Some(token) = self.peek() {
             match token {
-                // Collect path components (Word or Slash)
+                // A word after the first one starts the next argument
+                Token::Word(_) if !word_parts.is_empty() => break,
                 Token::Word(parts) => {
-                    path_parts.extend(parts.clone());
-                    self.consume();
-                }
-                Token::Slash => {
-                    path_parts.push(WordPart::Literal("/".to_string()));
+                    word_parts.extend(parts.clone());
                     self.consume();
                 }
 
                 // FIX: Consolidate "Equal" followed by a Word into the current word if we are still building it.
                 Token::Equal => {
-                    if path_parts.is_empty() {
+                    if word_parts.is_empty() {
                         // If "=" is the first token, we treat it as an unexpected token.
                         return Err(ParsingError::UnexpectedToken(self.consume().unwrap()));
                     }
                     // Consume '='
                     self.consume();
-                    path_parts.push(WordPart::Literal("=".to_string()));
+                    word_parts.push(WordPart::Literal("=".to_string()));
 
                     // Expect a value (Word) immediately after '='
                     if let Some(Token::Word(value_parts)) = self.peek() {
-                        path_parts.extend(value_parts.clone());
+                        word_parts.extend(value_parts.clone());
                         self.consume();
                         // Stop processing after consolidating NAME=VALUE argument
                         break;
@@ -288,25 +361,18 @@ impl AstBuilder {
             }
         }
 
-        if path_parts.is_empty() {
+        if word_parts.is_empty() {
             return Err(ParsingError::ExpectedWord);
         }
 
         // Return Literal if only one simple part, otherwise Compound.
-        if path_parts.len() == 1 {
-            if let WordPart::Literal(s) = &path_parts[0] {
-                return Ok(Word::Literal(s.clone()));
-            }
+        if word_parts.len() == 1
+            && let WordPart::Literal(s) = &word_parts[0]
+        {
+            return Ok(Word::Literal(s.clone()));
         }
 
-        Ok(Word::Compound(path_parts))
-    }
-
-
-    /// This is the original simple parse_path, now replaced by parse_word_or_path_with_equal for argv.
-    /// Kept for backward compatibility but might not be used if parse_word_or_path_with_equal is used everywhere.
-    fn parse_path(&mut self) -> Result<Word, ParsingError> {
-        self.parse_word_or_path_with_equal()
+        Ok(Word::Compound(word_parts))
     }
 
 
@@ -336,34 +402,63 @@ impl AstBuilder {
         // Parse the value if present
         let value = match self.peek() {
             Some(Token::Word(_)) => Some(self.parse_word()?),
-            Some(Token::PipeOp) | None => None,
+            Some(Token::PipeOp | Token::Semicolon) | None => None,
             Some(token) => return Err(ParsingError::UnexpectedToken(token.clone())),
         };
 
         Ok(AstNode::Assignment { name, value })
     }
 
-    /// Parse a redirect: '<' word or '>' word
+    /// Parse a redirect: '<', '>', '>>', '2>' or '2>>' and a word, '<<<' word or a here-document
     fn parse_redirect(&mut self) -> Result<AstNode, ParsingError> {
         let kind = match self.consume() {
             Some(Token::RedirectLeft) => RedirectKind::Input,
             Some(Token::RedirectRight) => RedirectKind::Output,
+            Some(Token::RedirectAppend) => RedirectKind::Append,
+            Some(Token::RedirectErr) => RedirectKind::OutputErr,
+            Some(Token::RedirectErrAppend) => RedirectKind::AppendErr,
+            Some(Token::HereString) => RedirectKind::HereString,
+            Some(Token::HereDoc(body)) => {
+                return Ok(AstNode::Redirect {
+                    kind: RedirectKind::HereDoc,
+                    target: Word::Compound(body),
+                });
+            }
             Some(token) => return Err(ParsingError::UnexpectedToken(token)),
             None => return Err(ParsingError::UnexpectedEnd),
         };
 
-        // Parse the target word
-        // Use parse_word here since redirect targets are usually single words/paths that don't need re-parsing
         let target = self.parse_word()?;
 
         Ok(AstNode::Redirect { kind, target })
     }
 
+    /// Parse the descriptor number after `N>&` into a duplication redirect
+    fn parse_dup_target(&mut self, from_fd: u32) -> Result<AstNode, ParsingError> {
+        match self.consume() {
+            Some(Token::Word(parts)) => {
+                let target = Self::word_parts_to_ast_word(parts.clone())?;
+                let to_fd = match &target {
+                    Word::Literal(digits) => digits.parse::<u32>().ok(),
+                    Word::Compound(_) => None,
+                };
+                match to_fd {
+                    Some(to_fd) => Ok(AstNode::Redirect {
+                        kind: RedirectKind::Dup { from_fd, to_fd },
+                        target,
+                    }),
+                    None => Err(ParsingError::UnexpectedToken(Token::Word(parts))),
+                }
+            }
+            Some(token) => Err(ParsingError::UnexpectedToken(token)),
+            None => Err(ParsingError::UnexpectedEnd),
+        }
+    }
+
     /// Parse a word from the current token (used primarily by parse_redirect and parse_assignment)
     fn parse_word(&mut self) -> Result<Word, ParsingError> {
         match self.consume() {
             Some(Token::Word(parts)) => Self::word_parts_to_ast_word(parts),
-            Some(Token::Slash) => Ok(Word::Literal("/".to_string())),
             Some(token) => Err(ParsingError::UnexpectedToken(token)),
             None => Err(ParsingError::UnexpectedEnd),
         }
@@ -375,17 +470,23 @@ impl AstBuilder {
 
         for part in parts {
             match part {
-                WordPart::CmdSubst(content) => {
+                WordPart::CmdSubst(content) | WordPart::ArithSubst(content) => {
                     // For now, we'll just return an error for command substitutions
                     return Err(ParsingError::UnsupportedSubstitution);
                 }
-                WordPart::ParamSubst(content) => {
+                WordPart::ParamSubst(content) | WordPart::QuotedParamSubst(content) => {
                     // For now, we'll just return an error for parameter substitutions
                     return Err(ParsingError::UnsupportedSubstitution);
                 }
                 WordPart::Literal(text) => {
                     processed_parts.push(WordPart::Literal(text));
                 }
+                WordPart::Tilde => {
+                    processed_parts.push(WordPart::Tilde);
+                }
+                WordPart::Glob(pattern) => {
+                    processed_parts.push(WordPart::Glob(pattern));
+                }
             }
         }
 
@@ -407,10 +508,10 @@ impl AstBuilder {
 
     /// Convert lexer WordParts to AST Word
     fn word_parts_to_ast_word(parts: Vec<WordPart>) -> Result<Word, ParsingError> {
-        if parts.len() == 1 {
-            if let WordPart::Literal(s) = &parts[0] {
-                return Ok(Word::Literal(s.clone()));
-            }
+        if parts.len() == 1
+            && let WordPart::Literal(s) = &parts[0]
+        {
+            return Ok(Word::Literal(s.clone()));
         }
         Ok(Word::Compound(parts))
     }
@@ -484,23 +585,207 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_stderr_dup_to_stdout() {
+        // Tokens for "cmd 2>&1"
+        let tokens = crate::lexer::split_into_tokens("cmd 2>&1".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word(vec![WordPart::Literal("cmd".to_string())]),
+                Token::RedirectErr,
+                Token::Ampersand,
+                Token::Word(vec![WordPart::Literal("1".to_string())]),
+            ]
+        );
+
+        let ast = construct_ast(tokens).unwrap();
+
+        if let AstNode::Command { argv, redirects, .. } = ast {
+            assert_eq!(argv, vec![lit("cmd")]);
+            assert_eq!(redirects.len(), 1);
+            assert!(matches!(
+                &redirects[0],
+                AstNode::Redirect {
+                    kind: RedirectKind::Dup { from_fd: 2, to_fd: 1 },
+                    ..
+                }
+            ));
+        } else {
+            panic!("Expected Command node");
+        }
+    }
+
+    #[test]
+    fn test_sequence_of_two_statements() {
+        // Tokens for "echo a; echo b;"
+        let tokens = vec![
+            Token::Word(vec![WordPart::Literal("echo".to_string())]),
+            Token::Word(vec![WordPart::Literal("a".to_string())]),
+            Token::Semicolon,
+            Token::Word(vec![WordPart::Literal("echo".to_string())]),
+            Token::Word(vec![WordPart::Literal("b".to_string())]),
+            Token::Semicolon,
+        ];
+
+        let ast = construct_ast(tokens).unwrap();
+
+        if let AstNode::Sequence(statements) = ast {
+            assert_eq!(statements.len(), 2, "Trailing ';' must not add a statement");
+            for (statement, expected) in statements.iter().zip(["a", "b"]) {
+                if let AstNode::Command { argv, .. } = statement {
+                    assert_eq!(argv, &vec![lit("echo"), lit(expected)]);
+                } else {
+                    panic!("Expected Command node");
+                }
+            }
+        } else {
+            panic!("Expected Sequence node");
+        }
+    }
+
     #[test]
     fn test_path_argument_fix_cd_parent() {
         let tokens = vec![
             Token::Word(vec![WordPart::Literal("cd".to_string())]),
-            Token::Word(vec![WordPart::Literal("..".to_string())]),
-            Token::Slash,
-            Token::Word(vec![WordPart::Literal("..".to_string())]),
+            Token::Word(vec![WordPart::Literal("../..".to_string())]),
         ];
         let ast = construct_ast(tokens).unwrap();
 
         if let AstNode::Command { argv, .. } = ast {
-            assert_eq!(argv.len(), 2, "Command should have 2 arguments: 'cd' and '.. / ..'");
+            assert_eq!(argv.len(), 2, "Command should have 2 arguments: 'cd' and '../..'");
             assert_eq!(argv[0], lit("cd"), "First argument must be the command 'cd'");
 
-            assert_compound_eq(&argv[1], "../..");
+            assert_eq!(argv[1], lit("../.."));
         } else {
             panic!("Expected Command node");
         }
     }
+
+    #[test]
+    fn test_here_string_redirect() {
+        // Tokens for "wc <<< $TEXT"
+        let tokens = vec![
+            Token::Word(vec![WordPart::Literal("wc".to_string())]),
+            Token::HereString,
+            Token::Word(vec![WordPart::ParamSubst("TEXT".to_string())]),
+        ];
+
+        let ast = construct_ast(tokens).unwrap();
+
+        if let AstNode::Command { argv, redirects, .. } = ast {
+            assert_eq!(argv, vec![lit("wc")]);
+            assert_eq!(redirects.len(), 1);
+            assert!(matches!(
+                &redirects[0],
+                AstNode::Redirect {
+                    kind: RedirectKind::HereString,
+                    target: Word::Compound(_),
+                }
+            ));
+        } else {
+            panic!("Expected Command node");
+        }
+    }
+
+    #[test]
+    fn test_redirect_targets_keep_quoted_spaces() {
+        let word = |text: &str| Token::Word(vec![WordPart::Literal(text.to_string())]);
+        // Tokens for `echo hi > "out file.txt"` and `echo hi > logs/out.txt extra`
+        let quoted = vec![word("echo"), word("hi"), Token::RedirectRight, word("out file.txt")];
+        let path = vec![word("echo"), Token::RedirectRight, word("logs/out.txt"), word("extra")];
+
+        let AstNode::Command { redirects, .. } = construct_ast(quoted).unwrap() else {
+            panic!("Expected Command node");
+        };
+        let AstNode::Redirect { kind: RedirectKind::Output, target } = &redirects[0] else {
+            panic!("Expected an output redirect");
+        };
+        assert_eq!(*target, lit("out file.txt"));
+
+        let AstNode::Command { argv, redirects, .. } = construct_ast(path).unwrap() else {
+            panic!("Expected Command node");
+        };
+        assert_eq!(argv, vec![lit("echo"), lit("extra")]);
+        let AstNode::Redirect { target, .. } = &redirects[0] else {
+            panic!("Expected a redirect");
+        };
+        assert_eq!(*target, lit("logs/out.txt"));
+    }
+
+    #[test]
+    fn test_here_string_without_word() {
+        let tokens = vec![
+            Token::Word(vec![WordPart::Literal("wc".to_string())]),
+            Token::HereString,
+        ];
+        assert!(matches!(construct_ast(tokens), Err(ParsingError::UnexpectedEnd)));
+    }
+
+    #[test]
+    fn test_ampersand_runs_statement_in_background() {
+        // Tokens for "sleep 1 & echo a"
+        let tokens = vec![
+            Token::Word(vec![WordPart::Literal("sleep".to_string())]),
+            Token::Word(vec![WordPart::Literal("1".to_string())]),
+            Token::Ampersand,
+            Token::Word(vec![WordPart::Literal("echo".to_string())]),
+            Token::Word(vec![WordPart::Literal("a".to_string())]),
+        ];
+
+        let ast = construct_ast(tokens).unwrap();
+
+        let AstNode::Sequence(statements) = ast else {
+            panic!("Expected Sequence node");
+        };
+        assert_eq!(statements.len(), 2);
+        let AstNode::Background(job) = &statements[0] else {
+            panic!("Expected Background node");
+        };
+        assert!(matches!(job.as_ref(), AstNode::Command { argv, .. } if argv[0] == lit("sleep")));
+        assert!(matches!(&statements[1], AstNode::Command { .. }));
+
+        let err = construct_ast(vec![Token::Ampersand]).unwrap_err();
+        assert!(matches!(err, ParsingError::UnexpectedToken(Token::Ampersand)));
+    }
+
+    #[test]
+    fn test_and_or_lists_are_rejected() {
+        let echo = || Token::Word(vec![WordPart::Literal("echo".to_string())]);
+        for operator in [Token::AndIf, Token::OrIf] {
+            let err = construct_ast(vec![echo(), operator.clone(), echo()]).unwrap_err();
+            assert!(matches!(err, ParsingError::UnexpectedToken(token) if token == operator));
+        }
+    }
+
+    #[test]
+    fn test_word_render_writes_substitutions_back() {
+        let word = Word::Compound(vec![
+            WordPart::Literal("prefix-".to_string()),
+            WordPart::ParamSubst("NAME".to_string()),
+            WordPart::CmdSubst("echo x".to_string()),
+            WordPart::QuotedParamSubst("Q".to_string()),
+        ]);
+        assert_eq!(word.render(), "prefix-${NAME}$(echo x)\"${Q}\"");
+        assert_eq!(lit("plain").render(), "plain");
+    }
+
+    #[test]
+    fn test_arithmetic_expansion_in_argument() {
+        // Tokens for "echo n=$((X + 1))"
+        let tokens = vec![
+            Token::Word(vec![WordPart::Literal("echo".to_string())]),
+            Token::Word(vec![
+                WordPart::Literal("n".to_string()),
+                WordPart::Literal("=".to_string()),
+                WordPart::ArithSubst("X + 1".to_string()),
+            ]),
+        ];
+
+        let AstNode::Command { argv, .. } = construct_ast(tokens).unwrap() else {
+            panic!("Expected Command node");
+        };
+        assert_eq!(argv.len(), 2);
+        assert_eq!(argv[1].render(), "n=$((X + 1))");
+    }
 }
@@ -0,0 +1,112 @@
+//! Interrupting external commands with Ctrl-C.
+//!
+//! On Unix every external command (or every external stage of a pipeline) runs in its own
+//! process group. While it runs, that group is the foreground one: it owns the terminal, so
+//! Ctrl-C and terminal reads go straight to it, and a SIGINT that reaches the shell anyway
+//! (e.g. `kill -INT`) is forwarded to it instead of terminating the shell.
+//!
+//! Job control isn't supported, so a command stopped with Ctrl-Z can't be resumed.
+
+use std::process::Command;
+use std::sync::Once;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// Process group of the command running in the foreground, 0 when there is none.
+static FOREGROUND_PGID: AtomicI32 = AtomicI32::new(0);
+
+/// Install the SIGINT handler. Calling it more than once has no effect.
+///
+/// Without a foreground command the shell exits with 130, like it did before
+/// the handler was installed.
+pub(crate) fn install_interrupt_handler() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let installed = ctrlc::set_handler(|| match FOREGROUND_PGID.load(Ordering::SeqCst) {
+            0 => std::process::exit(130),
+            pgid => forward_interrupt(pgid),
+        });
+        if let Err(e) = installed {
+            eprintln!("failed to install the SIGINT handler: {}", e);
+        }
+    });
+}
+
+/// Put the command being built into the process group `pgid`, or into a new group
+/// led by the command itself when `pgid` is 0.
+#[cfg(unix)]
+pub(crate) fn set_process_group(command: &mut Command, pgid: u32) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(pgid as i32);
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_process_group(_command: &mut Command, _pgid: u32) {}
+
+/// Marks a process group as the foreground one until dropped.
+pub(crate) struct Foreground {
+    /// Previous owner of the terminal, if it was handed over to the group.
+    terminal_owner: Option<i32>,
+}
+
+impl Foreground {
+    pub(crate) fn enter(pgid: u32) -> Self {
+        FOREGROUND_PGID.store(pgid as i32, Ordering::SeqCst);
+        Foreground {
+            terminal_owner: take_terminal(pgid as i32),
+        }
+    }
+}
+
+impl Drop for Foreground {
+    fn drop(&mut self) {
+        FOREGROUND_PGID.store(0, Ordering::SeqCst);
+        if let Some(owner) = self.terminal_owner {
+            set_terminal_owner(owner);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn forward_interrupt(pgid: i32) {
+    unsafe {
+        libc::kill(-pgid, libc::SIGINT);
+    }
+}
+
+#[cfg(not(unix))]
+fn forward_interrupt(_pgid: i32) {}
+
+/// Hand the terminal on stdin over to the process group `pgid` and return its previous
+/// owner. Does nothing unless stdin is a terminal owned by the shell's own group.
+#[cfg(unix)]
+fn take_terminal(pgid: i32) -> Option<i32> {
+    let owner = unsafe {
+        if libc::isatty(libc::STDIN_FILENO) != 1 {
+            return None;
+        }
+        libc::getpgrp()
+    };
+    if unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) } != owner {
+        return None;
+    }
+    set_terminal_owner(pgid);
+    Some(owner)
+}
+
+#[cfg(not(unix))]
+fn take_terminal(_pgid: i32) -> Option<i32> {
+    None
+}
+
+#[cfg(unix)]
+fn set_terminal_owner(pgid: i32) {
+    unsafe {
+        // A process outside of the foreground group gets SIGTTOU for changing it
+        let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+        libc::signal(libc::SIGTTOU, previous);
+    }
+}
+
+#[cfg(not(unix))]
+fn set_terminal_owner(_pgid: i32) {}
@@ -0,0 +1,9 @@
+//! Helpers shared by the unit tests of several modules.
+
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// Serialize the tests that change or depend on the process working directory.
+pub(crate) fn lock_current_dir() -> MutexGuard<'static, ()> {
+    static MUTEX: OnceLock<Mutex<()>> = OnceLock::new();
+    MUTEX.get_or_init(|| Mutex::new(())).lock().unwrap()
+}
@@ -0,0 +1,35 @@
+#![cfg(unix)]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn sigint_interrupts_the_running_command_but_not_the_shell() {
+    let mut shell = Command::new(env!("CARGO_BIN_EXE_shell_commands"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("spawn the shell");
+    shell
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"sleep 5\necho status $?\n")
+        .unwrap();
+
+    // Give the shell time to start `sleep`
+    thread::sleep(Duration::from_millis(500));
+    let started = Instant::now();
+    unsafe {
+        libc::kill(shell.id() as i32, libc::SIGINT);
+    }
+
+    let output = shell.wait_with_output().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(4), "sleep wasn't interrupted");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("status 130"), "unexpected output: {:?}", stdout);
+}
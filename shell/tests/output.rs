@@ -0,0 +1,24 @@
+#![cfg(unix)]
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[test]
+fn output_without_trailing_newline_comes_before_later_programs() {
+    let mut shell = Command::new(env!("CARGO_BIN_EXE_shell_commands"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("spawn the shell");
+    shell
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"echo -n a; sh -c \"echo b\"\necho -n c | cat; sh -c \"echo d\"\n")
+        .unwrap();
+
+    let output = shell.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("ab\ncd\n"), "unexpected output: {:?}", stdout);
+}